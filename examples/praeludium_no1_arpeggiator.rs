@@ -0,0 +1,123 @@
+use std::fs::File;
+
+use rust_music::{
+    composition::{Arpeggiator, ArpeggioPattern},
+    compute_pitch,
+    dynamic::*,
+    rhythm::*,
+    Accidental as Acc, Chord, Instrument, Metadata, Mode, NoteName as NN, Part, Phrase, Result,
+    Score, Tempo,
+};
+
+// This example requires the `composition` feature.
+fn main() {
+    let score = praeludium().unwrap();
+    let out_file = File::create("praeludium_arpeggiator.mid").unwrap();
+    score.write_midi_file(out_file).unwrap()
+}
+
+/// Defines Bach's Praeludium No. 1 as a progression of chords that are arpeggiated
+/// with a custom pattern by an `Arpeggiator`.
+fn praeludium() -> Result<Score> {
+    // Each half bar plays the five notes of the chord as: 1 2 3 4 5 3 4 5
+    let arpeggiator = Arpeggiator::new(
+        ArpeggioPattern::Custom(vec![0, 1, 2, 3, 4, 2, 3, 4]),
+        SEMIQUAVER,
+    )?;
+
+    let mut part = Part::new(Instrument::AcousticGrandPiano);
+    part.add_phrase(arpeggiator.arpeggiate(&progression()?)?, 0.);
+
+    let mut score = Score::new(
+        "Praeludium No 1 in C Major",
+        Tempo::new(96)?,
+        Some(Metadata {
+            key_signature: NN::C as i8,
+            mode: Mode::Major,
+            time_numerator: 4,
+            time_denominator: 4,
+        }),
+    );
+    score.add_part(part);
+    Ok(score)
+}
+
+fn progression() -> Result<Phrase> {
+    let bars = [
+        [
+            (NN::C, Acc::Natural, 4),
+            (NN::E, Acc::Natural, 4),
+            (NN::G, Acc::Natural, 4),
+            (NN::C, Acc::Natural, 5),
+            (NN::E, Acc::Natural, 5),
+        ],
+        [
+            (NN::C, Acc::Natural, 4),
+            (NN::D, Acc::Natural, 4),
+            (NN::A, Acc::Natural, 4),
+            (NN::D, Acc::Natural, 5),
+            (NN::F, Acc::Natural, 5),
+        ],
+        [
+            (NN::B, Acc::Natural, 3),
+            (NN::D, Acc::Natural, 4),
+            (NN::G, Acc::Natural, 4),
+            (NN::D, Acc::Natural, 5),
+            (NN::F, Acc::Natural, 5),
+        ],
+        [
+            (NN::C, Acc::Natural, 4),
+            (NN::E, Acc::Natural, 4),
+            (NN::G, Acc::Natural, 4),
+            (NN::C, Acc::Natural, 5),
+            (NN::E, Acc::Natural, 5),
+        ],
+        [
+            (NN::C, Acc::Natural, 4),
+            (NN::E, Acc::Natural, 4),
+            (NN::A, Acc::Natural, 4),
+            (NN::E, Acc::Natural, 5),
+            (NN::A, Acc::Natural, 5),
+        ],
+        [
+            (NN::C, Acc::Natural, 4),
+            (NN::D, Acc::Natural, 4),
+            (NN::F, Acc::Sharp, 4),
+            (NN::A, Acc::Natural, 4),
+            (NN::D, Acc::Natural, 5),
+        ],
+        [
+            (NN::B, Acc::Natural, 3),
+            (NN::D, Acc::Natural, 4),
+            (NN::G, Acc::Natural, 4),
+            (NN::D, Acc::Natural, 5),
+            (NN::G, Acc::Natural, 5),
+        ],
+        [
+            (NN::B, Acc::Natural, 3),
+            (NN::C, Acc::Natural, 4),
+            (NN::E, Acc::Natural, 4),
+            (NN::G, Acc::Natural, 4),
+            (NN::C, Acc::Natural, 5),
+        ],
+        [
+            (NN::A, Acc::Natural, 3),
+            (NN::C, Acc::Natural, 4),
+            (NN::E, Acc::Natural, 4),
+            (NN::G, Acc::Natural, 4),
+            (NN::C, Acc::Natural, 5),
+        ],
+    ];
+    let mut phrase = Phrase::new();
+    for bar in bars {
+        let pitches = bar
+            .iter()
+            .map(|(name, acc, octave)| compute_pitch(*name, *acc, *octave))
+            .collect::<Result<Vec<_>>>()?;
+        // each chord is arpeggiated twice per bar
+        for _ in 0..=1 {
+            phrase.add_chord(Chord::from_pitches(MINIM, MF, &pitches)?);
+        }
+    }
+    Ok(phrase)
+}
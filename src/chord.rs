@@ -75,4 +75,349 @@ impl Chord {
     pub fn notes(&self) -> &[Note] {
         self.notes.as_slice()
     }
+
+    /// Returns the root of the `Chord`, guessed from the intervals between its notes
+    /// (the pitch class above which the other notes best stack in thirds and fifths).
+    /// The returned pitch is the lowest note of the `Chord` with that pitch class.
+    pub fn root(&self) -> u7 {
+        let root_class = self.root_class();
+        self.notes
+            .iter()
+            .map(Note::pitch)
+            .filter(|p| p.as_int() % 12 == root_class)
+            .min()
+            .unwrap_or_default()
+    }
+
+    /// Returns the `Chord` in close position with its root in the bass.
+    /// This is the same as `inversion(0)`.
+    pub fn root_position(&self) -> Chord {
+        self.voiced(self.stacked(self.root_class()))
+    }
+
+    /// Returns the `Chord` in close position with the given chord member in the bass,
+    /// around the register of the current bass note.
+    /// The notes keep their rhythm values and dynamics, and are sorted by pitch.
+    ///
+    /// # Arguments
+    ///
+    /// * `inversion` - `0` for root position, `1` for the first inversion (third in the
+    ///   bass), `2` for the second inversion (fifth in the bass), `3` for the third
+    ///   inversion (seventh in the bass), etc.
+    ///
+    /// # Errors
+    ///
+    /// * `ChordError::InvalidInversion` if the `Chord` does not have enough distinct
+    ///   pitch classes for the requested inversion
+    pub fn inversion(&self, inversion: usize) -> Result<Chord> {
+        let members = self.members();
+        let bass_class = members
+            .get(inversion)
+            .ok_or(ChordError::InvalidInversion(inversion))?;
+        Ok(self.voiced(self.stacked(*bass_class)))
+    }
+
+    /// Returns the `Chord` in close position (all the notes as close as possible
+    /// above the bass), keeping the same bass pitch class
+    pub fn close_position(&self) -> Chord {
+        self.voiced(self.close_position_notes())
+    }
+
+    /// Returns the `Chord` in open position: the close position with every other note
+    /// above the bass raised by one octave (e.g. `C3 E3 G3` becomes `C3 G3 E4`)
+    ///
+    /// # Errors
+    ///
+    /// * `ChordError::NotEnoughNotes` if the `Chord` has fewer than 3 notes
+    pub fn open_position(&self) -> Result<Chord> {
+        if self.notes.len() < 3 {
+            return Err(ChordError::NotEnoughNotes(3).into());
+        }
+        let mut voiced = self.close_position_notes();
+        for v in voiced.iter_mut().skip(1).step_by(2) {
+            v.1 += 12;
+        }
+        Ok(self.voiced(voiced))
+    }
+
+    /// Returns the drop-2 voicing of the `Chord`: the close position with its
+    /// second highest note dropped by one octave
+    ///
+    /// # Errors
+    ///
+    /// * `ChordError::NotEnoughNotes` if the `Chord` has fewer than 3 notes
+    pub fn drop2(&self) -> Result<Chord> {
+        self.drop(2)
+    }
+
+    /// Returns the drop-3 voicing of the `Chord`: the close position with its
+    /// third highest note dropped by one octave
+    ///
+    /// # Errors
+    ///
+    /// * `ChordError::NotEnoughNotes` if the `Chord` has fewer than 4 notes
+    pub fn drop3(&self) -> Result<Chord> {
+        self.drop(3)
+    }
+
+    /// Returns the `Chord` spread as evenly as possible between two pitches, keeping
+    /// the order of the close position (bass first)
+    ///
+    /// # Arguments
+    ///
+    /// * `lowest` - The lowest pitch allowed
+    /// * `highest` - The highest pitch allowed
+    ///
+    /// # Errors
+    ///
+    /// * `ChordError::RangeTooSmall` if the notes cannot fit in ascending order in the range
+    pub fn spread(&self, lowest: u7, highest: u7) -> Result<Chord> {
+        let (low, high) = (lowest.as_int() as i32, highest.as_int() as i32);
+        let mut voiced = self.close_position_notes();
+        let last = voiced.len().max(2) as i32 - 1;
+        let mut min_pitch = low;
+        for (i, v) in voiced.iter_mut().enumerate() {
+            let target = low + (high - low) * i as i32 / last;
+            let mut pitch = min_pitch + (v.1 - min_pitch).rem_euclid(12);
+            while pitch + 12 <= high && (pitch + 12 - target).abs() < (pitch - target).abs() {
+                pitch += 12;
+            }
+            if pitch > high {
+                return Err(ChordError::RangeTooSmall(lowest.as_int(), highest.as_int()).into());
+            }
+            v.1 = pitch;
+            min_pitch = pitch + 1;
+        }
+        Ok(self.voiced(voiced))
+    }
+
+    /// Returns the voicing of the `Chord` that minimises the total movement of the
+    /// voices from `previous`. Each note keeps its pitch class but can change octave.
+    /// If `previous` has no notes, returns the close position of the `Chord`.
+    pub fn closest_voicing_to(&self, previous: &Chord) -> Chord {
+        if previous.notes.is_empty() {
+            return self.close_position();
+        }
+        let mut targets: Vec<i32> = previous
+            .notes
+            .iter()
+            .map(|n| n.pitch().as_int() as i32)
+            .collect();
+        targets.sort_unstable();
+        let close = self.close_position_notes();
+        let classes: Vec<i32> = close.iter().map(|v| v.1.rem_euclid(12)).collect();
+        let nearest = |class: i32, target: i32| {
+            let below = target - (target - class).rem_euclid(12);
+            if target - below > 6 {
+                below + 12
+            } else {
+                below
+            }
+        };
+
+        let pitches = if classes.len() == targets.len() && classes.len() <= 6 {
+            // try every assignment of the notes to the previous voices
+            let mut best = (i32::MAX, Vec::new());
+            let mut assignment = Vec::with_capacity(classes.len());
+            let mut used = vec![false; targets.len()];
+            best_assignment(
+                &classes,
+                &targets,
+                &nearest,
+                &mut assignment,
+                &mut used,
+                0,
+                &mut best,
+            );
+            best.1
+        } else {
+            // spread the notes over the previous voices, from the bass
+            let last = classes.len().max(2) - 1;
+            let target_last = targets.len() - 1;
+            classes
+                .iter()
+                .enumerate()
+                .map(|(i, c)| nearest(*c, targets[(i * target_last + last / 2) / last]))
+                .collect()
+        };
+        self.voiced(close.into_iter().map(|v| v.0).zip(pitches).collect())
+    }
+
+    /// Returns the pitch class of the root of the `Chord`
+    fn root_class(&self) -> u8 {
+        let mut classes: Vec<u8> = Vec::new();
+        let mut pitches: Vec<u8> = self.notes.iter().map(|n| n.pitch().as_int()).collect();
+        pitches.sort_unstable();
+        for p in pitches {
+            if !classes.contains(&(p % 12)) {
+                classes.push(p % 12);
+            }
+        }
+        let score = |root: u8| -> i32 {
+            classes
+                .iter()
+                .map(|c| match (c + 12 - root) % 12 {
+                    3 | 4 | 7 => 3,
+                    6 | 10 | 11 => 1,
+                    1 => -1,
+                    _ => 0,
+                })
+                .sum()
+        };
+        // the first best candidate is kept, which favors the lowest notes
+        let mut best = (i32::MIN, 0);
+        for c in classes.iter() {
+            let s = score(*c);
+            if s > best.0 {
+                best = (s, *c);
+            }
+        }
+        best.1
+    }
+
+    /// Returns the distinct pitch classes of the `Chord`, ordered by
+    /// interval above the root (root, third, fifth, seventh...)
+    fn members(&self) -> Vec<u8> {
+        let root = self.root_class();
+        let mut members: Vec<u8> = Vec::new();
+        for n in self.notes.iter() {
+            let class = n.pitch().as_int() % 12;
+            if !members.contains(&class) {
+                members.push(class);
+            }
+        }
+        members.sort_by_key(|c| (c + 12 - root) % 12);
+        members
+    }
+
+    /// Returns the notes stacked in close position above the given bass pitch class,
+    /// around the register of the current bass, sorted by pitch
+    fn stacked(&self, bass_class: u8) -> Vec<(Note, i32)> {
+        let mut notes = self.notes.clone();
+        notes.sort_by_key(|n| {
+            let pitch = n.pitch().as_int();
+            ((pitch % 12 + 12 - bass_class) % 12, pitch)
+        });
+        let lowest = self.notes.iter().map(|n| n.pitch().as_int() as i32).min();
+        let mut previous = lowest.unwrap_or_default() - 1;
+        notes
+            .into_iter()
+            .map(|n| {
+                let class = (n.pitch().as_int() % 12) as i32;
+                previous += 1 + (class - previous - 1).rem_euclid(12);
+                (n, previous)
+            })
+            .collect()
+    }
+
+    /// Returns the notes in close position with the current bass, sorted by pitch
+    fn close_position_notes(&self) -> Vec<(Note, i32)> {
+        let bass = self.notes.iter().map(Note::pitch).min().unwrap_or_default();
+        self.stacked(bass.as_int() % 12)
+    }
+
+    /// Returns the close position with its `nth` highest note dropped by one octave
+    fn drop(&self, nth: usize) -> Result<Chord> {
+        if self.notes.len() < nth.max(3) {
+            return Err(ChordError::NotEnoughNotes(nth.max(3)).into());
+        }
+        let mut voiced = self.close_position_notes();
+        let idx = voiced.len() - nth;
+        voiced[idx].1 -= 12;
+        Ok(self.voiced(voiced))
+    }
+
+    /// Returns a `Chord` with the same rhythm made of the given notes moved to the
+    /// associated pitches (transposed by octaves if needed to fit in the MIDI range),
+    /// sorted by pitch
+    fn voiced(&self, voiced: Vec<(Note, i32)>) -> Chord {
+        let lowest = voiced.iter().map(|v| v.1).min().unwrap_or_default();
+        let highest = voiced.iter().map(|v| v.1).max().unwrap_or_default();
+        let shift = if highest > 127 {
+            -12 * ((highest - 127 + 11) / 12)
+        } else if lowest < 0 {
+            12 * ((11 - lowest) / 12)
+        } else {
+            0
+        };
+        let mut notes: Vec<Note> = voiced
+            .into_iter()
+            .map(|(n, p)| n.with_pitch(u7::new((p + shift).clamp(0, 127) as u8)))
+            .collect();
+        notes.sort_by_key(Note::pitch);
+        Chord {
+            rhythm: self.rhythm,
+            notes,
+        }
+    }
+}
+
+/// Finds the assignment of pitch `classes` to distinct `targets` that minimises the
+/// total distance between each target and the nearest pitch of its assigned class
+fn best_assignment<F: Fn(i32, i32) -> i32>(
+    classes: &[i32],
+    targets: &[i32],
+    nearest: &F,
+    assignment: &mut Vec<i32>,
+    used: &mut [bool],
+    cost: i32,
+    best: &mut (i32, Vec<i32>),
+) {
+    if cost >= best.0 {
+        return;
+    }
+    let Some(class) = classes.get(assignment.len()) else {
+        *best = (cost, assignment.clone());
+        return;
+    };
+    for (t, target) in targets.iter().enumerate() {
+        if used[t] {
+            continue;
+        }
+        let pitch = nearest(*class, *target);
+        used[t] = true;
+        assignment.push(pitch);
+        let new_cost = cost + (pitch - target).abs();
+        best_assignment(classes, targets, nearest, assignment, used, new_cost, best);
+        assignment.pop();
+        used[t] = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::num::u7;
+    use crate::*;
+
+    fn pitches(c: &Chord) -> Vec<u8> {
+        c.notes().iter().map(|n| n.pitch().as_int()).collect()
+    }
+
+    #[test]
+    fn voicings() -> Result<()> {
+        // C major 7th given in first inversion: E3 G3 B3 C4
+        let cmaj7 =
+            Chord::from_pitches(rhythm::MINIM, dynamic::MF, &[48, 43, 47, 40].map(u7::new))?;
+        assert_eq!(cmaj7.root(), u7::new(48));
+        assert_eq!(pitches(&cmaj7.root_position()), vec![48, 52, 55, 59]);
+        assert_eq!(pitches(&cmaj7.inversion(1)?), vec![40, 43, 47, 48]);
+        assert_eq!(pitches(&cmaj7.inversion(3)?), vec![47, 48, 52, 55]);
+        assert!(cmaj7.inversion(4).is_err());
+        assert_eq!(pitches(&cmaj7.close_position()), vec![40, 43, 47, 48]);
+        assert_eq!(pitches(&cmaj7.open_position()?), vec![40, 47, 55, 60]);
+        assert_eq!(pitches(&cmaj7.drop2()?), vec![35, 40, 43, 48]);
+        assert_eq!(pitches(&cmaj7.drop3()?), vec![31, 40, 47, 48]);
+        assert_eq!(
+            pitches(&cmaj7.spread(u7::new(36), u7::new(72))?),
+            vec![40, 43, 59, 72]
+        );
+        assert!(cmaj7.spread(u7::new(60), u7::new(64)).is_err());
+
+        // G7 (G4 B4 D5 F5) moves to C major (C5 E5 G5 C6)
+        let g7 = Chord::from_pitches(rhythm::MINIM, dynamic::MF, &[55, 59, 62, 65].map(u7::new))?;
+        let c = Chord::from_pitches(rhythm::MINIM, dynamic::MF, &[60, 64, 67, 72].map(u7::new))?;
+        assert_eq!(pitches(&c.closest_voicing_to(&g7)), vec![55, 60, 60, 64]);
+        assert_eq!(c.closest_voicing_to(&Chord::default()), c.close_position());
+        Ok(())
+    }
 }
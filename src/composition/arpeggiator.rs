@@ -0,0 +1,254 @@
+use crate::composition::random::Rng;
+use crate::errors::NoteError;
+use crate::num::u7;
+use crate::{Chord, Note, Phrase, PhraseEntry, Result};
+
+/// Describes the order in which an `Arpeggiator` plays the notes of a chord
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+pub enum ArpeggioPattern {
+    /// From the lowest note to the highest
+    #[default]
+    Up,
+    /// From the highest note to the lowest
+    Down,
+    /// Up then down, without repeating the highest and lowest notes
+    UpDown,
+    /// Random notes, chosen with the given seed
+    Random(u64),
+    /// In the order of the notes in the `Chord`
+    AsPlayed,
+    /// Custom sequence of indices in the notes of the chord sorted from the lowest
+    /// (including the additional octaves). Indices larger than the number of notes
+    /// wrap around.
+    Custom(Vec<usize>),
+}
+
+/// Turns chords into sequences of single notes
+#[derive(Debug, Clone, PartialEq)]
+pub struct Arpeggiator {
+    /// The order of the notes
+    pattern: ArpeggioPattern,
+    /// The rhythm value of each step
+    step: f64,
+    /// The number of octaves covered by the arpeggio
+    octaves: u8,
+    /// The proportion of each step during which the note is played
+    gate: f64,
+}
+
+impl Arpeggiator {
+    /// Returns a new `Arpeggiator` covering one octave with a gate of `1.0`
+    ///
+    /// # Arguments
+    ///
+    /// * `pattern` - The order in which the notes are played
+    /// * `step` - The rhythm value of each note of the arpeggio
+    ///
+    /// # Errors
+    ///
+    /// * `NoteError::InvalidRhythm` if `step` is below `0.000_001`
+    pub fn new(pattern: ArpeggioPattern, step: f64) -> Result<Self> {
+        if step < 0.000_001 {
+            return Err(NoteError::InvalidRhythm(step).into());
+        }
+        Ok(Self {
+            pattern,
+            step,
+            octaves: 1,
+            gate: 1.,
+        })
+    }
+
+    /// Sets the number of octaves covered by the arpeggio. With more than one octave,
+    /// the notes of the chord are repeated on the octaves above. `0` is treated as `1`.
+    pub fn set_octaves(&mut self, octaves: u8) {
+        self.octaves = octaves.max(1);
+    }
+
+    /// Sets the proportion of each step during which the note is played.
+    /// Below `1.0`, the notes are followed by a rest (staccato). Above `1.0`, the notes
+    /// keep playing while the next ones start (legato).
+    ///
+    /// # Errors
+    ///
+    /// * `NoteError::InvalidRhythm` if the resulting note length is below `0.000_001`
+    pub fn set_gate(&mut self, gate: f64) -> Result<()> {
+        if gate * self.step < 0.000_001 {
+            return Err(NoteError::InvalidRhythm(gate * self.step).into());
+        }
+        self.gate = gate;
+        Ok(())
+    }
+
+    /// Returns a `Phrase` that arpeggiates the `Chord` during its rhythm value
+    ///
+    /// # Errors
+    ///
+    /// * Any error returned when creating the notes
+    pub fn arpeggiate_chord(&self, chord: &Chord) -> Result<Phrase> {
+        let mut phrase = Phrase::new();
+        let mut rng = self.rng();
+        self.add_arpeggio(&mut phrase, chord.notes(), chord.rhythm(), &mut rng)?;
+        Ok(phrase)
+    }
+
+    /// Returns a `Phrase` in which each `Chord` of `phrase` is arpeggiated during
+    /// its rhythm value. Single notes are repeated on their octaves (if more than one)
+    /// and rests are kept.
+    ///
+    /// # Errors
+    ///
+    /// * Any error returned when creating the notes
+    pub fn arpeggiate(&self, phrase: &Phrase) -> Result<Phrase> {
+        let mut arpeggio = Phrase::new();
+        arpeggio.set_name(phrase.name());
+        let mut rng = self.rng();
+        for entry in phrase.entries() {
+            match entry {
+                PhraseEntry::Chord(c) => {
+                    self.add_arpeggio(&mut arpeggio, c.notes(), c.rhythm(), &mut rng)?
+                }
+                PhraseEntry::Note(n) => {
+                    self.add_arpeggio(&mut arpeggio, std::slice::from_ref(n), n.rhythm(), &mut rng)?
+                }
+                PhraseEntry::Rest(r) => arpeggio.add_rest(*r),
+            }
+        }
+        Ok(arpeggio)
+    }
+
+    /// Returns the random generator used by the `Random` pattern
+    fn rng(&self) -> Rng {
+        match self.pattern {
+            ArpeggioPattern::Random(seed) => Rng::new(seed),
+            _ => Rng::new(0),
+        }
+    }
+
+    /// Adds the arpeggio of `notes` lasting `length` beats at the end of `phrase`
+    fn add_arpeggio(
+        &self,
+        phrase: &mut Phrase,
+        notes: &[Note],
+        length: f64,
+        rng: &mut Rng,
+    ) -> Result<()> {
+        let mut sorted = notes.to_vec();
+        if self.pattern != ArpeggioPattern::AsPlayed {
+            sorted.sort_by_key(Note::pitch);
+        }
+        let mut pool: Vec<Note> = Vec::with_capacity(sorted.len() * self.octaves as usize);
+        for octave in 0..self.octaves as u32 {
+            let lowest = sorted.iter().map(|n| n.pitch().as_int()).min().unwrap_or(0);
+            if lowest as u32 + 12 * octave > 127 {
+                break;
+            }
+            for n in sorted.iter() {
+                let pitch = n.pitch().as_int() as u32 + 12 * octave;
+                if pitch <= 127 {
+                    pool.push(n.with_pitch(u7::new(pitch as u8)));
+                }
+            }
+        }
+        if pool.is_empty() {
+            phrase.add_rest(length);
+            return Ok(());
+        }
+        let order: Vec<usize> = match &self.pattern {
+            ArpeggioPattern::Up | ArpeggioPattern::AsPlayed | ArpeggioPattern::Random(_) => {
+                (0..pool.len()).collect()
+            }
+            ArpeggioPattern::Down => (0..pool.len()).rev().collect(),
+            ArpeggioPattern::UpDown => (0..pool.len())
+                .chain((1..pool.len().saturating_sub(1)).rev())
+                .collect(),
+            ArpeggioPattern::Custom(indices) => indices.iter().map(|i| i % pool.len()).collect(),
+        };
+        if order.is_empty() {
+            phrase.add_rest(length);
+            return Ok(());
+        }
+
+        let mut elapsed = 0.;
+        let mut step = 0;
+        while length - elapsed >= 0.000_001 {
+            let index = match self.pattern {
+                ArpeggioPattern::Random(_) => rng.below(pool.len()),
+                _ => order[step % order.len()],
+            };
+            let source = &pool[index];
+            let duration = self.step.min(length - elapsed);
+            let sounding = (self.step * self.gate).min(length - elapsed);
+            let note = Note::new(source.pitch(), sounding, source.dynamic())?;
+            if sounding > duration {
+                phrase.add_chord(Chord::new(duration, vec![note])?);
+            } else {
+                phrase.add_note(note);
+                if duration - sounding >= 0.000_001 {
+                    phrase.add_rest(duration - sounding);
+                }
+            }
+            elapsed += duration;
+            step += 1;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Arpeggiator, ArpeggioPattern};
+    use crate::num::u7;
+    use crate::*;
+
+    fn pitches(phrase: &Phrase) -> Vec<u8> {
+        phrase
+            .entries()
+            .iter()
+            .filter_map(|e| match e {
+                PhraseEntry::Note(n) => Some(n.pitch().as_int()),
+                PhraseEntry::Chord(c) => Some(c.notes()[0].pitch().as_int()),
+                PhraseEntry::Rest(_) => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn arpeggiate() -> Result<()> {
+        let c_major =
+            Chord::from_pitches(rhythm::SEMIBREVE, dynamic::MF, &[67, 60, 64].map(u7::new))?;
+
+        let mut up_down = Arpeggiator::new(ArpeggioPattern::UpDown, rhythm::QUAVER)?;
+        up_down.set_octaves(2);
+        let phrase = up_down.arpeggiate_chord(&c_major)?;
+        assert_eq!(pitches(&phrase), vec![60, 64, 67, 72, 76, 79, 76, 72]);
+        assert_eq!(phrase.duration(), 4.);
+
+        let mut as_played = Arpeggiator::new(ArpeggioPattern::AsPlayed, rhythm::CROTCHET)?;
+        as_played.set_gate(0.5)?;
+        let phrase = as_played.arpeggiate_chord(&c_major)?;
+        assert_eq!(pitches(&phrase), vec![67, 60, 64, 67]);
+        assert_eq!(phrase.entries().len(), 8);
+
+        let random = Arpeggiator::new(ArpeggioPattern::Random(42), rhythm::SEMIQUAVER)?;
+        assert_eq!(
+            pitches(&random.arpeggiate_chord(&c_major)?),
+            pitches(&random.arpeggiate_chord(&c_major)?)
+        );
+
+        // the octaves above the MIDI range are left out
+        let mut high = Arpeggiator::new(ArpeggioPattern::Up, rhythm::QUAVER)?;
+        high.set_octaves(11);
+        let chord = Chord::from_pitches(rhythm::MINIM, dynamic::MF, &[115, 124].map(u7::new))?;
+        assert_eq!(
+            pitches(&high.arpeggiate_chord(&chord)?),
+            vec![115, 124, 127, 115]
+        );
+
+        // a chord without notes is a rest
+        let custom = Arpeggiator::new(ArpeggioPattern::Custom(vec![0, 2]), rhythm::QUAVER)?;
+        let phrase = custom.arpeggiate_chord(&Chord::default())?;
+        assert!(matches!(phrase.entries(), [PhraseEntry::Rest(_)]));
+        Ok(())
+    }
+}
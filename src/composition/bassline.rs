@@ -0,0 +1,335 @@
+use crate::composition::random::Rng;
+use crate::composition::Harmony;
+use crate::errors::NoteError;
+use crate::num::u7;
+use crate::{Instrument, Note, Part, Phrase, Result};
+
+/// Describes how a `BassLine` plays each chord of a progression
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BassStyle {
+    /// The bass of the chord held for the whole chord
+    #[default]
+    Root,
+    /// The bass of the chord for the first half of the chord, its closest fifth for the
+    /// second half
+    RootFifth,
+    /// One note per beat, alternating the bass of the chord and its fifth below
+    /// (country "boom-chick" bass)
+    Alternating,
+    /// One note per beat, going up the notes of the chord
+    Arpeggiated,
+    /// One note per beat, starting each chord on its bass, walking through chord tones
+    /// and ending on a chromatic approach note to the next chord (jazz walking bass)
+    Walking,
+}
+
+/// Generates bass parts from chord progressions
+#[derive(Debug, Clone, PartialEq)]
+pub struct BassLine {
+    /// How each chord is played
+    style: BassStyle,
+    /// The lowest and highest pitches of the bass
+    range: (u7, u7),
+    /// The instrument of the generated parts
+    instrument: Instrument,
+    /// The dynamic of the notes
+    dynamic: u7,
+}
+
+impl BassLine {
+    /// Returns a new `BassLine` in the range of an acoustic bass (pitches 28 to 55)
+    /// played by `Instrument::AcousticBass`
+    pub fn new(style: BassStyle) -> Self {
+        Self {
+            style,
+            range: (u7::new(28), u7::new(55)),
+            instrument: Instrument::AcousticBass,
+            dynamic: crate::dynamic::MF,
+        }
+    }
+
+    /// Sets the lowest and highest pitches of the bass (at least an octave apart)
+    pub fn set_range(&mut self, lowest: u7, highest: u7) {
+        let lowest = lowest.min(highest).as_int().min(115);
+        let highest = lowest.max(highest.as_int()).max(lowest + 12);
+        self.range = (u7::new(lowest), u7::new(highest));
+    }
+
+    /// Sets the instrument of the generated parts
+    pub fn set_instrument(&mut self, instrument: Instrument) {
+        self.instrument = instrument;
+    }
+
+    /// Sets the dynamic of the notes
+    pub fn set_dynamic(&mut self, dynamic: u7) {
+        self.dynamic = dynamic;
+    }
+
+    /// Returns the bass line of a chord progression as a `Phrase`
+    ///
+    /// # Arguments
+    ///
+    /// * `progression` - The chords (`ChordSymbol`s, `&Chord`s, sets of pitch classes
+    ///   or `Harmony`s) with their duration in beats. Chords without pitch classes
+    ///   are rests.
+    /// * `seed` - The seed of the random choices of the walking bass
+    ///
+    /// # Errors
+    ///
+    /// * `NoteError::InvalidRhythm` if a duration is below `0.000_001`
+    pub fn to_phrase<H: Into<Harmony> + Clone>(
+        &self,
+        progression: &[(H, f64)],
+        seed: u64,
+    ) -> Result<Phrase> {
+        let mut rng = Rng::new(seed);
+        let harmonies: Vec<(Harmony, f64)> = progression
+            .iter()
+            .map(|(h, d)| (h.clone().into(), *d))
+            .collect();
+        let mut phrase = Phrase::new();
+        // start the line around the lower third of the range
+        let (low, high) = (self.range.0.as_int(), self.range.1.as_int());
+        let mut previous = low + (high - low) / 3;
+        for (index, (harmony, duration)) in harmonies.iter().enumerate() {
+            if *duration < 0.000_001 {
+                return Err(NoteError::InvalidRhythm(*duration).into());
+            }
+            let Some(root) = harmony.classes().first().copied() else {
+                phrase.add_rest(*duration);
+                continue;
+            };
+            let bass = harmony.bass().unwrap_or(root);
+            let fifth = chord_tone(harmony, root, &[7, 6, 8]).unwrap_or((root + 7) % 12);
+            let beats = num_beats(*duration);
+            let pitches: Vec<u8> = match self.style {
+                BassStyle::Root => vec![self.place(bass, previous)],
+                BassStyle::RootFifth => {
+                    let first = self.place(bass, previous);
+                    if *duration < 2. - 0.000_001 {
+                        vec![first]
+                    } else {
+                        vec![first, self.place(fifth, first)]
+                    }
+                }
+                BassStyle::Alternating => {
+                    let first = self.place(bass, previous);
+                    let below = self.place_below(fifth, first);
+                    (0..beats)
+                        .map(|i| if i % 2 == 0 { first } else { below })
+                        .collect()
+                }
+                BassStyle::Arpeggiated => {
+                    let mut tones = vec![bass];
+                    tones.extend(
+                        [&[3, 4][..], &[7, 6, 8], &[10, 11, 9]]
+                            .iter()
+                            .filter_map(|i| chord_tone(harmony, root, i))
+                            .filter(|c| *c != bass),
+                    );
+                    let mut pitch = self.place(bass, previous);
+                    let mut line = vec![pitch];
+                    for i in 1..beats {
+                        pitch = self.place_above(tones[i % tones.len()], pitch);
+                        line.push(pitch);
+                    }
+                    line
+                }
+                BassStyle::Walking => {
+                    let next_root = harmonies[index + 1..]
+                        .iter()
+                        .find_map(|(h, _)| h.bass().or(h.classes().first().copied()));
+                    self.walk(harmony, bass, next_root, beats, previous, &mut rng)
+                }
+            };
+            self.add_notes(&mut phrase, &pitches, *duration)?;
+            previous = *pitches.last().unwrap_or(&previous);
+        }
+        Ok(phrase)
+    }
+
+    /// Returns the bass line of a chord progression as a `Part` (see `to_phrase`)
+    ///
+    /// # Errors
+    ///
+    /// * Same as `to_phrase`
+    pub fn to_part<H: Into<Harmony> + Clone>(
+        &self,
+        progression: &[(H, f64)],
+        seed: u64,
+    ) -> Result<Part> {
+        let mut part = Part::new(self.instrument);
+        part.set_name("Bass");
+        part.add_phrase(self.to_phrase(progression, seed)?, 0.);
+        Ok(part)
+    }
+
+    /// Returns the walking line of a chord: its bass, chord tones moving toward the next
+    /// chord and a chromatic approach note (a semitone above or below the next bass)
+    fn walk(
+        &self,
+        harmony: &Harmony,
+        bass: u8,
+        next: Option<u8>,
+        num_beats: usize,
+        previous: u8,
+        rng: &mut Rng,
+    ) -> Vec<u8> {
+        let first = self.place(bass, previous);
+        let mut line = vec![first];
+        if num_beats == 1 {
+            return line;
+        }
+        let target = self.place(next.unwrap_or(bass), first);
+        // approach from the side the line comes from, or at random for repeated roots
+        let from_below = match target.cmp(&first) {
+            std::cmp::Ordering::Greater => true,
+            std::cmp::Ordering::Less => false,
+            std::cmp::Ordering::Equal => rng.below(2) == 0,
+        };
+        let approach = if from_below && target > self.range.0.as_int() {
+            target - 1
+        } else if target < self.range.1.as_int() {
+            target + 1
+        } else {
+            target - 1
+        };
+        for i in 1..num_beats - 1 {
+            // ideal position on the way to the approach note
+            let ideal =
+                first as f64 + (approach as f64 - first as f64) * i as f64 / (num_beats - 1) as f64;
+            let last = line[line.len() - 1];
+            let candidates: Vec<u8> = (self.range.0.as_int()..=self.range.1.as_int())
+                .filter(|p| harmony.classes().contains(&(p % 12)) && *p != last && *p != approach)
+                .collect();
+            // the lowest of the chord tones closest to the ideal position
+            let pitch = candidates
+                .iter()
+                .min_by(|a, b| {
+                    (**a as f64 - ideal)
+                        .abs()
+                        .total_cmp(&(**b as f64 - ideal).abs())
+                        .then(a.cmp(b))
+                })
+                .copied()
+                .unwrap_or(last);
+            line.push(pitch);
+        }
+        line.push(approach);
+        // vary the line by choosing randomly the octave of the middle chord tones
+        if num_beats > 3 && rng.below(2) == 1 {
+            let mid = num_beats / 2;
+            let up = line[mid] + 12;
+            if up <= self.range.1.as_int() && up.abs_diff(line[mid - 1]) <= 9 {
+                line[mid] = up;
+            }
+        }
+        line
+    }
+
+    /// Adds one note per pitch to `phrase`, lasting `duration` beats in total:
+    /// one beat each, except the last one that lasts until the end of the chord
+    fn add_notes(&self, phrase: &mut Phrase, pitches: &[u8], duration: f64) -> Result<()> {
+        let each = match self.style {
+            BassStyle::Root => duration,
+            BassStyle::RootFifth if pitches.len() == 2 => duration / 2.,
+            _ => 1.,
+        };
+        let mut elapsed = 0.;
+        for (i, pitch) in pitches.iter().enumerate() {
+            let rhythm = if i == pitches.len() - 1 {
+                duration - elapsed
+            } else {
+                each
+            };
+            phrase.add_note(Note::new(u7::new(*pitch), rhythm, self.dynamic)?);
+            elapsed += rhythm;
+        }
+        Ok(())
+    }
+
+    /// Returns the pitch of pitch class `class` in the range closest to `near`
+    fn place(&self, class: u8, near: u8) -> u8 {
+        (self.range.0.as_int()..=self.range.1.as_int())
+            .filter(|p| p % 12 == class)
+            .min_by_key(|p| (p.abs_diff(near), *p))
+            .unwrap_or(near)
+    }
+
+    /// Returns the pitch of pitch class `class` in the range just below `pitch`
+    /// (or just above if there is none below)
+    fn place_below(&self, class: u8, pitch: u8) -> u8 {
+        (self.range.0.as_int()..pitch)
+            .rev()
+            .find(|p| p % 12 == class)
+            .unwrap_or_else(|| self.place(class, pitch))
+    }
+
+    /// Returns the pitch of pitch class `class` in the range just above `pitch`
+    /// (or just below if there is none above)
+    fn place_above(&self, class: u8, pitch: u8) -> u8 {
+        (pitch + 1..=self.range.1.as_int())
+            .find(|p| p % 12 == class)
+            .unwrap_or_else(|| self.place_below(class, pitch))
+    }
+}
+
+/// Returns the first pitch class of the harmony that is one of the `intervals` above
+/// the root
+fn chord_tone(harmony: &Harmony, root: u8, intervals: &[u8]) -> Option<u8> {
+    intervals
+        .iter()
+        .map(|i| (root + i) % 12)
+        .find(|c| harmony.classes().contains(c))
+}
+
+/// Returns the number of beats of a chord lasting `duration` beats (a last partial
+/// beat counts if it lasts at least half a beat)
+fn num_beats(duration: f64) -> usize {
+    (duration + 0.5 - 0.000_001).floor().max(1.) as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BassLine, BassStyle};
+    use crate::composition::ChordSymbol;
+    use crate::*;
+
+    fn pitches(phrase: &Phrase) -> Vec<u8> {
+        phrase
+            .entries()
+            .iter()
+            .filter_map(|e| match e {
+                PhraseEntry::Note(n) => Some(n.pitch().as_int()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn bass_lines() -> Result<()> {
+        let progression: Vec<(ChordSymbol, f64)> = ["C", "Am7", "Dm7", "G7"]
+            .iter()
+            .map(|s| Ok((s.parse()?, 4.)))
+            .collect::<Result<_>>()?;
+
+        let root_fifth = BassLine::new(BassStyle::RootFifth).to_phrase(&progression, 0)?;
+        assert_eq!(pitches(&root_fifth), vec![36, 31, 33, 28, 38, 33, 31, 38]);
+        assert_eq!(root_fifth.duration(), 16.);
+
+        let alternating = BassLine::new(BassStyle::Alternating).to_phrase(&progression, 0)?;
+        assert_eq!(pitches(&alternating)[..4], [36, 31, 36, 31]);
+
+        let walking = BassLine::new(BassStyle::Walking);
+        let line = walking.to_phrase(&progression, 1)?;
+        let line = pitches(&line);
+        assert_eq!(line.len(), 16);
+        // each chord starts on its root and is approached chromatically
+        for (chord, root) in [(1, 9), (2, 2), (3, 7)] {
+            assert_eq!(line[chord * 4] % 12, root);
+            assert_eq!(line[chord * 4 - 1].abs_diff(line[chord * 4]), 1);
+        }
+        assert!(line.iter().all(|p| (28..=55).contains(p)));
+        Ok(())
+    }
+}
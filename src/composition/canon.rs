@@ -0,0 +1,328 @@
+use crate::composition::{Scale, SnapDirection};
+use crate::errors::NoteError;
+use crate::num::u7;
+use crate::{
+    Chord, Instrument, Note, NoteEvents, Part, Phrase, PhraseEntry, Result, Score, Tempo,
+    TICKS_PER_BEAT,
+};
+
+/// Describes the interval between two consecutive voices of a `Canon`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Imitation {
+    /// Each voice is transposed by the given number of semitones from the previous one
+    Chromatic(i8),
+    /// Each voice is transposed by the given number of steps of the `Scale` from the
+    /// previous one (e.g. `4` for a canon at the fifth). Pitches out of the scale keep
+    /// their distance to the scale pitch below them.
+    Diatonic(Scale, i32),
+}
+
+impl Default for Imitation {
+    fn default() -> Self {
+        Imitation::Chromatic(0)
+    }
+}
+
+/// Describes an interval between two voices of a canon at a given beat
+#[derive(Debug, Clone, PartialEq)]
+pub struct CanonInterval {
+    /// The beat at which the interval starts
+    pub beat: f64,
+    /// The indices of the two voices, leader first
+    pub voices: (usize, usize),
+    /// The interval in semitones, modulo an octave
+    pub interval: u8,
+    /// True if the interval is consonant (unisons, thirds, perfect fourths and fifths,
+    /// sixths)
+    pub consonant: bool,
+}
+
+/// Generates canons (and rounds) from a melody: several voices play the same melody,
+/// each one entering after the previous one, optionally transposed, inverted or
+/// augmented.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Canon {
+    /// The number of voices
+    voices: usize,
+    /// The number of beats between the entries of two consecutive voices
+    entry_offset: f64,
+    /// The interval between two consecutive voices
+    imitation: Imitation,
+    /// The factor applied to the rhythm values of the following voices
+    augmentation: f64,
+    /// True if the following voices play the melody upside down
+    inversion: bool,
+    /// The number of times each voice plays the melody
+    repeats: usize,
+    /// The instrument of each voice (repeated cyclically)
+    instruments: Vec<Instrument>,
+}
+
+impl Canon {
+    /// Returns a new `Canon` at the unison, without augmentation nor inversion, played
+    /// once on the piano
+    ///
+    /// # Arguments
+    ///
+    /// * `voices` - The number of voices
+    /// * `entry_offset` - The number of beats between the entries of two voices
+    pub fn new(voices: usize, entry_offset: f64) -> Self {
+        Self {
+            voices,
+            entry_offset: entry_offset.max(0.),
+            imitation: Imitation::default(),
+            augmentation: 1.,
+            inversion: false,
+            repeats: 1,
+            instruments: vec![Instrument::default()],
+        }
+    }
+
+    /// Sets the interval between two consecutive voices
+    pub fn set_imitation(&mut self, imitation: Imitation) {
+        self.imitation = imitation;
+    }
+
+    /// Sets the factor applied to the rhythm values of all the voices after the first
+    /// (e.g. `2.0` for a canon by augmentation)
+    ///
+    /// # Errors
+    ///
+    /// * `NoteError::InvalidRhythm` if the factor is not positive
+    pub fn set_augmentation(&mut self, factor: f64) -> Result<()> {
+        if factor < 0.000_001 {
+            return Err(NoteError::InvalidRhythm(factor).into());
+        }
+        self.augmentation = factor;
+        Ok(())
+    }
+
+    /// Sets if the voices after the first play the melody upside down, mirrored around
+    /// its first pitch
+    pub fn set_inversion(&mut self, inversion: bool) {
+        self.inversion = inversion;
+    }
+
+    /// Sets the number of times each voice plays the melody (a round usually repeats it)
+    pub fn set_repeats(&mut self, repeats: usize) {
+        self.repeats = repeats;
+    }
+
+    /// Sets the instrument of each voice. If there are fewer instruments than voices,
+    /// they are repeated cyclically.
+    pub fn set_instruments(&mut self, instruments: Vec<Instrument>) {
+        self.instruments = instruments;
+    }
+
+    /// Returns one `Part` per voice, the leader first. Each voice starts `entry_offset`
+    /// beats after the previous one.
+    ///
+    /// # Errors
+    ///
+    /// * `NoteError::InvalidPitch` if a transposed pitch is outside of the MIDI range
+    /// * Any error returned when creating the notes
+    pub fn parts(&self, melody: &Phrase) -> Result<Vec<Part>> {
+        let axis = melody.entries().iter().find_map(|e| match e {
+            PhraseEntry::Note(n) => Some(n.pitch().as_int() as i32),
+            PhraseEntry::Chord(c) => c.notes().first().map(|n| n.pitch().as_int() as i32),
+            PhraseEntry::Rest(_) => None,
+        });
+        (0..self.voices)
+            .map(|voice| {
+                let phrase = self.voice_phrase(melody, voice, axis.unwrap_or(0))?;
+                let instrument = self
+                    .instruments
+                    .get(voice % self.instruments.len().max(1))
+                    .copied()
+                    .unwrap_or_default();
+                let mut part = Part::new(instrument);
+                part.set_name(format!("Voice {}", voice + 1));
+                let mut start = self.entry_offset * voice as f64;
+                for _ in 0..self.repeats {
+                    part.add_phrase(phrase.clone(), start);
+                    start += phrase.duration();
+                }
+                Ok(part)
+            })
+            .collect()
+    }
+
+    /// Returns a `Score` with the parts of the canon
+    ///
+    /// # Errors
+    ///
+    /// * Same as `parts`
+    pub fn to_score(&self, name: &str, melody: &Phrase, tempo: Tempo) -> Result<Score> {
+        let mut score = Score::new(name, tempo, None);
+        for part in self.parts(melody)? {
+            score.add_part(part);
+        }
+        Ok(score)
+    }
+
+    /// Returns the intervals between the voices of the canon while they overlap, at each
+    /// onset of any voice. Only the highest note of `Chord`s is considered.
+    ///
+    /// # Errors
+    ///
+    /// * Same as `parts`
+    pub fn consonance_report(&self, melody: &Phrase) -> Result<Vec<CanonInterval>> {
+        let lines: Vec<Vec<(u64, u64, u8)>> = self
+            .parts(melody)?
+            .iter()
+            .map(|part| {
+                NoteEvents::from_parts([(0, part)], 60)
+                    .highest_per_entry()
+                    .iter()
+                    .map(|e| (e.start_tick, e.end_tick, e.pitch().as_int()))
+                    .collect()
+            })
+            .collect();
+        let mut times: Vec<u64> = lines.iter().flatten().map(|n| n.0).collect();
+        times.sort_unstable();
+        times.dedup();
+
+        let mut report = Vec::new();
+        for time in times {
+            let sounding: Vec<Option<u8>> = lines
+                .iter()
+                .map(|line| line.iter().find(|n| n.0 <= time && time < n.1).map(|n| n.2))
+                .collect();
+            for i in 0..sounding.len() {
+                for j in i + 1..sounding.len() {
+                    let (Some(a), Some(b)) = (sounding[i], sounding[j]) else {
+                        continue;
+                    };
+                    let interval = a.abs_diff(b) % 12;
+                    report.push(CanonInterval {
+                        beat: time as f64 / TICKS_PER_BEAT as f64,
+                        voices: (i, j),
+                        interval,
+                        consonant: !matches!(interval, 1 | 2 | 6 | 10 | 11),
+                    });
+                }
+            }
+        }
+        Ok(report)
+    }
+
+    /// Returns the melody transformed for the given voice
+    fn voice_phrase(&self, melody: &Phrase, voice: usize, axis: i32) -> Result<Phrase> {
+        if voice == 0 {
+            return Ok(melody.clone());
+        }
+        let factor = self.augmentation;
+        let transform = |note: &Note| -> Result<Note> {
+            let pitch = self.transform_pitch(note.pitch().as_int() as i32, voice, axis);
+            if !(0..=127).contains(&pitch) {
+                return Err(NoteError::InvalidPitch(pitch.max(0) as u32).into());
+            }
+            Note::new(u7::new(pitch as u8), note.rhythm() * factor, note.dynamic())
+        };
+        let mut phrase = Phrase::new();
+        phrase.set_name(melody.name());
+        for entry in melody.entries() {
+            match entry {
+                PhraseEntry::Note(n) => phrase.add_note(transform(n)?),
+                PhraseEntry::Chord(c) => {
+                    let notes = c.notes().iter().map(transform).collect::<Result<_>>()?;
+                    phrase.add_chord(Chord::new(c.rhythm() * factor, notes)?)
+                }
+                PhraseEntry::Rest(r) => phrase.add_rest(r * factor),
+            }
+        }
+        Ok(phrase)
+    }
+
+    /// Returns the pitch played by the given voice for a pitch of the melody
+    fn transform_pitch(&self, pitch: i32, voice: usize, axis: i32) -> i32 {
+        let voice = voice as i32;
+        match self.imitation {
+            Imitation::Chromatic(semitones) => {
+                let pitch = if self.inversion {
+                    2 * axis - pitch
+                } else {
+                    pitch
+                };
+                pitch + semitones as i32 * voice
+            }
+            Imitation::Diatonic(scale, steps) => {
+                let (step, offset) = scale_position(&scale, pitch);
+                let (axis_step, _) = scale_position(&scale, axis);
+                let step = if self.inversion {
+                    2 * axis_step - step
+                } else {
+                    step
+                };
+                let offset = if self.inversion { -offset } else { offset };
+                scale.step_to_pitch(step + steps * voice) + offset
+            }
+        }
+    }
+}
+
+/// Returns the scale step of the scale pitch at or below `pitch`, and the distance in
+/// semitones between them
+fn scale_position(scale: &Scale, pitch: i32) -> (i32, i32) {
+    let clamped = u7::new(pitch.clamp(0, 127) as u8);
+    let below = scale
+        .snap(clamped, SnapDirection::Down)
+        .or_else(|| scale.snap(clamped, SnapDirection::Up))
+        .map_or(pitch, |p| p.as_int() as i32);
+    let step = scale.step_of(below).unwrap_or(0);
+    (step, pitch - below)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Canon, Imitation};
+    use crate::composition::{Scale, ScaleMode};
+    use crate::num::u7;
+    use crate::*;
+
+    fn first_pitches(part: &Part) -> Vec<u8> {
+        part.phrases()[0]
+            .1
+            .entries()
+            .iter()
+            .filter_map(|e| match e {
+                PhraseEntry::Note(n) => Some(n.pitch().as_int()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn canon() -> Result<()> {
+        // Frère Jacques
+        let mut melody = Phrase::from_notes_sequence(Note::new_sequence(
+            rhythm::CROTCHET,
+            dynamic::MF,
+            [60, 62, 64, 60, 64, 65].map(u7::new),
+        ))?;
+        melody.add_note(Note::new(u7::new(67), rhythm::MINIM, dynamic::MF)?);
+        let mut round = Canon::new(3, 4.);
+        round.set_repeats(2);
+        round.set_instruments(vec![Instrument::Flute, Instrument::Clarinet]);
+        let parts = round.parts(&melody)?;
+        assert_eq!(parts.len(), 3);
+        assert_eq!(parts[2].instrument(), Instrument::Flute);
+        assert_eq!(parts[2].phrases()[0].0, 8.);
+        assert_eq!(parts[2].duration(), 24.);
+        let report = round.consonance_report(&melody)?;
+        assert!(!report.is_empty());
+        assert!(report.iter().all(|i| i.consonant));
+
+        let mut canon = Canon::new(2, 2.);
+        canon.set_imitation(Imitation::Diatonic(
+            Scale::new(u7::new(60), ScaleMode::Ionian),
+            4,
+        ));
+        canon.set_inversion(true);
+        canon.set_augmentation(2.)?;
+        let parts = canon.parts(&melody)?;
+        assert_eq!(first_pitches(&parts[1]), vec![67, 65, 64, 67, 64, 62, 60]);
+        assert_eq!(parts[1].duration(), 18.);
+        Ok(())
+    }
+}
@@ -0,0 +1,343 @@
+use crate::composition::{Harmony, Scale, ScaleMode, VoiceLeader};
+use crate::errors::HarmonizationError;
+use crate::num::u7;
+use crate::{Instrument, Note, Part, Phrase, PhraseEntry, Result, Score, Tempo};
+
+/// Cost of moving from the chord on a degree (row) to the chord on another
+/// degree (column), following the usual functional progressions
+/// (e.g. ii -> V -> I is cheap, V -> IV is expensive)
+static PROGRESSION_COSTS: [[u32; 7]; 7] = [
+    // I  ii iii IV  V  vi vii
+    [2, 1, 2, 0, 0, 1, 1], // I
+    [3, 2, 4, 3, 0, 4, 0], // ii
+    [3, 4, 2, 0, 3, 0, 4], // iii
+    [0, 1, 4, 2, 0, 3, 1], // IV
+    [0, 4, 4, 5, 2, 1, 3], // V
+    [3, 0, 2, 0, 1, 2, 3], // vi
+    [0, 4, 3, 5, 3, 4, 2], // vii
+];
+/// Cost of not ending on the tonic chord
+const FINAL_CADENCE_COST: u32 = 20;
+/// Cost of not having a dominant chord before the final chord
+const PENULTIMATE_CADENCE_COST: u32 = 8;
+/// Cost of not starting on the tonic chord
+const OPENING_COST: u32 = 4;
+
+/// Harmonizes a soprano melody in four parts (SATB) with the triads of a `Scale`.
+///
+/// The chords are chosen following the usual functional progressions, with an authentic
+/// cadence (V - I) at the end when the melody allows it. They are in root position or
+/// first inversion (root position for the first and last chords when possible),
+/// the root is doubled when possible, the leading tone is never doubled, and the voices are led with
+/// a `VoiceLeader` (no crossing, no parallel fifths or octaves, minimal movement).
+/// In minor modes, the dominant and leading-tone chords use the raised seventh degree.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChoraleHarmonizer {
+    /// The key of the chorale
+    scale: Scale,
+    /// The instrument of the generated parts
+    instrument: Instrument,
+    /// The voices of the chorale
+    voices: VoiceLeader,
+}
+
+impl ChoraleHarmonizer {
+    /// Returns a new `ChoraleHarmonizer` in the given key, with choir voices
+    /// (see `VoiceLeader::satb`)
+    pub fn new(scale: Scale) -> Self {
+        Self {
+            scale,
+            instrument: Instrument::VoiceAahs,
+            voices: VoiceLeader::satb(),
+        }
+    }
+
+    /// Sets the instrument of the generated `Part`s
+    pub fn set_instrument(&mut self, instrument: Instrument) {
+        self.instrument = instrument;
+    }
+
+    /// Returns the alto, tenor, and bass `Part`s (in this order) harmonizing the
+    /// soprano `Phrase`. Rests of the soprano are rests in every voice. If the soprano
+    /// contains `Chord`s, their highest note is used as the melody.
+    ///
+    /// # Errors
+    ///
+    /// * `HarmonizationError::EmptyMelody` if the soprano contains no note
+    /// * `HarmonizationError::MelodyOutOfRange` if a soprano note is out of the soprano range
+    /// * `HarmonizationError::NoChord` if no triad of the scale can harmonize a note
+    pub fn harmonize_parts(&self, soprano: &Phrase) -> Result<Vec<Part>> {
+        let melody: Vec<(Option<Note>, f64)> = soprano
+            .entries()
+            .iter()
+            .map(|e| match e {
+                PhraseEntry::Note(n) => (Some(n.clone()), n.rhythm()),
+                PhraseEntry::Chord(c) => (
+                    c.notes().iter().max_by_key(|n| n.pitch()).cloned(),
+                    c.rhythm(),
+                ),
+                PhraseEntry::Rest(r) => (None, *r),
+            })
+            .collect();
+        let notes: Vec<&Note> = melody.iter().filter_map(|(n, _)| n.as_ref()).collect();
+        if notes.is_empty() {
+            return Err(HarmonizationError::EmptyMelody.into());
+        }
+        let (low, high) = self.voices.ranges()[3];
+        if let Some(n) = notes.iter().find(|n| n.pitch() < low || n.pitch() > high) {
+            return Err(HarmonizationError::MelodyOutOfRange(n.pitch().as_int()).into());
+        }
+
+        let degrees = self.choose_degrees(&notes)?;
+        let harmonies: Vec<Harmony> = degrees
+            .iter()
+            .map(|d| Harmony::new(&self.triad(*d)))
+            .collect();
+        let voicings = self
+            .lead(&notes, &harmonies, &degrees, true)
+            .or_else(|_| self.lead(&notes, &harmonies, &degrees, false))?;
+
+        let mut parts = Vec::with_capacity(3);
+        for (voice, name) in [(2, "Alto"), (1, "Tenor"), (0, "Bass")] {
+            let mut phrase = Phrase::new();
+            let mut voicing = voicings.iter();
+            for (note, rhythm) in melody.iter() {
+                match (note, note.as_ref().and_then(|_| voicing.next())) {
+                    (Some(n), Some(v)) => {
+                        phrase.add_note(Note::new(v[voice], *rhythm, n.dynamic())?)
+                    }
+                    _ => phrase.add_rest(*rhythm),
+                }
+            }
+            let mut part = Part::new(self.instrument);
+            part.set_name(name);
+            part.add_phrase(phrase, 0.);
+            parts.push(part);
+        }
+        Ok(parts)
+    }
+
+    /// Returns a `Score` with the soprano and the alto, tenor and bass `Part`s
+    /// harmonizing it (see `harmonize_parts`)
+    ///
+    /// # Errors
+    ///
+    /// Same errors as `harmonize_parts`
+    pub fn harmonize<S: ToString>(&self, name: S, soprano: &Phrase, tempo: Tempo) -> Result<Score> {
+        let lower_parts = self.harmonize_parts(soprano)?;
+        let mut score = Score::new(name, tempo, None);
+        let mut soprano_part = Part::new(self.instrument);
+        soprano_part.set_name("Soprano");
+        soprano_part.add_phrase(soprano.clone(), 0.);
+        score.add_part(soprano_part);
+        for part in lower_parts {
+            score.add_part(part);
+        }
+        Ok(score)
+    }
+
+    /// Returns the pitch classes of the triad on a degree (`0` for the tonic),
+    /// root first
+    fn triad(&self, degree: usize) -> Vec<u8> {
+        let step = degree as i32;
+        let leading_tone = (self.scale.tonic_pitch().as_int() + 11) % 12;
+        [step, step + 2, step + 4]
+            .iter()
+            .map(|s| {
+                let class = self.scale.step_to_pitch(*s).rem_euclid(12) as u8;
+                // raise the subtonic to the leading tone on V and vii in minor
+                let raise = self.scale.mode() == ScaleMode::Aeolian
+                    && (degree == 4 || degree == 6)
+                    && class == (leading_tone + 11) % 12;
+                if raise {
+                    leading_tone
+                } else {
+                    class
+                }
+            })
+            .collect()
+    }
+
+    /// Chooses the degree of the chord harmonizing each note, minimising the
+    /// total cost of the progression
+    fn choose_degrees(&self, notes: &[&Note]) -> Result<Vec<usize>> {
+        let options: Vec<Vec<usize>> = notes
+            .iter()
+            .enumerate()
+            .map(|(i, n)| {
+                let options: Vec<usize> = (0..7)
+                    .filter(|d| {
+                        let triad = self.triad(*d);
+                        triad.contains(&(n.pitch().as_int() % 12))
+                            && !self
+                                .candidate_voicings(n.pitch(), &triad, *d, false)
+                                .is_empty()
+                    })
+                    .collect();
+                if options.is_empty() {
+                    return Err(HarmonizationError::NoChord(i));
+                }
+                Ok(options)
+            })
+            .collect::<std::result::Result<_, _>>()?;
+
+        let last = options.len() - 1;
+        let position_cost = |i: usize, degree: usize| -> u32 {
+            let mut cost = 0;
+            if i == 0 && degree != 0 {
+                cost += OPENING_COST;
+            }
+            if i == last && degree != 0 {
+                cost += FINAL_CADENCE_COST;
+            }
+            if last > 0 && i == last - 1 && degree != 4 && degree != 6 {
+                cost += PENULTIMATE_CADENCE_COST;
+            }
+            cost
+        };
+        let mut costs: Vec<u32> = options[0].iter().map(|d| position_cost(0, *d)).collect();
+        let mut back: Vec<Vec<usize>> = Vec::with_capacity(options.len());
+        for i in 1..options.len() {
+            let mut new_costs = Vec::with_capacity(options[i].len());
+            let mut pointers = Vec::with_capacity(options[i].len());
+            for degree in options[i].iter() {
+                let (best, cost) = options[i - 1]
+                    .iter()
+                    .enumerate()
+                    .map(|(j, prev)| (j, costs[j] + PROGRESSION_COSTS[*prev][*degree]))
+                    .min_by_key(|(_, c)| *c)
+                    .unwrap_or_default();
+                new_costs.push(cost + position_cost(i, *degree));
+                pointers.push(best);
+            }
+            costs = new_costs;
+            back.push(pointers);
+        }
+        let mut idx = (0..costs.len()).min_by_key(|i| costs[*i]).unwrap_or(0);
+        let mut degrees = vec![0; options.len()];
+        for i in (0..options.len()).rev() {
+            degrees[i] = options[i][idx];
+            if i > 0 {
+                idx = back[i - 1][idx];
+            }
+        }
+        Ok(degrees)
+    }
+
+    /// Returns the voicings of a triad under a given soprano pitch
+    fn candidate_voicings(
+        &self,
+        soprano: u7,
+        triad: &[u8],
+        degree: usize,
+        strict: bool,
+    ) -> Vec<Vec<i32>> {
+        self.voices
+            .voicings(&Harmony::new(triad))
+            .into_iter()
+            .filter(|v| self.valid_voicing(v, soprano, triad, degree, strict, false))
+            .collect()
+    }
+
+    /// Checks the soprano, the bass (root position or first inversion), and the doublings
+    /// of a voicing. If `strict` is false, only the leading tone doubling is checked.
+    /// If `root_position` is true, the first inversion is not allowed.
+    fn valid_voicing(
+        &self,
+        voicing: &[i32],
+        soprano: u7,
+        triad: &[u8],
+        degree: usize,
+        strict: bool,
+        root_position: bool,
+    ) -> bool {
+        let count = |class: u8| voicing.iter().filter(|p| **p % 12 == class as i32).count();
+        let leading_tone = (self.scale.tonic_pitch().as_int() + 11) % 12;
+        let bass = (voicing[0] % 12) as u8;
+        let mut valid = voicing[3] == soprano.as_int() as i32
+            && (bass == triad[0] || (bass == triad[1] && !root_position))
+            && count(leading_tone) <= 1;
+        if strict {
+            // double the root, except on the leading-tone chord where the third is doubled
+            let doubled = if degree == 6 { triad[1] } else { triad[0] };
+            valid &= count(doubled) >= 2 || voicing.len() < 4;
+        }
+        valid
+    }
+
+    /// Leads the voices through the chosen chords with the soprano fixed
+    fn lead(
+        &self,
+        notes: &[&Note],
+        harmonies: &[Harmony],
+        degrees: &[usize],
+        strict: bool,
+    ) -> Result<Vec<Vec<u7>>> {
+        self.voices.lead_filtered(harmonies, |i, v| {
+            self.valid_voicing(
+                v,
+                notes[i].pitch(),
+                harmonies[i].classes(),
+                degrees[i],
+                strict,
+                strict && (i == 0 || i == notes.len() - 1),
+            )
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ChoraleHarmonizer;
+    use crate::composition::{Scale, ScaleMode};
+    use crate::num::u7;
+    use crate::*;
+
+    #[test]
+    fn harmonize_chorale() -> Result<()> {
+        let g_major = Scale::new(u7::new(55), ScaleMode::MAJOR);
+        // G A B A G F# G
+        let soprano = Phrase::from_notes_sequence(Note::new_sequence(
+            rhythm::CROTCHET,
+            dynamic::MF,
+            [67, 69, 71, 69, 67, 66, 67].map(u7::new),
+        ))?;
+        let score =
+            ChoraleHarmonizer::new(g_major).harmonize("chorale", &soprano, Tempo::new(80)?)?;
+        assert_eq!(score.parts().len(), 4);
+
+        let voice = |i: usize| -> Vec<u8> {
+            score.parts()[i].phrases()[0]
+                .1
+                .entries()
+                .iter()
+                .filter_map(|e| match e {
+                    PhraseEntry::Note(n) => Some(n.pitch().as_int()),
+                    _ => None,
+                })
+                .collect()
+        };
+        let bass = voice(3);
+        assert_eq!(bass.len(), 7);
+        // authentic cadence: D then G in the bass
+        assert_eq!(bass[5] % 12, 2);
+        assert_eq!(bass[6] % 12, 7);
+        for i in 0..7 {
+            let chord = [voice(3)[i], voice(2)[i], voice(1)[i], voice(0)[i]];
+            assert!(chord.windows(2).all(|w| w[0] < w[1]), "{chord:?}");
+            assert!(chord.iter().all(|p| g_major.contains(u7::new(*p))));
+        }
+
+        // the lower voices follow the rhythm of the soprano chords, not of their notes
+        let mut soprano = Phrase::new();
+        soprano.add_chord(Chord::new(
+            rhythm::CROTCHET,
+            vec![Note::new(u7::new(67), rhythm::MINIM, dynamic::MF)?],
+        )?);
+        soprano.add_note(Note::new(u7::new(67), rhythm::CROTCHET, dynamic::MF)?);
+        for part in ChoraleHarmonizer::new(g_major).harmonize_parts(&soprano)? {
+            assert_eq!(part.duration(), 2.);
+        }
+        Ok(())
+    }
+}
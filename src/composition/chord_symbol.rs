@@ -0,0 +1,324 @@
+use std::str::FromStr;
+
+use crate::errors::{ChordError, NoteError};
+use crate::num::u7;
+use crate::{Accidental, Chord, NoteName, Result};
+
+mod intervals {
+    pub static MAJOR: [u8; 2] = [4, 7];
+    pub static MINOR: [u8; 2] = [3, 7];
+    pub static DIMINISHED: [u8; 2] = [3, 6];
+    pub static AUGMENTED: [u8; 2] = [4, 8];
+    pub static SUS2: [u8; 2] = [2, 7];
+    pub static SUS4: [u8; 2] = [5, 7];
+    pub static MAJOR6: [u8; 3] = [4, 7, 9];
+    pub static MINOR6: [u8; 3] = [3, 7, 9];
+    pub static DOMINANT7: [u8; 3] = [4, 7, 10];
+    pub static MAJOR7: [u8; 3] = [4, 7, 11];
+    pub static MINOR7: [u8; 3] = [3, 7, 10];
+    pub static MINOR_MAJOR7: [u8; 3] = [3, 7, 11];
+    pub static HALF_DIMINISHED7: [u8; 3] = [3, 6, 10];
+    pub static DIMINISHED7: [u8; 3] = [3, 6, 9];
+    pub static DOMINANT9: [u8; 4] = [4, 7, 10, 14];
+    pub static MAJOR9: [u8; 4] = [4, 7, 11, 14];
+    pub static MINOR9: [u8; 4] = [3, 7, 10, 14];
+    pub static ADD9: [u8; 3] = [4, 7, 14];
+}
+
+/// The quality of a chord, which defines the intervals of its notes above the root
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ChordQuality {
+    #[default]
+    Major,
+    Minor,
+    Diminished,
+    Augmented,
+    Sus2,
+    Sus4,
+    Major6,
+    Minor6,
+    Dominant7,
+    Major7,
+    Minor7,
+    MinorMajor7,
+    HalfDiminished7,
+    Diminished7,
+    Dominant9,
+    Major9,
+    Minor9,
+    Add9,
+}
+
+impl ChordQuality {
+    /// The suffixes recognized after the root when parsing a chord symbol
+    const SUFFIXES: [(&'static str, ChordQuality); 33] = [
+        ("maj9", Self::Major9),
+        ("M9", Self::Major9),
+        ("maj7", Self::Major7),
+        ("M7", Self::Major7),
+        ("mMaj7", Self::MinorMajor7),
+        ("mM7", Self::MinorMajor7),
+        ("m7b5", Self::HalfDiminished7),
+        ("ø", Self::HalfDiminished7),
+        ("dim7", Self::Diminished7),
+        ("°7", Self::Diminished7),
+        ("add9", Self::Add9),
+        ("sus2", Self::Sus2),
+        ("sus4", Self::Sus4),
+        ("sus", Self::Sus4),
+        ("dim", Self::Diminished),
+        ("°", Self::Diminished),
+        ("aug", Self::Augmented),
+        ("+", Self::Augmented),
+        ("min9", Self::Minor9),
+        ("m9", Self::Minor9),
+        ("min7", Self::Minor7),
+        ("m7", Self::Minor7),
+        ("-7", Self::Minor7),
+        ("min6", Self::Minor6),
+        ("m6", Self::Minor6),
+        ("min", Self::Minor),
+        ("m", Self::Minor),
+        ("-", Self::Minor),
+        ("9", Self::Dominant9),
+        ("7", Self::Dominant7),
+        ("6", Self::Major6),
+        ("maj", Self::Major),
+        ("", Self::Major),
+    ];
+
+    // Returns the list of intervals above the root for this quality.
+    pub fn intervals(&self) -> &'static [u8] {
+        match *self {
+            Self::Major => &intervals::MAJOR,
+            Self::Minor => &intervals::MINOR,
+            Self::Diminished => &intervals::DIMINISHED,
+            Self::Augmented => &intervals::AUGMENTED,
+            Self::Sus2 => &intervals::SUS2,
+            Self::Sus4 => &intervals::SUS4,
+            Self::Major6 => &intervals::MAJOR6,
+            Self::Minor6 => &intervals::MINOR6,
+            Self::Dominant7 => &intervals::DOMINANT7,
+            Self::Major7 => &intervals::MAJOR7,
+            Self::Minor7 => &intervals::MINOR7,
+            Self::MinorMajor7 => &intervals::MINOR_MAJOR7,
+            Self::HalfDiminished7 => &intervals::HALF_DIMINISHED7,
+            Self::Diminished7 => &intervals::DIMINISHED7,
+            Self::Dominant9 => &intervals::DOMINANT9,
+            Self::Major9 => &intervals::MAJOR9,
+            Self::Minor9 => &intervals::MINOR9,
+            Self::Add9 => &intervals::ADD9,
+        }
+    }
+}
+
+/// Describes a chord by its root, quality, and optional bass note (slash chord),
+/// as written in lead sheets (e.g. `C`, `F#m7`, `Bbmaj7`, `G7/B`).
+///
+/// Chord symbols can be parsed from strings with `str::parse`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ChordSymbol {
+    /// Pitch class of the root (`0` is C)
+    root: u8,
+    /// Quality of the chord
+    quality: ChordQuality,
+    /// Pitch class of the bass if it is not the root
+    bass: Option<u8>,
+}
+
+impl ChordSymbol {
+    /// Returns a new `ChordSymbol` with its root in the bass
+    pub fn new(root: NoteName, accidental: Accidental, quality: ChordQuality) -> Self {
+        Self {
+            root: pitch_class(root, accidental),
+            quality,
+            bass: None,
+        }
+    }
+
+    /// Returns a copy of the `ChordSymbol` with another note in the bass (slash chord)
+    pub fn with_bass(&self, bass: NoteName, accidental: Accidental) -> Self {
+        let bass = pitch_class(bass, accidental);
+        Self {
+            bass: (bass != self.root).then_some(bass),
+            ..*self
+        }
+    }
+
+    /// Returns the pitch class of the root (between `0` and `11`, `0` is C)
+    pub fn root_class(&self) -> u8 {
+        self.root
+    }
+
+    /// Returns the pitch class of the bass, which is the root unless
+    /// a different bass note was specified
+    pub fn bass_class(&self) -> u8 {
+        self.bass.unwrap_or(self.root)
+    }
+
+    /// Returns the quality of the chord
+    pub fn quality(&self) -> ChordQuality {
+        self.quality
+    }
+
+    /// Returns the distinct pitch classes of the chord, starting with the root and
+    /// followed by the other chord members in order (third, fifth, seventh...).
+    /// A slash bass that is not a member of the chord is added last.
+    pub fn pitch_classes(&self) -> Vec<u8> {
+        let mut classes = vec![self.root];
+        classes.extend(
+            self.quality
+                .intervals()
+                .iter()
+                .map(|i| (self.root + i) % 12),
+        );
+        if let Some(bass) = self.bass {
+            if !classes.contains(&bass) {
+                classes.push(bass);
+            }
+        }
+        classes
+    }
+
+    /// Returns the pitches of the chord in root position (or with the bass note
+    /// below the root position for slash chords), starting on the given octave
+    ///
+    /// # Errors
+    ///
+    /// * `NoteError::InvalidPitch` if a pitch is above `127`
+    pub fn pitches(&self, octave: u8) -> Result<Vec<u7>> {
+        let root = 12 * octave as u32 + self.root as u32;
+        let mut pitches = Vec::new();
+        if let Some(bass) = self.bass {
+            let below = (self.root as u32 + 12 - bass as u32) % 12;
+            pitches.push(root.checked_sub(below).unwrap_or(root + 12 - below));
+        }
+        pitches.push(root);
+        pitches.extend(self.quality.intervals().iter().map(|i| root + *i as u32));
+        pitches
+            .into_iter()
+            .map(|p| {
+                if p > 127 {
+                    return Err(NoteError::InvalidPitch(p).into());
+                }
+                Ok(u7::new(p as u8))
+            })
+            .collect()
+    }
+
+    /// Returns a `Chord` with the pitches returned by `pitches`
+    ///
+    /// # Errors
+    ///
+    /// * `NoteError::InvalidPitch` if a pitch is above `127`
+    pub fn to_chord(&self, octave: u8, rhythm: f64, dynamic: u7) -> Result<Chord> {
+        Chord::from_pitches(rhythm, dynamic, &self.pitches(octave)?)
+    }
+}
+
+impl FromStr for ChordSymbol {
+    type Err = crate::Error;
+
+    /// Parses a chord symbol such as `C`, `F#m7`, `Bbmaj7`, `Ebdim`, `G7/B` or `Am7b5`
+    ///
+    /// # Errors
+    ///
+    /// * `ChordError::InvalidSymbol` if the string is not a recognized chord symbol
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || ChordError::InvalidSymbol(s.to_string());
+        let (chord, bass) = match s.split_once('/') {
+            Some((chord, bass)) => (chord, Some(bass)),
+            None => (s, None),
+        };
+        let (root, suffix) = parse_note(chord).ok_or_else(invalid)?;
+        let quality = ChordQuality::SUFFIXES
+            .iter()
+            .find(|(sfx, _)| *sfx == suffix)
+            .map(|(_, q)| *q)
+            .ok_or_else(invalid)?;
+        let mut symbol = Self {
+            root,
+            quality,
+            bass: None,
+        };
+        if let Some(bass) = bass {
+            match parse_note(bass) {
+                Some((bass, "")) if bass != root => symbol.bass = Some(bass),
+                Some((_, "")) => {}
+                _ => return Err(invalid().into()),
+            }
+        }
+        Ok(symbol)
+    }
+}
+
+/// Returns the pitch class of a note name and accidental
+fn pitch_class(name: NoteName, accidental: Accidental) -> u8 {
+    let base = name as u8 + 12;
+    match accidental {
+        Accidental::Flat => (base - 1) % 12,
+        Accidental::Natural => base % 12,
+        Accidental::Sharp => (base + 1) % 12,
+    }
+}
+
+/// Parses a note name (letter and optional accidental) at the start of `s` and returns
+/// its pitch class and the rest of the string
+fn parse_note(s: &str) -> Option<(u8, &str)> {
+    let mut chars = s.chars();
+    let name = match chars.next()? {
+        'C' => NoteName::C,
+        'D' => NoteName::D,
+        'E' => NoteName::E,
+        'F' => NoteName::F,
+        'G' => NoteName::G,
+        'A' => NoteName::A,
+        'B' => NoteName::B,
+        _ => return None,
+    };
+    let rest = chars.as_str();
+    let (accidental, rest) = if let Some(r) = rest.strip_prefix(['#', '♯']) {
+        (Accidental::Sharp, r)
+    } else if let Some(r) = rest.strip_prefix(['b', '♭']) {
+        (Accidental::Flat, r)
+    } else {
+        (Accidental::Natural, rest)
+    };
+    Some((pitch_class(name, accidental), rest))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ChordQuality, ChordSymbol};
+    use crate::*;
+
+    #[test]
+    fn parse_chord_symbols() -> Result<()> {
+        let g7_b: ChordSymbol = "G7/B".parse()?;
+        assert_eq!(g7_b.root_class(), 7);
+        assert_eq!(g7_b.bass_class(), 11);
+        assert_eq!(g7_b.quality(), ChordQuality::Dominant7);
+        assert_eq!(g7_b.pitch_classes(), vec![7, 11, 2, 5]);
+
+        let bb: ChordSymbol = "Bbmaj7".parse()?;
+        assert_eq!(bb.pitch_classes(), vec![10, 2, 5, 9]);
+        assert_eq!(
+            "F#m7b5".parse::<ChordSymbol>()?,
+            ChordSymbol::new(
+                NoteName::F,
+                Accidental::Sharp,
+                ChordQuality::HalfDiminished7
+            )
+        );
+        assert_eq!(
+            "Cm".parse::<ChordSymbol>()?.pitches(4)?,
+            vec![48, 51, 55]
+                .into_iter()
+                .map(num::u7::new)
+                .collect::<Vec<_>>()
+        );
+        assert!("H7".parse::<ChordSymbol>().is_err());
+        assert!("Cxyz".parse::<ChordSymbol>().is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,374 @@
+use std::time::{Duration, Instant};
+
+use crate::composition::counterpoint::{motion_issue, phrase_line};
+use crate::composition::random::Rng;
+use crate::composition::{CounterpointIssue, Harmony, Scale};
+use crate::errors::{GenerationError, NoteError};
+use crate::events::to_ticks;
+use crate::num::u7;
+use crate::{Note, Phrase, Result};
+
+/// Describes a rule that the melodies found by a `MelodySolver` must follow. Harmony
+/// rules apply to the melody against a given harmonic context (chords or a bass line).
+#[derive(Debug, Clone)]
+pub enum MelodyConstraint {
+    /// All the pitches belong to the `Scale`
+    InScale(Scale),
+    /// All the pitches are between the two pitches (included)
+    Range(u7, u7),
+    /// The first note has the given pitch class (`0` for C to `11` for B)
+    StartOn(u8),
+    /// The last note has the given pitch class (`0` for C to `11` for B), e.g. the
+    /// pitch class of the tonic of the scale
+    EndOn(u8),
+    /// No interval between two consecutive notes is larger than the given number of
+    /// semitones (e.g. `9` for "no leap over a major sixth")
+    MaxLeap(u8),
+    /// The highest pitch of the melody is only reached in its second half (in beats)
+    ClimaxInSecondHalf,
+    /// The rhythm values of the notes sum to the given number of beats
+    TotalDuration(f64),
+    /// No parallel fifths or octaves against the given voice (e.g. a bass line)
+    NoParallelsWith(Phrase),
+    /// One `Harmony` per beat, the last one lasting until the end of the melody: the
+    /// notes starting on a strong beat, every given (positive) number of beats from the
+    /// start (e.g. `2.` for the first and third beats of 4/4 bars), are chord tones of the
+    /// `Harmony` of their beat
+    ChordTonesOnStrongBeats(Vec<Harmony>, f64),
+    /// Custom rule called with the notes chosen so far, and true if the melody is
+    /// complete. It must return false if the notes cannot lead to a valid melody.
+    Custom(fn(&[Note], bool) -> bool),
+}
+
+/// Finds melodies that satisfy a set of `MelodyConstraint`s by searching over the
+/// pitches and rhythm values of each note, with backtracking.
+///
+/// The candidate values are tried in a random order determined by the seed, so different
+/// seeds give different solutions and the same seed always gives the same solutions.
+#[derive(Debug, Clone)]
+pub struct MelodySolver {
+    /// The number of notes of the melodies
+    num_notes: usize,
+    /// The candidate pitches of each note
+    pitches: Vec<u7>,
+    /// The candidate rhythm values of each note
+    rhythms: Vec<f64>,
+    /// The dynamic of the notes
+    dynamic: u7,
+    /// The rules of the melodies
+    constraints: Vec<MelodyConstraint>,
+    /// The melodic line of the voice of each `NoParallelsWith` rule (empty for the other
+    /// rules) as `(start, end, pitch)` with times in ticks
+    lines: Vec<Vec<(u64, u64, i32)>>,
+    /// The maximum number of notes tried during a search
+    max_iterations: usize,
+    /// The maximum duration of a search
+    time_limit: Option<Duration>,
+}
+
+impl MelodySolver {
+    /// Returns a new `MelodySolver` without constraints, limited to one million iterations
+    ///
+    /// # Arguments
+    ///
+    /// * `num_notes` - The number of notes of the melodies
+    /// * `pitches` - The candidate pitches of each note
+    /// * `rhythms` - The candidate rhythm values of each note
+    ///
+    /// # Errors
+    ///
+    /// * `NoteError::InvalidRhythm` if a rhythm value is below `0.000_001`
+    pub fn new(num_notes: usize, pitches: Vec<u7>, rhythms: Vec<f64>) -> Result<Self> {
+        if let Some(r) = rhythms.iter().find(|r| **r < 0.000_001) {
+            return Err(NoteError::InvalidRhythm(*r).into());
+        }
+        Ok(Self {
+            num_notes,
+            pitches,
+            rhythms,
+            dynamic: crate::dynamic::MF,
+            constraints: Vec::new(),
+            lines: Vec::new(),
+            max_iterations: 1_000_000,
+            time_limit: None,
+        })
+    }
+
+    /// Adds a rule that the melodies must follow
+    pub fn add_constraint(&mut self, constraint: MelodyConstraint) {
+        self.lines.push(match &constraint {
+            MelodyConstraint::NoParallelsWith(voice) => phrase_line(voice),
+            _ => Vec::new(),
+        });
+        self.constraints.push(constraint);
+    }
+
+    /// Sets the dynamic of the notes of the melodies
+    pub fn set_dynamic(&mut self, dynamic: u7) {
+        self.dynamic = dynamic;
+    }
+
+    /// Sets the maximum number of notes tried during a search
+    pub fn set_max_iterations(&mut self, max_iterations: usize) {
+        self.max_iterations = max_iterations;
+    }
+
+    /// Sets the maximum duration of a search (`None` for no time limit)
+    pub fn set_time_limit(&mut self, time_limit: Option<Duration>) {
+        self.time_limit = time_limit;
+    }
+
+    /// Returns the rules of the melodies
+    pub fn constraints(&self) -> &[MelodyConstraint] {
+        &self.constraints
+    }
+
+    /// Returns up to `max_solutions` different melodies that satisfy all the constraints.
+    /// The search stops when enough melodies are found, when all the possibilities were
+    /// tried, or when the iteration or time budget is exhausted.
+    ///
+    /// # Errors
+    ///
+    /// * `GenerationError::NoSolution` if no melody satisfies the constraints
+    /// * `GenerationError::BudgetExhausted` if the budget was exhausted before finding a
+    ///   melody
+    pub fn solve(&self, max_solutions: usize, seed: u64) -> Result<Vec<Phrase>> {
+        let mut search = Search {
+            rng: Rng::new(seed),
+            iterations: 0,
+            max_iterations: self.max_iterations,
+            deadline: self.time_limit.map(|t| Instant::now() + t),
+            exhausted: false,
+            solutions: Vec::new(),
+        };
+        let mut notes = Vec::with_capacity(self.num_notes);
+        self.search(&mut notes, &mut search, max_solutions)?;
+        if !search.solutions.is_empty() {
+            return Ok(search.solutions);
+        }
+        if search.exhausted {
+            return Err(GenerationError::BudgetExhausted(search.iterations).into());
+        }
+        Err(GenerationError::NoSolution.into())
+    }
+
+    /// Returns the first melody found (see `solve`)
+    ///
+    /// # Errors
+    ///
+    /// * Same as `solve`
+    pub fn solve_one(&self, seed: u64) -> Result<Phrase> {
+        Ok(self.solve(1, seed)?.remove(0))
+    }
+
+    /// Tries every candidate for the next note, recursively
+    fn search(&self, notes: &mut Vec<Note>, search: &mut Search, max: usize) -> Result<()> {
+        if notes.len() == self.num_notes {
+            search
+                .solutions
+                .push(Phrase::from_notes_sequence(notes.iter().cloned().map(Ok))?);
+            return Ok(());
+        }
+        let mut pitches = self.pitches.clone();
+        let mut rhythms = self.rhythms.clone();
+        search.shuffle(&mut pitches);
+        search.shuffle(&mut rhythms);
+        for pitch in pitches {
+            for rhythm in rhythms.iter() {
+                if search.solutions.len() >= max || search.out_of_budget() {
+                    return Ok(());
+                }
+                search.iterations += 1;
+                notes.push(Note::new(pitch, *rhythm, self.dynamic)?);
+                if self.is_valid(notes) {
+                    self.search(notes, search, max)?;
+                }
+                notes.pop();
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns true if the notes chosen so far can still lead to a valid melody
+    fn is_valid(&self, notes: &[Note]) -> bool {
+        let complete = notes.len() == self.num_notes;
+        let remaining = (self.num_notes - notes.len()) as f64;
+        let last = notes[notes.len() - 1].pitch().as_int();
+        let start: f64 = notes[..notes.len() - 1].iter().map(Note::rhythm).sum();
+        let mut constraints = self.constraints.iter().zip(&self.lines);
+        constraints.all(|(constraint, line)| match constraint {
+            MelodyConstraint::InScale(scale) => scale.contains(u7::new(last)),
+            MelodyConstraint::Range(low, high) => (low.as_int()..=high.as_int()).contains(&last),
+            MelodyConstraint::StartOn(class) => notes[0].pitch().as_int() % 12 == class % 12,
+            MelodyConstraint::EndOn(class) => !complete || last % 12 == class % 12,
+            MelodyConstraint::MaxLeap(max) => {
+                notes.len() < 2 || {
+                    let previous = notes[notes.len() - 2].pitch().as_int();
+                    last.abs_diff(previous) <= *max
+                }
+            }
+            MelodyConstraint::ClimaxInSecondHalf => {
+                !complete || {
+                    let total: f64 = notes.iter().map(Note::rhythm).sum();
+                    let highest = notes.iter().map(Note::pitch).max();
+                    let mut beat = 0.;
+                    notes.iter().all(|n| {
+                        let ok = Some(n.pitch()) != highest || beat >= total / 2. - 0.000_001;
+                        beat += n.rhythm();
+                        ok
+                    })
+                }
+            }
+            MelodyConstraint::TotalDuration(beats) => {
+                let elapsed: f64 = notes.iter().map(Note::rhythm).sum();
+                let shortest = self.rhythms.iter().copied().fold(f64::INFINITY, f64::min);
+                let longest = self.rhythms.iter().copied().fold(0., f64::max);
+                elapsed + remaining * shortest <= beats + 0.000_001
+                    && elapsed + remaining * longest >= beats - 0.000_001
+            }
+            MelodyConstraint::NoParallelsWith(_) => !adds_parallels(notes, start, line),
+            MelodyConstraint::ChordTonesOnStrongBeats(harmonies, every) => {
+                let position = start / every;
+                (position - position.round()).abs() > 0.000_001 || harmonies.is_empty() || {
+                    let beat = ((start + 0.000_001) as usize).min(harmonies.len() - 1);
+                    harmonies[beat].classes().contains(&(last % 12))
+                }
+            }
+            MelodyConstraint::Custom(rule) => rule(notes, complete),
+        })
+    }
+}
+
+/// Returns true if the last note, starting at `start` beats, forms parallel fifths or
+/// octaves with the voice `line` (only the intervals from the onset of the previous note
+/// are checked, the earlier ones being checked when the previous notes were chosen)
+fn adds_parallels(notes: &[Note], start: f64, line: &[(u64, u64, i32)]) -> bool {
+    let last = &notes[notes.len() - 1];
+    let (begin, end) = (to_ticks(start), to_ticks(start + last.rhythm()));
+    let lower_at = |tick: u64| line.iter().find(|n| n.0 <= tick && tick < n.1).map(|n| n.2);
+    // the vertical interval just before the last note, between the previous note and
+    // the voice, then the ones at each onset during the last note
+    let mut previous = notes.len().checked_sub(2).map(|i| {
+        let onset = line.iter().map(|n| n.0).filter(|t| *t < begin).max();
+        let tick = onset.unwrap_or(0).max(to_ticks(start - notes[i].rhythm()));
+        (notes[i].pitch().as_int() as i32, lower_at(tick))
+    });
+    let onsets = line.iter().map(|n| n.0).filter(|t| begin < *t && *t < end);
+    for tick in std::iter::once(begin).chain(onsets) {
+        let next = (last.pitch().as_int() as i32, lower_at(tick));
+        if let Some((upper, Some(lower))) = previous {
+            if let (upper_next, Some(lower_next)) = next {
+                let issue = motion_issue((upper, lower), (upper_next, lower_next));
+                if matches!(
+                    issue,
+                    Some(CounterpointIssue::ParallelFifths | CounterpointIssue::ParallelOctaves)
+                ) {
+                    return true;
+                }
+            }
+        }
+        previous = Some(next);
+    }
+    false
+}
+
+/// State of a search of the `MelodySolver`
+struct Search {
+    rng: Rng,
+    iterations: usize,
+    max_iterations: usize,
+    deadline: Option<Instant>,
+    exhausted: bool,
+    solutions: Vec<Phrase>,
+}
+
+impl Search {
+    /// Shuffles the candidate values (Fisher-Yates)
+    fn shuffle<T>(&mut self, values: &mut [T]) {
+        for i in (1..values.len()).rev() {
+            values.swap(i, self.rng.below(i + 1));
+        }
+    }
+
+    /// Returns true (and remembers it) if the iteration or time budget is exhausted
+    fn out_of_budget(&mut self) -> bool {
+        self.exhausted = self.exhausted
+            || self.iterations >= self.max_iterations
+            || self.deadline.is_some_and(|d| Instant::now() >= d);
+        self.exhausted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MelodyConstraint as C, MelodySolver};
+    use crate::composition::{check_counterpoint, CounterpointIssue, Harmony, Scale, ScaleMode};
+    use crate::errors::GenerationError;
+    use crate::num::u7;
+    use crate::*;
+
+    #[test]
+    fn melody_solver() -> Result<()> {
+        let scale = Scale::new(u7::new(60), ScaleMode::Ionian);
+        let pitches: Vec<u7> = (60..=72).map(u7::new).collect();
+        let mut solver = MelodySolver::new(8, pitches, vec![rhythm::CROTCHET, rhythm::MINIM])?;
+        solver.add_constraint(C::InScale(scale));
+        solver.add_constraint(C::StartOn(0));
+        solver.add_constraint(C::EndOn(0));
+        solver.add_constraint(C::MaxLeap(9));
+        solver.add_constraint(C::ClimaxInSecondHalf);
+        solver.add_constraint(C::TotalDuration(10.));
+        let bass = Phrase::from_notes_sequence(Note::new_sequence(
+            rhythm::SEMIBREVE,
+            dynamic::MF,
+            [48, 53, 43].map(u7::new),
+        ))?;
+        solver.add_constraint(C::NoParallelsWith(bass.clone()));
+
+        // C major, F major and G major triads for two bars of 4/4, then C major
+        let chords: Vec<Harmony> = [[0, 4, 7], [0, 4, 7], [0, 5, 9], [2, 7, 11]]
+            .iter()
+            .flat_map(|c| [Harmony::new(c), Harmony::new(c)])
+            .chain([Harmony::new(&[0, 4, 7])])
+            .collect();
+        solver.add_constraint(C::ChordTonesOnStrongBeats(chords.clone(), 2.));
+
+        let solutions = solver.solve(3, 1)?;
+        assert_eq!(solutions.len(), 3);
+        assert_eq!(solutions, solver.solve(3, 1)?);
+        for melody in solutions.iter() {
+            assert_eq!(melody.duration(), 10.);
+            assert_eq!(melody.entries().len(), 8);
+            assert!(!check_counterpoint(&[melody.clone(), bass.clone()])
+                .iter()
+                .any(|d| matches!(
+                    d.issue,
+                    CounterpointIssue::ParallelFifths | CounterpointIssue::ParallelOctaves
+                )));
+            let mut beat = 0.;
+            for entry in melody.entries() {
+                if beat % 2. == 0. {
+                    let PhraseEntry::Note(n) = entry else {
+                        unreachable!()
+                    };
+                    let harmony = &chords[(beat as usize).min(chords.len() - 1)];
+                    assert!(harmony.classes().contains(&(n.pitch().as_int() % 12)));
+                }
+                beat += entry.rhythm();
+            }
+        }
+
+        let mut impossible = MelodySolver::new(2, vec![u7::new(60)], vec![1.])?;
+        impossible.add_constraint(C::EndOn(2));
+        assert!(matches!(
+            impossible.solve_one(0),
+            Err(Error::Generation(GenerationError::NoSolution))
+        ));
+        impossible.set_max_iterations(1);
+        assert!(matches!(
+            impossible.solve_one(0),
+            Err(Error::Generation(GenerationError::BudgetExhausted(1)))
+        ));
+        Ok(())
+    }
+}
@@ -0,0 +1,224 @@
+use crate::{NoteEvents, Part, Phrase, TICKS_PER_BEAT};
+
+/// Describes a rule of species counterpoint broken by the voices
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CounterpointIssue {
+    /// Two voices a fifth apart both move in the same direction to another fifth
+    ParallelFifths,
+    /// Two voices an octave (or unison) apart both move in the same direction to
+    /// another octave
+    ParallelOctaves,
+    /// Two voices reach a fifth by similar motion with a leap in the upper voice
+    HiddenFifths,
+    /// Two voices reach an octave by similar motion with a leap in the upper voice
+    HiddenOctaves,
+    /// A voice goes below a voice that is supposed to be lower
+    VoiceCrossing,
+    /// A dissonance is not followed by a consonance reached by step
+    UnresolvedDissonance,
+    /// A voice leaps by more than an octave
+    LeapOverOctave,
+    /// A voice repeats the same pitch
+    RepeatedNote,
+}
+
+/// Describes a counterpoint rule broken at a given position
+#[derive(Debug, Clone, PartialEq)]
+pub struct CounterpointDiagnostic {
+    /// The rule that is broken
+    pub issue: CounterpointIssue,
+    /// The beat at which the rule is broken (the start of the second note for
+    /// issues about a movement)
+    pub beat: f64,
+    /// The indices of the voices involved, upper voice first
+    pub voices: Vec<usize>,
+}
+
+/// Checks the rules of species counterpoint between the given voices and returns the
+/// diagnostics sorted by beat.
+///
+/// The voices must be given from the highest to the lowest. If a `Phrase` contains
+/// `Chord`s, only their highest note is considered.
+pub fn check_counterpoint(voices: &[Phrase]) -> Vec<CounterpointDiagnostic> {
+    let lines: Vec<Vec<(u64, u64, i32)>> = voices.iter().map(phrase_line).collect();
+    check_lines(&lines)
+}
+
+/// Same as `check_counterpoint` but each `Part` is a voice. The phrases of each `Part`
+/// are placed at their start beat.
+pub fn check_counterpoint_parts(voices: &[Part]) -> Vec<CounterpointDiagnostic> {
+    let lines: Vec<Vec<(u64, u64, i32)>> = voices
+        .iter()
+        .map(|part| {
+            let mut line = line(NoteEvents::from_parts([(0, part)], 60));
+            line.sort_by_key(|n| n.0);
+            line
+        })
+        .collect();
+    check_lines(&lines)
+}
+
+/// Returns the melodic line of a `Phrase` as `(start, end, pitch)` with times in ticks
+pub(crate) fn phrase_line(phrase: &Phrase) -> Vec<(u64, u64, i32)> {
+    line(NoteEvents::from_phrase(phrase, 0.))
+}
+
+/// Returns the melodic line of the notes as `(start, end, pitch)` with times in ticks
+fn line(events: NoteEvents) -> Vec<(u64, u64, i32)> {
+    events
+        .highest_per_entry()
+        .iter()
+        .map(|e| (e.start_tick, e.end_tick, e.pitch().as_int() as i32))
+        .collect()
+}
+
+/// Checks the melodic and harmonic rules on the notes of each voice
+fn check_lines(lines: &[Vec<(u64, u64, i32)>]) -> Vec<CounterpointDiagnostic> {
+    let mut diagnostics = Vec::new();
+    let mut report = |issue, time: u64, voices: Vec<usize>| {
+        diagnostics.push(CounterpointDiagnostic {
+            issue,
+            beat: time as f64 / TICKS_PER_BEAT as f64,
+            voices,
+        })
+    };
+
+    // melodic rules
+    for (v, line) in lines.iter().enumerate() {
+        for pair in line.windows(2) {
+            let (prev, next) = (pair[0].2, pair[1].2);
+            if (next - prev).abs() > 12 {
+                report(CounterpointIssue::LeapOverOctave, pair[1].0, vec![v]);
+            }
+            if next == prev {
+                report(CounterpointIssue::RepeatedNote, pair[1].0, vec![v]);
+            }
+        }
+    }
+
+    // harmonic rules, checked at each onset of any voice
+    let mut times: Vec<u64> = lines.iter().flatten().map(|n| n.0).collect();
+    times.sort_unstable();
+    times.dedup();
+    let slices: Vec<Vec<Option<i32>>> = times
+        .iter()
+        .map(|t| {
+            lines
+                .iter()
+                .map(|line| line.iter().find(|n| n.0 <= *t && *t < n.1).map(|n| n.2))
+                .collect()
+        })
+        .collect();
+    for (k, slice) in slices.iter().enumerate() {
+        let lowest = slice.iter().rposition(Option::is_some);
+        for i in 0..slice.len() {
+            for j in i + 1..slice.len() {
+                let (Some(upper), Some(lower)) = (slice[i], slice[j]) else {
+                    continue;
+                };
+                if upper < lower {
+                    report(CounterpointIssue::VoiceCrossing, times[k], vec![i, j]);
+                }
+                let interval = (upper - lower).abs() % 12;
+                let bass_pair = lowest == Some(j);
+                if is_dissonant(interval, bass_pair) && !is_resolved(&slices, k, i, j) {
+                    report(
+                        CounterpointIssue::UnresolvedDissonance,
+                        times[k],
+                        vec![i, j],
+                    );
+                }
+                if k == 0 {
+                    continue;
+                }
+                let (Some(prev_upper), Some(prev_lower)) = (slices[k - 1][i], slices[k - 1][j])
+                else {
+                    continue;
+                };
+                if let Some(issue) = motion_issue((prev_upper, prev_lower), (upper, lower)) {
+                    report(issue, times[k], vec![i, j]);
+                }
+            }
+        }
+    }
+    diagnostics.sort_by(|a, b| a.beat.total_cmp(&b.beat));
+    diagnostics
+}
+
+/// Returns the parallel or hidden fifths or octaves formed by two voices moving from
+/// the `(upper, lower)` pitches `previous` to `next`, if any
+pub(crate) fn motion_issue(previous: (i32, i32), next: (i32, i32)) -> Option<CounterpointIssue> {
+    let (up_move, low_move) = (next.0 - previous.0, next.1 - previous.1);
+    if up_move == 0 || low_move == 0 || up_move.signum() != low_move.signum() {
+        return None;
+    }
+    let interval = (next.0 - next.1).abs() % 12;
+    let prev_interval = (previous.0 - previous.1).abs() % 12;
+    match (interval, prev_interval == interval, up_move.abs() > 2) {
+        (7, true, _) => Some(CounterpointIssue::ParallelFifths),
+        (0, true, _) => Some(CounterpointIssue::ParallelOctaves),
+        (7, false, true) => Some(CounterpointIssue::HiddenFifths),
+        (0, false, true) => Some(CounterpointIssue::HiddenOctaves),
+        _ => None,
+    }
+}
+
+/// Returns true if the interval (in semitones, modulo an octave) is dissonant.
+/// The perfect fourth is only dissonant against the lowest voice.
+fn is_dissonant(interval: i32, against_bass: bool) -> bool {
+    matches!(interval, 1 | 2 | 6 | 10 | 11) || (interval == 5 && against_bass)
+}
+
+/// Returns true if the dissonance between voices `i` and `j` in slice `k` is followed by
+/// a consonance reached by step in at least one of the voices
+fn is_resolved(slices: &[Vec<Option<i32>>], k: usize, i: usize, j: usize) -> bool {
+    let Some(next) = slices.get(k + 1) else {
+        return false;
+    };
+    let (Some(upper), Some(lower), Some(next_upper), Some(next_lower)) =
+        (slices[k][i], slices[k][j], next[i], next[j])
+    else {
+        // a voice stops on the dissonance
+        return false;
+    };
+    let interval = (next_upper - next_lower).abs() % 12;
+    let by_step = |a: i32, b: i32| (1..=2).contains(&(a - b).abs());
+    let lowest = next.iter().rposition(Option::is_some) == Some(j);
+    !is_dissonant(interval, lowest) && (by_step(upper, next_upper) || by_step(lower, next_lower))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check_counterpoint, CounterpointIssue as Issue};
+    use crate::num::u7;
+    use crate::*;
+
+    fn voice(pitches: &[u8]) -> Result<Phrase> {
+        Phrase::from_notes_sequence(Note::new_sequence(
+            rhythm::SEMIBREVE,
+            dynamic::MF,
+            pitches.iter().map(|p| u7::new(*p)),
+        ))
+    }
+
+    #[test]
+    fn counterpoint_rules() -> Result<()> {
+        let upper = voice(&[72, 79, 79, 77, 93, 91])?;
+        let lower = voice(&[60, 72, 71, 67, 62, 60])?;
+        let issues: Vec<(Issue, f64)> = check_counterpoint(&[upper, lower])
+            .into_iter()
+            .map(|d| (d.issue, d.beat))
+            .collect();
+        assert_eq!(
+            issues,
+            vec![
+                (Issue::HiddenFifths, 4.),
+                (Issue::RepeatedNote, 8.),
+                (Issue::UnresolvedDissonance, 12.),
+                (Issue::LeapOverOctave, 16.),
+                (Issue::ParallelFifths, 20.),
+            ]
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,415 @@
+use crate::composition::random::Rng;
+use crate::composition::Scale;
+use crate::errors::GenerationError;
+use crate::events::to_ticks;
+use crate::num::u7;
+use crate::{Note, Phrase, PhraseEntry, Result};
+
+/// Shortest rhythm value produced by the rhythm split mutation
+const MIN_RHYTHM: f64 = 0.125;
+
+/// Evaluates how good a `Phrase` is. Higher is better.
+///
+/// Closures taking a `&Phrase` and returning a `f64` implement this trait, as well as
+/// `FitnessTerm` and weighted sums of `FitnessTerm`s (`Vec<(f64, FitnessTerm)>`).
+pub trait Fitness {
+    /// Returns the fitness of `phrase`
+    fn fitness(&self, phrase: &Phrase) -> f64;
+}
+
+impl<F: Fn(&Phrase) -> f64> Fitness for F {
+    fn fitness(&self, phrase: &Phrase) -> f64 {
+        self(phrase)
+    }
+}
+
+/// Describes a built-in fitness criterion. Each term returns a value between `0.0`
+/// (worst) and `1.0` (best).
+#[derive(Debug, Clone, PartialEq)]
+pub enum FitnessTerm {
+    /// Proportion of the notes that belong to the `Scale`
+    ScaleFit(Scale),
+    /// Favors melodies moving by small intervals: `1.0` when all the notes repeat the
+    /// same pitch, `0.0` when all the intervals are octaves or larger
+    ContourSmoothness,
+    /// Favors melodies using various rhythm values in balanced proportions
+    /// (normalized entropy of the rhythm values)
+    RhythmicVariety,
+    /// Proportion of the notes between the two pitches (included)
+    RangeFit(u7, u7),
+}
+
+impl Fitness for FitnessTerm {
+    fn fitness(&self, phrase: &Phrase) -> f64 {
+        let genes = genes(phrase);
+        let pitches: Vec<u8> = genes.iter().filter_map(|g| g.pitch).collect();
+        if pitches.is_empty() {
+            return 0.;
+        }
+        let proportion = |accept: &dyn Fn(u8) -> bool| {
+            pitches.iter().filter(|p| accept(**p)).count() as f64 / pitches.len() as f64
+        };
+        match self {
+            FitnessTerm::ScaleFit(scale) => proportion(&|p| scale.contains(u7::new(p))),
+            FitnessTerm::RangeFit(low, high) => {
+                proportion(&|p| (low.as_int()..=high.as_int()).contains(&p))
+            }
+            FitnessTerm::ContourSmoothness => {
+                if pitches.len() < 2 {
+                    return 1.;
+                }
+                let total: f64 = pitches
+                    .windows(2)
+                    .map(|w| w[0].abs_diff(w[1]).min(12) as f64)
+                    .sum();
+                1. - total / (12. * (pitches.len() - 1) as f64)
+            }
+            FitnessTerm::RhythmicVariety => {
+                let mut counts: Vec<(u64, usize)> = Vec::new();
+                for gene in genes.iter() {
+                    let ticks = to_ticks(gene.rhythm);
+                    match counts.iter_mut().find(|c| c.0 == ticks) {
+                        Some(c) => c.1 += 1,
+                        None => counts.push((ticks, 1)),
+                    }
+                }
+                if counts.len() < 2 {
+                    return 0.;
+                }
+                let total = genes.len() as f64;
+                let entropy: f64 = counts
+                    .iter()
+                    .map(|c| c.1 as f64 / total)
+                    .map(|p| -p * p.ln())
+                    .sum();
+                entropy / (counts.len() as f64).ln()
+            }
+        }
+    }
+}
+
+impl Fitness for Vec<(f64, FitnessTerm)> {
+    fn fitness(&self, phrase: &Phrase) -> f64 {
+        self.iter().map(|(w, term)| w * term.fitness(phrase)).sum()
+    }
+}
+
+/// Describes a random modification applied to the phrases during an evolution
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Mutation {
+    /// Moves a note by one or two semitones up or down
+    PitchShift,
+    /// Splits a note in two notes of half its rhythm value
+    RhythmSplit,
+    /// Merges two consecutive entries in a single note
+    RhythmMerge,
+    /// Transposes a group of two to four consecutive notes by up to a fifth
+    MotifTransposition,
+}
+
+/// Evolves phrases with a genetic algorithm: at each generation, the phrases are
+/// selected by tournament according to their fitness, crossed over and mutated.
+///
+/// `Chord`s of the initial phrases are reduced to their highest note. The crossover
+/// joins the start of a phrase to the end of another at the same beat, so the evolved
+/// phrases keep the duration of the initial ones.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Evolution {
+    /// The number of phrases of each generation
+    population_size: usize,
+    /// The number of generations
+    generations: usize,
+    /// The probability to mutate each new phrase
+    mutation_rate: f64,
+    /// The probability to create each new phrase by crossover
+    crossover_rate: f64,
+    /// The mutations that can be applied
+    mutations: Vec<Mutation>,
+}
+
+impl Default for Evolution {
+    fn default() -> Self {
+        Self {
+            population_size: 50,
+            generations: 100,
+            mutation_rate: 0.8,
+            crossover_rate: 0.5,
+            mutations: vec![
+                Mutation::PitchShift,
+                Mutation::RhythmSplit,
+                Mutation::RhythmMerge,
+                Mutation::MotifTransposition,
+            ],
+        }
+    }
+}
+
+impl Evolution {
+    /// Returns a new `Evolution` of 100 generations of 50 phrases, using all the
+    /// mutations
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the number of phrases of each generation (at least 2)
+    pub fn set_population_size(&mut self, population_size: usize) {
+        self.population_size = population_size.max(2);
+    }
+
+    /// Sets the number of generations
+    pub fn set_generations(&mut self, generations: usize) {
+        self.generations = generations;
+    }
+
+    /// Sets the probability (between `0.0` and `1.0`) to mutate each new phrase
+    pub fn set_mutation_rate(&mut self, rate: f64) {
+        self.mutation_rate = rate.clamp(0., 1.);
+    }
+
+    /// Sets the probability (between `0.0` and `1.0`) to create each new phrase by
+    /// crossover of two phrases instead of copying one
+    pub fn set_crossover_rate(&mut self, rate: f64) {
+        self.crossover_rate = rate.clamp(0., 1.);
+    }
+
+    /// Sets the mutations that can be applied (one of them is chosen at random for each
+    /// mutated phrase)
+    pub fn set_mutations(&mut self, mutations: Vec<Mutation>) {
+        self.mutations = mutations;
+    }
+
+    /// Runs the evolution and returns the best `Phrase` of each generation with its
+    /// fitness, starting with the first generation. The best `Phrase` of a
+    /// generation is always kept in the next one, so the fitness never decreases.
+    /// The same seed always gives the same result.
+    ///
+    /// # Arguments
+    ///
+    /// * `initial` - The phrases of the first generation (completed by mutated copies
+    ///   if there are fewer than the population size)
+    /// * `fitness` - The evaluation of the phrases
+    /// * `seed` - The seed of the random choices
+    ///
+    /// # Errors
+    ///
+    /// * `GenerationError::EmptyPopulation` if `initial` is empty
+    /// * Any error returned when creating the notes
+    pub fn run<F: Fitness + ?Sized>(
+        &self,
+        initial: &[Phrase],
+        fitness: &F,
+        seed: u64,
+    ) -> Result<Vec<(Phrase, f64)>> {
+        if initial.is_empty() {
+            return Err(GenerationError::EmptyPopulation.into());
+        }
+        let mut rng = Rng::new(seed);
+        let mut population: Vec<Vec<Gene>> = initial.iter().map(genes).collect();
+        while population.len() < self.population_size {
+            let mut copy = population[rng.below(initial.len())].clone();
+            self.mutate(&mut copy, &mut rng);
+            population.push(copy);
+        }
+
+        let mut best = Vec::with_capacity(self.generations + 1);
+        let mut scores = self.evaluate(&population, fitness)?;
+        for generation in 0..=self.generations {
+            let elite = (0..population.len())
+                .max_by(|a, b| scores[*a].total_cmp(&scores[*b]))
+                .unwrap_or(0);
+            best.push((to_phrase(&population[elite])?, scores[elite]));
+            if generation == self.generations {
+                break;
+            }
+            let mut next = vec![population[elite].clone()];
+            while next.len() < self.population_size {
+                let parent = Self::select(&scores, &mut rng);
+                let mut child = if rng.next_f64() < self.crossover_rate {
+                    let other = Self::select(&scores, &mut rng);
+                    crossover(&population[parent], &population[other], &mut rng)
+                } else {
+                    population[parent].clone()
+                };
+                if rng.next_f64() < self.mutation_rate {
+                    self.mutate(&mut child, &mut rng);
+                }
+                next.push(child);
+            }
+            population = next;
+            scores = self.evaluate(&population, fitness)?;
+        }
+        Ok(best)
+    }
+
+    /// Returns the fitness of each member of the population
+    fn evaluate<F: Fitness + ?Sized>(
+        &self,
+        population: &[Vec<Gene>],
+        fitness: &F,
+    ) -> Result<Vec<f64>> {
+        population
+            .iter()
+            .map(|g| Ok(fitness.fitness(&to_phrase(g)?)))
+            .collect()
+    }
+
+    /// Returns the index of the best of three random members (tournament selection)
+    fn select(scores: &[f64], rng: &mut Rng) -> usize {
+        (0..3)
+            .map(|_| rng.below(scores.len()))
+            .max_by(|a, b| scores[*a].total_cmp(&scores[*b]))
+            .unwrap_or(0)
+    }
+
+    /// Applies a random mutation to the genes
+    fn mutate(&self, genes: &mut Vec<Gene>, rng: &mut Rng) {
+        if self.mutations.is_empty() || genes.is_empty() {
+            return;
+        }
+        let notes: Vec<usize> = (0..genes.len())
+            .filter(|i| genes[*i].pitch.is_some())
+            .collect();
+        match self.mutations[rng.below(self.mutations.len())] {
+            Mutation::PitchShift => {
+                if let Some(i) = notes.get(rng.below(notes.len().max(1))) {
+                    let shift = [-2, -1, 1, 2][rng.below(4)];
+                    genes[*i].transpose(shift);
+                }
+            }
+            Mutation::RhythmSplit => {
+                let i = rng.below(genes.len());
+                if genes[i].rhythm >= 2. * MIN_RHYTHM {
+                    genes[i].rhythm /= 2.;
+                    genes.insert(i + 1, genes[i]);
+                }
+            }
+            Mutation::RhythmMerge => {
+                if genes.len() >= 2 {
+                    let i = rng.below(genes.len() - 1);
+                    let next = genes.remove(i + 1);
+                    genes[i].rhythm += next.rhythm;
+                    genes[i].pitch = genes[i].pitch.or(next.pitch);
+                }
+            }
+            Mutation::MotifTransposition => {
+                let length = 2 + rng.below(3);
+                let start = rng.below(genes.len());
+                let shift = rng.below(15) as i32 - 7;
+                for gene in genes.iter_mut().skip(start).take(length) {
+                    gene.transpose(shift);
+                }
+            }
+        }
+    }
+}
+
+/// A note or a rest of a phrase being evolved
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Gene {
+    /// The pitch of the note (`None` for a rest)
+    pitch: Option<u8>,
+    rhythm: f64,
+    dynamic: u7,
+}
+
+impl Gene {
+    /// Moves the pitch by `shift` semitones (kept between 0 and 127)
+    fn transpose(&mut self, shift: i32) {
+        self.pitch = self.pitch.map(|p| (p as i32 + shift).clamp(0, 127) as u8);
+    }
+}
+
+/// Returns the genes of a `Phrase`. `Chord`s are reduced to their highest note.
+fn genes(phrase: &Phrase) -> Vec<Gene> {
+    phrase
+        .entries()
+        .iter()
+        .map(|entry| match entry {
+            PhraseEntry::Note(n) => Gene {
+                pitch: Some(n.pitch().as_int()),
+                rhythm: n.rhythm(),
+                dynamic: n.dynamic(),
+            },
+            PhraseEntry::Chord(c) => {
+                let top = c.notes().iter().max_by_key(|n| n.pitch());
+                Gene {
+                    pitch: top.map(|n| n.pitch().as_int()),
+                    rhythm: c.rhythm(),
+                    dynamic: top.map_or(crate::dynamic::MF, Note::dynamic),
+                }
+            }
+            PhraseEntry::Rest(r) => Gene {
+                pitch: None,
+                rhythm: *r,
+                dynamic: crate::dynamic::MF,
+            },
+        })
+        .collect()
+}
+
+/// Returns the `Phrase` of the genes
+fn to_phrase(genes: &[Gene]) -> Result<Phrase> {
+    let mut phrase = Phrase::new();
+    for gene in genes {
+        match gene.pitch {
+            Some(p) => phrase.add_note(Note::new(u7::new(p), gene.rhythm, gene.dynamic)?),
+            None => phrase.add_rest(gene.rhythm),
+        }
+    }
+    Ok(phrase)
+}
+
+/// Returns the start of `first` until an onset chosen at random, followed by the end of
+/// `second` from the same beat (the entry of `second` playing at that beat is shortened)
+fn crossover(first: &[Gene], second: &[Gene], rng: &mut Rng) -> Vec<Gene> {
+    if first.is_empty() {
+        return second.to_vec();
+    }
+    let cut = rng.below(first.len());
+    let cut_beat: f64 = first[..cut].iter().map(|g| g.rhythm).sum();
+    let mut child = first[..cut].to_vec();
+    let mut beat = 0.;
+    for gene in second {
+        let end = beat + gene.rhythm;
+        if end - cut_beat >= 0.000_001 {
+            let mut gene = *gene;
+            gene.rhythm = end - beat.max(cut_beat);
+            child.push(gene);
+        }
+        beat = end;
+    }
+    child
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Evolution, Fitness, FitnessTerm};
+    use crate::composition::{Scale, ScaleMode};
+    use crate::num::u7;
+    use crate::*;
+
+    #[test]
+    fn evolve_melody() -> Result<()> {
+        let initial = Phrase::from_notes_sequence(Note::new_sequence(
+            rhythm::CROTCHET,
+            dynamic::MF,
+            [61, 75, 54, 70, 63, 49, 66, 58].map(u7::new),
+        ))?;
+        let scale = Scale::new(u7::new(60), ScaleMode::Ionian);
+        let fitness = vec![
+            (2., FitnessTerm::ScaleFit(scale)),
+            (1., FitnessTerm::ContourSmoothness),
+            (0.5, FitnessTerm::RhythmicVariety),
+        ];
+        let mut evolution = Evolution::new();
+        evolution.set_generations(40);
+        let best = evolution.run(std::slice::from_ref(&initial), &fitness, 3)?;
+        assert_eq!(best.len(), 41);
+        assert!(best.windows(2).all(|w| w[1].1 >= w[0].1));
+        assert!(best[40].1 > best[0].1);
+        assert!(best[0].1 >= fitness.fitness(&initial));
+        assert!((best[40].0.duration() - 8.).abs() < 1e-9);
+        assert_eq!(best, evolution.run(&[initial], &fitness, 3)?);
+        Ok(())
+    }
+}
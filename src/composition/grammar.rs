@@ -0,0 +1,275 @@
+use std::collections::BTreeMap;
+
+use crate::composition::random::Rng;
+use crate::composition::Scale;
+use crate::errors::NoteError;
+use crate::num::u7;
+use crate::{Note, Phrase, Result};
+
+/// Describes the musical action of a symbol of a `Grammar`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GrammarAction {
+    /// Plays a note on the current degree
+    Play,
+    /// Moves the current degree by the given number of scale steps, then plays a note
+    /// (e.g. `PlayStep(1)` plays the next degree of the scale)
+    PlayStep(i32),
+    /// Moves the current degree by the given number of scale steps without playing
+    Step(i32),
+    /// Inserts a rest
+    Rest,
+    /// Multiplies the current rhythm value by the given factor (e.g. `0.5` doubles the
+    /// speed)
+    ScaleRhythm(f64),
+    /// Saves the current degree and rhythm value on the stack
+    Push,
+    /// Restores the degree and rhythm value last saved on the stack (no effect if the
+    /// stack is empty)
+    Pop,
+}
+
+/// A generative grammar whose symbols are characters that can be rewritten by rules and
+/// interpreted as musical actions.
+///
+/// The symbols can be expanded as an L-system (all the symbols are rewritten in parallel
+/// at each iteration) or as a context-free grammar (the leftmost symbol that has rules
+/// is rewritten until none is left). Symbols with several rules are rewritten by one of
+/// them, chosen at random according to their weights. The expanded symbols are then
+/// rendered as a `Phrase` by running their actions on a `Scale`. Symbols without action
+/// are ignored by the rendering.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Grammar {
+    /// The initial symbols
+    axiom: String,
+    /// The replacements of each symbol with their weight
+    rules: BTreeMap<char, Vec<(String, u32)>>,
+    /// The actions run for each symbol
+    actions: BTreeMap<char, Vec<GrammarAction>>,
+}
+
+impl Grammar {
+    /// Returns a new `Grammar` without rules or actions
+    ///
+    /// # Arguments
+    ///
+    /// * `axiom` - The initial symbols
+    pub fn new(axiom: &str) -> Self {
+        Self {
+            axiom: axiom.to_string(),
+            rules: BTreeMap::new(),
+            actions: BTreeMap::new(),
+        }
+    }
+
+    /// Returns a `Grammar` with the usual actions of the symbols:
+    /// `+` plays the next degree, `-` plays the previous degree, `=` repeats the current
+    /// degree, `.` is a rest, `>` doubles the speed, `<` halves the speed, `[` pushes and
+    /// `]` pops the stack
+    pub fn with_default_actions(axiom: &str) -> Self {
+        let mut grammar = Self::new(axiom);
+        grammar.set_action('+', vec![GrammarAction::PlayStep(1)]);
+        grammar.set_action('-', vec![GrammarAction::PlayStep(-1)]);
+        grammar.set_action('=', vec![GrammarAction::Play]);
+        grammar.set_action('.', vec![GrammarAction::Rest]);
+        grammar.set_action('>', vec![GrammarAction::ScaleRhythm(0.5)]);
+        grammar.set_action('<', vec![GrammarAction::ScaleRhythm(2.)]);
+        grammar.set_action('[', vec![GrammarAction::Push]);
+        grammar.set_action(']', vec![GrammarAction::Pop]);
+        grammar
+    }
+
+    /// Adds a rule that rewrites `symbol` into `replacement`
+    pub fn add_rule(&mut self, symbol: char, replacement: &str) {
+        self.add_weighted_rule(symbol, replacement, 1);
+    }
+
+    /// Adds a rule that rewrites `symbol` into `replacement`. When a symbol has several
+    /// rules, each rule is chosen with a probability proportional to its weight.
+    pub fn add_weighted_rule(&mut self, symbol: char, replacement: &str, weight: u32) {
+        self.rules
+            .entry(symbol)
+            .or_default()
+            .push((replacement.to_string(), weight));
+    }
+
+    /// Sets the actions run when rendering `symbol`
+    pub fn set_action(&mut self, symbol: char, actions: Vec<GrammarAction>) {
+        self.actions.insert(symbol, actions);
+    }
+
+    /// Returns the initial symbols
+    pub fn axiom(&self) -> &str {
+        &self.axiom
+    }
+
+    /// Returns the symbols obtained after `iterations` parallel rewritings of the axiom
+    /// (L-system). Symbols without rules are kept as is.
+    pub fn expand_lsystem(&self, iterations: usize, seed: u64) -> String {
+        let mut rng = Rng::new(seed);
+        let mut symbols = self.axiom.clone();
+        for _ in 0..iterations {
+            symbols = symbols
+                .chars()
+                .map(|c| match self.choose(c, &mut rng) {
+                    Some(replacement) => replacement.to_string(),
+                    None => c.to_string(),
+                })
+                .collect();
+        }
+        symbols
+    }
+
+    /// Returns the symbols obtained by rewriting the leftmost symbol that has rules,
+    /// until no symbol has rules or after `max_steps` rewritings (context-free grammar)
+    pub fn expand_context_free(&self, max_steps: usize, seed: u64) -> String {
+        let mut rng = Rng::new(seed);
+        let mut symbols: Vec<char> = self.axiom.chars().collect();
+        // the symbols before `start` have no rules
+        let mut start = 0;
+        for _ in 0..max_steps {
+            let Some(index) =
+                (start..symbols.len()).find(|i| self.rules.contains_key(&symbols[*i]))
+            else {
+                break;
+            };
+            let replacement = self.choose(symbols[index], &mut rng).unwrap_or_default();
+            symbols.splice(index..=index, replacement.chars());
+            start = index;
+        }
+        symbols.into_iter().collect()
+    }
+
+    /// Returns a `Phrase` that runs the actions of the symbols.
+    ///
+    /// # Arguments
+    ///
+    /// * `symbols` - The symbols to render (usually the result of an expansion)
+    /// * `scale` - The `Scale` of the degrees
+    /// * `octave` - The octave of the tonic, which is the initial degree (same
+    ///   convention as `compute_pitch`)
+    /// * `rhythm` - The initial rhythm value of the notes and rests
+    /// * `dynamic` - The dynamic of the notes
+    ///
+    /// # Errors
+    ///
+    /// * `NoteError::InvalidPitch` if a degree is outside of the MIDI range
+    /// * `NoteError::InvalidRhythm` if a rhythm value becomes invalid
+    pub fn render(
+        &self,
+        symbols: &str,
+        scale: Scale,
+        octave: u8,
+        rhythm: f64,
+        dynamic: u7,
+    ) -> Result<Phrase> {
+        let mut phrase = Phrase::new();
+        let mut step = octave as i32 * scale.num_degrees() as i32;
+        let mut rhythm = rhythm;
+        let mut stack = Vec::new();
+        for symbol in symbols.chars() {
+            let Some(actions) = self.actions.get(&symbol) else {
+                continue;
+            };
+            for action in actions {
+                match action {
+                    GrammarAction::Play => {
+                        phrase.add_note(Note::new(scale.step_pitch(step)?, rhythm, dynamic)?)
+                    }
+                    GrammarAction::PlayStep(steps) => {
+                        step += steps;
+                        phrase.add_note(Note::new(scale.step_pitch(step)?, rhythm, dynamic)?)
+                    }
+                    GrammarAction::Step(steps) => step += steps,
+                    GrammarAction::Rest => {
+                        if rhythm < 0.000_001 {
+                            return Err(NoteError::InvalidRhythm(rhythm).into());
+                        }
+                        phrase.add_rest(rhythm)
+                    }
+                    GrammarAction::ScaleRhythm(factor) => rhythm *= factor,
+                    GrammarAction::Push => stack.push((step, rhythm)),
+                    GrammarAction::Pop => {
+                        if let Some(state) = stack.pop() {
+                            (step, rhythm) = state;
+                        }
+                    }
+                }
+            }
+        }
+        Ok(phrase)
+    }
+
+    /// Returns a replacement of `symbol` chosen according to the weights of its rules,
+    /// or `None` if it has no rules
+    fn choose(&self, symbol: char, rng: &mut Rng) -> Option<&str> {
+        let rules = self.rules.get(&symbol)?;
+        let total: u32 = rules.iter().map(|r| r.1).sum();
+        if total == 0 {
+            return rules.first().map(|r| r.0.as_str());
+        }
+        let mut target = rng.below(total as usize) as u32;
+        for (replacement, weight) in rules {
+            if target < *weight {
+                return Some(replacement);
+            }
+            target -= weight;
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Grammar, GrammarAction};
+    use crate::composition::{Scale, ScaleMode};
+    use crate::num::u7;
+    use crate::*;
+
+    fn pitches(phrase: &Phrase) -> Vec<Option<u8>> {
+        phrase
+            .entries()
+            .iter()
+            .map(|e| match e {
+                PhraseEntry::Note(n) => Some(n.pitch().as_int()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn grammar() -> Result<()> {
+        let scale = Scale::new(u7::new(60), ScaleMode::Ionian);
+
+        // Fibonacci-like L-system: A -> AB, B -> A
+        let mut lsystem = Grammar::new("A");
+        lsystem.add_rule('A', "AB");
+        lsystem.add_rule('B', "A");
+        lsystem.set_action('A', vec![GrammarAction::PlayStep(1)]);
+        lsystem.set_action('B', vec![GrammarAction::Rest, GrammarAction::Step(-2)]);
+        let symbols = lsystem.expand_lsystem(4, 0);
+        assert_eq!(symbols, "ABAABABA");
+        let phrase = lsystem.render(&symbols, scale, 5, rhythm::QUAVER, dynamic::MF)?;
+        assert_eq!(
+            pitches(&phrase),
+            vec![
+                Some(62),
+                None,
+                Some(60),
+                Some(62),
+                None,
+                Some(60),
+                None,
+                Some(59)
+            ]
+        );
+
+        let mut motif = Grammar::with_default_actions("M.M");
+        motif.add_rule('M', "[>++]=");
+        let symbols = motif.expand_context_free(10, 0);
+        assert_eq!(symbols, "[>++]=.[>++]=");
+        let phrase = motif.render(&symbols, scale, 5, rhythm::CROTCHET, dynamic::MF)?;
+        assert_eq!(phrase.duration(), 5.);
+        assert_eq!(pitches(&phrase)[..3], [Some(62), Some(64), Some(60)]);
+        Ok(())
+    }
+}
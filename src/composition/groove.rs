@@ -0,0 +1,378 @@
+use crate::composition::random::Rng;
+use crate::composition::{Step, StepPattern, StepSequence};
+use crate::errors::ScoreError;
+use crate::num::u7;
+use crate::{dynamic, DrumSound, Metadata, Part, Result};
+
+/// Describes the style of a `DrumGroove`
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DrumStyle {
+    /// Straight quavers on the hi-hat, backbeat on the snare
+    #[default]
+    Rock,
+    /// Semiquavers on the hi-hat, syncopated bass drum and ghost notes on the snare
+    Funk,
+    /// Swung (triplet) quavers on the hi-hat, backbeat on the snare
+    Shuffle,
+    /// Bossa nova with the rim clicks of the clave over two bars of 4/4
+    BossaNova,
+    /// Jazz ride pattern with the hi-hat pedal on the backbeat and a feathered bass drum
+    SwingRide,
+    /// 6/8 feel: quavers grouped by three with the snare on the second pulse
+    SixEight,
+    /// 12/8 feel (blues ballad): quavers grouped by three with the snare on the second
+    /// and fourth pulses
+    TwelveEight,
+}
+
+/// A drum cell of a style: the number of steps per pulse, the number of bars spanned by
+/// the grids (`1` if they restart at each bar) and a grid per drum sound spanning whole
+/// pulses, the time-keeping cymbal first
+type Cell = (usize, usize, &'static [(DrumSound, &'static str)]);
+
+impl DrumStyle {
+    /// Returns the drum cell of the style.
+    ///
+    /// Grid characters: `X` is an accent, `x` a normal hit, `o` a ghost note and `?` an
+    /// optional hit played according to the variation of the groove.
+    fn cell(&self) -> Cell {
+        use DrumSound::*;
+        match self {
+            DrumStyle::Rock => (
+                4,
+                1,
+                &[
+                    (ClosedHiHat, "x.x. x.x. x.x. x.x."),
+                    (BassDrum, "X... ...? X.x. ...."),
+                    (AcousticSnare, ".... X... .... X..?"),
+                ],
+            ),
+            DrumStyle::Funk => (
+                4,
+                1,
+                &[
+                    (ClosedHiHat, "XxXx XxXx XxXx XxXx"),
+                    (BassDrum, "X..x ..x. ..X. .?.."),
+                    (AcousticSnare, ".o.. X..o .o?. X..o"),
+                ],
+            ),
+            DrumStyle::Shuffle => (
+                3,
+                1,
+                &[
+                    (ClosedHiHat, "X.x X.x X.x X.x"),
+                    (BassDrum, "X.. ..? X.? ..."),
+                    (AcousticSnare, "..o X.. ..o X.."),
+                ],
+            ),
+            DrumStyle::BossaNova => (
+                4,
+                2,
+                &[
+                    (ClosedHiHat, "x.x. x.x. x.x. x.x. x.x. x.x. x.x. x.x."),
+                    (BassDrum, "X..x X..x X..x X..x X..x X..x X..x X..x"),
+                    (SideStick, "x..x ..x. ..x. .x.. ..x. .x.. x..x ...."),
+                ],
+            ),
+            DrumStyle::SwingRide => (
+                3,
+                1,
+                &[
+                    (RideCymbal1, "x.. X.x x.. X.x"),
+                    (PedalHiHat, "... x.. ... x.."),
+                    (BassDrum, "o.. o.. o.. o.."),
+                    (AcousticSnare, "... ..? ... ..?"),
+                ],
+            ),
+            DrumStyle::SixEight => (
+                3,
+                1,
+                &[
+                    (ClosedHiHat, "Xxx Xxx"),
+                    (BassDrum, "X.. ..?"),
+                    (AcousticSnare, "... X.."),
+                ],
+            ),
+            DrumStyle::TwelveEight => (
+                3,
+                1,
+                &[
+                    (ClosedHiHat, "Xxx Xxx Xxx Xxx"),
+                    (BassDrum, "X.. ..x X.. ..?"),
+                    (AcousticSnare, "... X.. ... X.."),
+                ],
+            ),
+        }
+    }
+}
+
+/// The drums of the fills, from the highest to the lowest
+const FILL_DRUMS: [DrumSound; 6] = [
+    DrumSound::AcousticSnare,
+    DrumSound::HighTom,
+    DrumSound::HighMidTom,
+    DrumSound::LowMidTom,
+    DrumSound::LowTom,
+    DrumSound::HighFloorTom,
+];
+
+/// Generates drum parts in a given style.
+///
+/// The grooves follow the time signature of a `Metadata`: in compound time signatures
+/// (6/8, 9/8, 12/8) a pulse is a dotted beat, otherwise it is a beat, and each style
+/// divides the pulse in its own subdivision (e.g. a shuffle in 12/8 has one triplet per
+/// dotted crotchet, a 12/8 feel in 4/4 has one triplet per crotchet). The patterns of
+/// the styles are repeated over the pulses of the bars, so a bossa nova in 3/4 shifts
+/// its clave from bar to bar.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DrumGroove {
+    /// The style of the groove
+    style: DrumStyle,
+    /// The number of bars between two fills (`0` for no fills)
+    fill_every: usize,
+    /// The number of pulses at the end of a bar played by the fills
+    fill_length: usize,
+    /// True if a crash cymbal is played on the first downbeat and after each fill
+    crash: bool,
+    /// The probability that the optional hits of the style are played
+    variation: f64,
+}
+
+impl DrumGroove {
+    /// Returns a new `DrumGroove` with a fill of two pulses every four bars, crash
+    /// cymbals, and optional hits played half the time
+    pub fn new(style: DrumStyle) -> Self {
+        Self {
+            style,
+            fill_every: 4,
+            fill_length: 2,
+            crash: true,
+            variation: 0.5,
+        }
+    }
+
+    /// Sets the number of bars between two fills, which are played at the end of their
+    /// bar (`0` for no fills)
+    pub fn set_fill_every(&mut self, bars: usize) {
+        self.fill_every = bars;
+    }
+
+    /// Sets the number of pulses at the end of a bar played by the fills (at most the
+    /// whole bar)
+    pub fn set_fill_length(&mut self, pulses: usize) {
+        self.fill_length = pulses;
+    }
+
+    /// Sets if a crash cymbal is played on the first downbeat and after each fill
+    pub fn set_crash(&mut self, crash: bool) {
+        self.crash = crash;
+    }
+
+    /// Sets the probability that the optional hits of the style are played, between
+    /// `0.0` (plain groove) and `1.0` (busiest groove)
+    pub fn set_variation(&mut self, variation: f64) {
+        self.variation = variation.clamp(0., 1.);
+    }
+
+    /// Returns the style of the groove
+    pub fn style(&self) -> DrumStyle {
+        self.style
+    }
+
+    /// Returns the `StepSequence` of the groove, one pattern per bar
+    ///
+    /// # Arguments
+    ///
+    /// * `bars` - The number of bars
+    /// * `metadata` - The `Metadata` giving the time signature
+    /// * `seed` - The seed of the fills and of the optional hits
+    ///
+    /// # Errors
+    ///
+    /// * `ScoreError::InvalidTimeSignature` if the numerator or the denominator of the
+    ///   time signature is 0
+    pub fn to_sequence(&self, bars: usize, metadata: &Metadata, seed: u64) -> Result<StepSequence> {
+        let (numerator, denominator) = (metadata.time_numerator, metadata.time_denominator);
+        if numerator == 0 || denominator == 0 {
+            return Err(ScoreError::InvalidTimeSignature(numerator, denominator).into());
+        }
+        let beat = 4. / denominator as f64;
+        let (pulses, pulse) = if denominator >= 8 && numerator % 3 == 0 && numerator > 3 {
+            (numerator as usize / 3, beat * 3.)
+        } else {
+            (numerator as usize, beat)
+        };
+        let (steps_per_pulse, _, _) = self.style.cell();
+        let mut rng = Rng::new(seed);
+        let mut sequence = StepSequence::new();
+        for bar in 0..bars {
+            let fill = self.fill_every > 0 && (bar + 1) % self.fill_every == 0;
+            let crash =
+                self.crash && (bar == 0 || (self.fill_every > 0 && bar % self.fill_every == 0));
+            let mut pattern = self.bar(bar, pulses, pulse / steps_per_pulse as f64)?;
+            if fill {
+                self.add_fill(&mut pattern, pulses, &mut rng);
+            }
+            if crash {
+                let (_, _, rows) = self.style.cell();
+                pattern.clear_step(rows[0].0, 0);
+                pattern.set_step(DrumSound::CrashCymbal1, 0, Step::new(dynamic::FF));
+                pattern.set_step(DrumSound::BassDrum, 0, Step::new(dynamic::FF));
+            }
+            sequence.add_pattern(pattern, 1);
+        }
+        Ok(sequence)
+    }
+
+    /// Returns a percussion `Part` playing the groove (see `to_sequence`)
+    ///
+    /// # Errors
+    ///
+    /// * Same as `to_sequence`
+    pub fn to_part(&self, bars: usize, metadata: &Metadata, seed: u64) -> Result<Part> {
+        let mut part = self
+            .to_sequence(bars, metadata, seed)?
+            .to_percussion_part(seed)?;
+        part.set_name("Drums");
+        Ok(part)
+    }
+
+    /// Returns the pattern of the bar `bar` without fill nor crash
+    fn bar(&self, bar: usize, pulses: usize, step: f64) -> Result<StepPattern> {
+        let (steps_per_pulse, cell_bars, rows) = self.style.cell();
+        let num_steps = pulses * steps_per_pulse;
+        // one-bar cells restart at each bar, longer ones continue over the bars
+        let offset = if cell_bars > 1 { bar * num_steps } else { 0 };
+        let mut pattern = StepPattern::new(num_steps, step)?;
+        for (sound, grid) in rows {
+            let cells: Vec<char> = grid.chars().filter(|c| !c.is_whitespace()).collect();
+            for i in 0..num_steps {
+                let velocity = match cells[(offset + i) % cells.len()] {
+                    'X' => dynamic::FF,
+                    'x' => dynamic::MF,
+                    'o' => dynamic::PP,
+                    '?' => {
+                        pattern.set_step(
+                            *sound,
+                            i,
+                            Step {
+                                probability: self.variation,
+                                ..Step::new(dynamic::MP)
+                            },
+                        );
+                        continue;
+                    }
+                    _ => continue,
+                };
+                pattern.set_step(*sound, i, Step::new(velocity));
+            }
+        }
+        Ok(pattern)
+    }
+
+    /// Replaces the last pulses of a bar by a fill going down the toms, with a crescendo
+    fn add_fill(&self, pattern: &mut StepPattern, pulses: usize, rng: &mut Rng) {
+        let (steps_per_pulse, _, _) = self.style.cell();
+        let length = self.fill_length.clamp(1, pulses) * steps_per_pulse;
+        let start = pattern.num_steps() - length;
+        let sounds: Vec<u7> = pattern.rows().iter().map(|r| r.pitch()).collect();
+        for i in start..pattern.num_steps() {
+            for sound in sounds.iter() {
+                pattern.clear_step(*sound, i);
+            }
+        }
+        for k in 0..length {
+            // some inner steps are left silent to vary the rhythm of the fill
+            if k > 0 && k < length - 1 && rng.below(4) == 0 {
+                continue;
+            }
+            let position = k * FILL_DRUMS.len() / length + rng.below(2);
+            let drum = FILL_DRUMS[position.min(FILL_DRUMS.len() - 1)];
+            let velocity = dynamic::MF.as_int()
+                + ((dynamic::FF.as_int() - dynamic::MF.as_int()) as usize * k / length) as u8;
+            pattern.set_step(drum, start + k, Step::new(u7::new(velocity)));
+        }
+        pattern.set_step(DrumSound::BassDrum, start, Step::new(dynamic::F));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DrumGroove, DrumStyle};
+    use crate::errors::ScoreError;
+    use crate::*;
+
+    fn metadata(numerator: u8, denominator: u8) -> Metadata {
+        Metadata {
+            time_numerator: numerator,
+            time_denominator: denominator,
+            ..Default::default()
+        }
+    }
+
+    fn onsets(part: &Part, sound: DrumSound) -> Vec<f64> {
+        let mut time = 0.;
+        let mut onsets = Vec::new();
+        for entry in part.phrases()[0].1.entries() {
+            let notes = match entry {
+                PhraseEntry::Note(n) => vec![n.clone()],
+                PhraseEntry::Chord(c) => c.notes().to_vec(),
+                PhraseEntry::Rest(_) => vec![],
+            };
+            if notes.iter().any(|n| n.pitch() == sound.pitch()) {
+                onsets.push(time);
+            }
+            time += entry.rhythm();
+        }
+        onsets
+    }
+
+    #[test]
+    fn drum_grooves() -> Result<()> {
+        let mut rock = DrumGroove::new(DrumStyle::Rock);
+        rock.set_variation(0.);
+        let part = rock.to_part(4, &metadata(4, 4), 3)?;
+        assert!(part.is_percussion());
+        assert_eq!(part.duration(), 16.);
+        assert_eq!(onsets(&part, DrumSound::CrashCymbal1), vec![0.]);
+        // backbeat in the first three bars, fill in the last two beats
+        assert_eq!(
+            onsets(&part, DrumSound::AcousticSnare)[..6],
+            [1., 3., 5., 7., 9., 11.]
+        );
+        assert!(onsets(&part, DrumSound::ClosedHiHat)
+            .iter()
+            .all(|t| *t < 14.));
+        assert_eq!(part, rock.to_part(4, &metadata(4, 4), 3)?);
+
+        let mut twelve = DrumGroove::new(DrumStyle::TwelveEight);
+        twelve.set_fill_every(0);
+        twelve.set_crash(false);
+        let part = twelve.to_part(2, &metadata(12, 8), 0)?;
+        assert_eq!(part.duration(), 12.);
+        assert_eq!(onsets(&part, DrumSound::ClosedHiHat).len(), 24);
+        assert_eq!(
+            onsets(&part, DrumSound::AcousticSnare),
+            vec![1.5, 4.5, 7.5, 10.5]
+        );
+
+        // in 3/4, each bar restarts the one-bar rock cell
+        let mut waltz = DrumGroove::new(DrumStyle::Rock);
+        waltz.set_variation(0.);
+        waltz.set_fill_every(0);
+        waltz.set_crash(false);
+        let part = waltz.to_part(2, &metadata(3, 4), 0)?;
+        for sound in [DrumSound::BassDrum, DrumSound::AcousticSnare] {
+            let (first, second): (Vec<f64>, Vec<f64>) =
+                onsets(&part, sound).iter().partition(|t| **t < 3.);
+            assert_eq!(second, first.iter().map(|t| t + 3.).collect::<Vec<_>>());
+        }
+        assert_eq!(onsets(&part, DrumSound::AcousticSnare), vec![1., 4.]);
+
+        assert!(matches!(
+            rock.to_part(1, &Metadata::default(), 0),
+            Err(Error::Score(ScoreError::InvalidTimeSignature(0, 0)))
+        ));
+        Ok(())
+    }
+}
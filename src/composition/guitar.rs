@@ -0,0 +1,411 @@
+use crate::composition::Harmony;
+use crate::errors::NoteError;
+use crate::num::u7;
+use crate::{Chord, Note, Phrase, Result};
+
+/// Number of frets that the fingers can cover without moving the hand
+pub(crate) const HAND_SPAN: u8 = 3;
+/// Maximum number of fingers available to fret notes (a barre counts as one)
+const MAX_FINGERS: usize = 4;
+/// Length of the notes of muted strums
+const MUTED_NOTE_LENGTH: f64 = 0.05;
+
+/// Describes the pitch of each open string of a fretted instrument, from the lowest string
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Tuning {
+    strings: Vec<u7>,
+}
+
+impl Tuning {
+    /// Returns a new `Tuning` with the given open string pitches, from the lowest string
+    pub fn new(strings: Vec<u7>) -> Self {
+        Self { strings }
+    }
+
+    /// Returns the standard guitar tuning: E3 A3 D4 G4 B4 E5 (pitches 40 to 64)
+    pub fn standard() -> Self {
+        Self::from_pitches(&[40, 45, 50, 55, 59, 64])
+    }
+
+    /// Returns the drop D guitar tuning: D3 A3 D4 G4 B4 E5 (pitches 38 to 64)
+    pub fn drop_d() -> Self {
+        Self::from_pitches(&[38, 45, 50, 55, 59, 64])
+    }
+
+    /// Returns the open G guitar tuning: D3 G3 D4 G4 B4 D5 (pitches 38 to 62)
+    pub fn open_g() -> Self {
+        Self::from_pitches(&[38, 43, 50, 55, 59, 62])
+    }
+
+    /// Returns the standard tuning of a four-string bass: E2 A2 D3 G3 (pitches 28 to 43)
+    pub fn bass() -> Self {
+        Self::from_pitches(&[28, 33, 38, 43])
+    }
+
+    /// Returns the standard tuning of a five-string bass: B1 E2 A2 D3 G3 (pitches 23 to 43)
+    pub fn five_string_bass() -> Self {
+        Self::from_pitches(&[23, 28, 33, 38, 43])
+    }
+
+    /// Returns the pitches of the open strings, from the lowest string
+    pub fn strings(&self) -> &[u7] {
+        &self.strings
+    }
+
+    fn from_pitches(pitches: &[u8]) -> Self {
+        Self::new(pitches.iter().map(|p| u7::new(*p)).collect())
+    }
+}
+
+/// Describes how a chord is played on a fretted instrument: the fret pressed on each
+/// string (from the lowest string), `Some(0)` for an open string (or the capo fret)
+/// and `None` for a muted string.
+/// Frets are counted from the nut, regardless of the capo.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ChordShape {
+    frets: Vec<Option<u8>>,
+}
+
+impl ChordShape {
+    /// Returns a new `ChordShape` from the frets of each string, from the lowest string
+    pub fn new(frets: Vec<Option<u8>>) -> Self {
+        Self { frets }
+    }
+
+    /// Returns the fret of each string, from the lowest string (`None` if muted)
+    pub fn frets(&self) -> &[Option<u8>] {
+        &self.frets
+    }
+}
+
+/// Describes a stroke of a strumming pattern
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum StrumStroke {
+    /// Strums from the lowest string to the highest
+    Down,
+    /// Strums from the highest string to the lowest
+    Up,
+    /// Percussive down strum with the strings muted by the fretting hand
+    MutedDown,
+    /// Percussive up strum with the strings muted by the fretting hand
+    MutedUp,
+    /// No stroke
+    Rest,
+}
+
+/// A fretted instrument (guitar, bass...) with a tuning and a capo, that finds playable
+/// chord shapes and renders strumming patterns
+#[derive(Debug, Clone, PartialEq)]
+pub struct Guitar {
+    /// The tuning of the open strings (without capo)
+    tuning: Tuning,
+    /// The fret of the capo (`0` for no capo)
+    capo: u8,
+    /// The number of frets
+    frets: u8,
+    /// The delay in beats between two strings of a strum
+    strum_spread: f64,
+}
+
+impl Guitar {
+    /// Returns a new `Guitar` with the given tuning, without capo, with 20 frets
+    pub fn new(tuning: Tuning) -> Self {
+        Self {
+            tuning,
+            capo: 0,
+            frets: 20,
+            strum_spread: 0.02,
+        }
+    }
+
+    /// Sets the fret of the capo (`0` to remove it)
+    pub fn set_capo(&mut self, capo: u8) {
+        self.capo = capo;
+    }
+
+    /// Sets the number of frets of the instrument
+    pub fn set_frets(&mut self, frets: u8) {
+        self.frets = frets;
+    }
+
+    /// Sets the delay in beats between the onsets of two consecutive strings in a strum
+    pub fn set_strum_spread(&mut self, spread: f64) {
+        self.strum_spread = spread.max(0.);
+    }
+
+    /// Returns the tuning of the instrument
+    pub fn tuning(&self) -> &Tuning {
+        &self.tuning
+    }
+
+    /// Returns the fret of the capo (`0` for no capo)
+    pub fn capo(&self) -> u8 {
+        self.capo
+    }
+
+    /// Returns the number of frets of the instrument
+    pub fn frets(&self) -> u8 {
+        self.frets
+    }
+
+    /// Returns the pitch played on a string (from the lowest, starting at `0`) at a fret
+    /// (counted from the nut), or `None` if the string or fret does not exist
+    pub fn pitch_at(&self, string: usize, fret: u8) -> Option<u7> {
+        let open = self.tuning.strings.get(string)?;
+        if fret < self.capo || fret > self.frets {
+            return None;
+        }
+        let pitch = open.as_int() + fret;
+        (pitch <= 127).then(|| u7::new(pitch))
+    }
+
+    /// Returns the pitches played by a `ChordShape`, from the lowest string
+    /// (`None` for muted strings)
+    pub fn shape_pitches(&self, shape: &ChordShape) -> Vec<Option<u7>> {
+        shape
+            .frets
+            .iter()
+            .enumerate()
+            .map(|(s, f)| f.and_then(|f| self.pitch_at(s, f.max(self.capo))))
+            .collect()
+    }
+
+    /// Returns the playable shapes of a chord, the easiest first.
+    ///
+    /// A shape is playable if it fits in the hand span (open strings allowed), needs
+    /// at most four fingers (a barre on the lowest fret counts as one), only mutes
+    /// strings below the lowest played string, has the bass of the chord on its lowest
+    /// string, and contains every pitch class of the chord (the fifth can be omitted
+    /// for chords of four notes or more). Easier shapes are lower on the neck, use more
+    /// open strings, and play more strings.
+    ///
+    /// # Arguments
+    ///
+    /// * `harmony` - The chord to play: a `ChordSymbol`, a `&Chord`, a set of pitch
+    ///   classes, or a `Harmony`
+    pub fn chord_shapes<H: Into<Harmony>>(&self, harmony: H) -> Vec<ChordShape> {
+        let harmony = harmony.into();
+        let num_strings = self.tuning.strings.len();
+        let mut shapes: Vec<(i32, ChordShape)> = Vec::new();
+        for position in self.capo.max(1)..=self.frets.saturating_sub(HAND_SPAN) {
+            let options: Vec<Vec<Option<u8>>> = (0..num_strings)
+                .map(|s| {
+                    let mut opts = vec![None];
+                    let frets = std::iter::once(self.capo).chain(position..=position + HAND_SPAN);
+                    for fret in frets {
+                        let pitch = self.pitch_at(s, fret);
+                        if pitch.is_some_and(|p| harmony.classes().contains(&(p.as_int() % 12))) {
+                            opts.push(Some(fret));
+                        }
+                    }
+                    opts
+                })
+                .collect();
+            let mut current = Vec::with_capacity(num_strings);
+            self.collect_shapes(&harmony, &options, &mut current, &mut shapes);
+        }
+        shapes.sort_by_key(|(score, shape)| (*score, shape.frets.clone()));
+        shapes.dedup_by(|a, b| a.1 == b.1);
+        shapes.into_iter().map(|(_, shape)| shape).collect()
+    }
+
+    /// Adds the strumming of a `ChordShape` at the end of a `Phrase`.
+    /// Each string is a single-note `Chord` whose rhythm is the delay before the next
+    /// string, so that all the strings keep ringing until the end of the stroke.
+    ///
+    /// # Arguments
+    ///
+    /// * `phrase` - The `Phrase` to add the strums to
+    /// * `shape` - The shape of the chord
+    /// * `pattern` - The strokes and their rhythm values
+    /// * `dynamic` - The dynamic of the strums (muted strums are played softer)
+    ///
+    /// # Errors
+    ///
+    /// * `NoteError::InvalidRhythm` if a rhythm value of the pattern is invalid
+    pub fn strum(
+        &self,
+        phrase: &mut Phrase,
+        shape: &ChordShape,
+        pattern: &[(StrumStroke, f64)],
+        dynamic: u7,
+    ) -> Result<()> {
+        let pitches: Vec<u7> = self.shape_pitches(shape).into_iter().flatten().collect();
+        for (stroke, rhythm) in pattern {
+            if *rhythm < 0.000_001 {
+                return Err(NoteError::InvalidRhythm(*rhythm).into());
+            }
+            let mut strings = pitches.clone();
+            let (muted, dynamic) = match stroke {
+                StrumStroke::Rest => {
+                    phrase.add_rest(*rhythm);
+                    continue;
+                }
+                StrumStroke::Down => (false, dynamic),
+                StrumStroke::Up => {
+                    strings.reverse();
+                    (false, dynamic)
+                }
+                StrumStroke::MutedDown => (true, u7::new(dynamic.as_int() / 2)),
+                StrumStroke::MutedUp => {
+                    strings.reverse();
+                    (true, u7::new(dynamic.as_int() / 2))
+                }
+            };
+            if strings.is_empty() {
+                phrase.add_rest(*rhythm);
+                continue;
+            }
+            // the whole strum must fit in the stroke
+            let spread = self.strum_spread.min(rhythm / (strings.len() as f64 + 1.));
+            let mut elapsed = 0.;
+            for (i, pitch) in strings.iter().enumerate() {
+                let last = i == strings.len() - 1;
+                let step = if last { rhythm - elapsed } else { spread };
+                let length = if muted {
+                    MUTED_NOTE_LENGTH.min(rhythm - elapsed).max(step)
+                } else {
+                    rhythm - elapsed
+                };
+                let note = Note::new(*pitch, length, dynamic)?;
+                phrase.add_chord(Chord::new(step, vec![note])?);
+                elapsed += step;
+            }
+        }
+        Ok(())
+    }
+
+    /// Recursively builds the shapes from the lowest string with the fret options of
+    /// each string, and stores the valid ones with their difficulty score
+    fn collect_shapes(
+        &self,
+        harmony: &Harmony,
+        options: &[Vec<Option<u8>>],
+        current: &mut Vec<Option<u8>>,
+        shapes: &mut Vec<(i32, ChordShape)>,
+    ) {
+        let string = current.len();
+        if string == options.len() {
+            if let Some(score) = self.shape_score(harmony, current) {
+                shapes.push((score, ChordShape::new(current.clone())));
+            }
+            return;
+        }
+        let started = current.iter().any(Option::is_some);
+        for option in options[string].iter() {
+            // strings can only be muted below the lowest played string
+            if option.is_none() && started {
+                continue;
+            }
+            // the lowest played string plays the bass
+            if let (Some(fret), false, Some(bass)) = (option, started, harmony.bass()) {
+                let class = self.pitch_at(string, *fret).map(|p| p.as_int() % 12);
+                if class != Some(bass) {
+                    continue;
+                }
+            }
+            current.push(*option);
+            self.collect_shapes(harmony, options, current, shapes);
+            current.pop();
+        }
+    }
+
+    /// Returns the difficulty score of a shape (lower is easier),
+    /// or `None` if the shape is not valid
+    fn shape_score(&self, harmony: &Harmony, frets: &[Option<u8>]) -> Option<i32> {
+        let played: Vec<(usize, u8)> = frets
+            .iter()
+            .enumerate()
+            .filter_map(|(s, f)| f.map(|f| (s, f)))
+            .collect();
+        if played.len() < 3.min(frets.len()) {
+            return None;
+        }
+        let mut classes: Vec<u8> = played
+            .iter()
+            .filter_map(|(s, f)| self.pitch_at(*s, *f))
+            .map(|p| p.as_int() % 12)
+            .collect();
+        classes.sort_unstable();
+        classes.dedup();
+        let missing = harmony
+            .classes()
+            .iter()
+            .filter(|c| !classes.contains(c))
+            .count();
+        let fifth = harmony.classes().first().map(|r| (r + 7) % 12);
+        let fifth_missing = fifth.is_some_and(|f| !classes.contains(&f));
+        let allowed_missing = usize::from(harmony.classes().len() >= 4 && fifth_missing);
+        if missing > allowed_missing {
+            return None;
+        }
+
+        let fretted: Vec<u8> = played
+            .iter()
+            .map(|(_, f)| *f)
+            .filter(|f| *f > self.capo)
+            .collect();
+        let lowest = fretted.iter().min().copied().unwrap_or(self.capo);
+        let highest = fretted.iter().max().copied().unwrap_or(self.capo);
+        let barre = usize::from(fretted.iter().filter(|f| **f == lowest).count() > 1);
+        // one finger (or a barre) on the lowest fret, one finger per note above
+        let fingers =
+            fretted.iter().filter(|f| **f > lowest).count() + usize::from(!fretted.is_empty());
+        if fingers > MAX_FINGERS {
+            return None;
+        }
+        let open = played.len() - fretted.len();
+        let muted = frets.len() - played.len();
+        Some(
+            4 * (lowest - self.capo) as i32 + 2 * (highest - lowest) as i32 + 3 * muted as i32
+                - 2 * open as i32
+                + 3 * barre as i32,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ChordShape, Guitar, StrumStroke, Tuning};
+    use crate::composition::ChordSymbol;
+    use crate::*;
+
+    #[test]
+    fn chord_shapes() -> Result<()> {
+        let guitar = Guitar::new(Tuning::standard());
+        let c: ChordSymbol = "C".parse()?;
+        assert_eq!(
+            guitar.chord_shapes(c)[0],
+            ChordShape::new(vec![None, Some(3), Some(2), Some(0), Some(1), Some(0)])
+        );
+        let g: ChordSymbol = "G".parse()?;
+        assert_eq!(
+            guitar.chord_shapes(g)[0].frets(),
+            &[Some(3), Some(2), Some(0), Some(0), Some(0), Some(3)]
+        );
+
+        // with a capo on the 2nd fret, the C shape plays a D chord
+        let mut capo_guitar = Guitar::new(Tuning::standard());
+        capo_guitar.set_capo(2);
+        let d: ChordSymbol = "D".parse()?;
+        let d_shape = &capo_guitar.chord_shapes(d)[0];
+        assert_eq!(
+            d_shape.frets(),
+            &[None, Some(5), Some(4), Some(2), Some(3), Some(2)]
+        );
+
+        let mut phrase = Phrase::new();
+        capo_guitar.strum(
+            &mut phrase,
+            d_shape,
+            &[
+                (StrumStroke::Down, rhythm::CROTCHET),
+                (StrumStroke::Up, rhythm::CROTCHET),
+            ],
+            dynamic::MF,
+        )?;
+        assert_eq!(phrase.duration(), 2.);
+        assert_eq!(phrase.entries().len(), 10);
+        Ok(())
+    }
+}
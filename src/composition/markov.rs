@@ -0,0 +1,292 @@
+use std::collections::{BTreeMap, HashMap};
+use std::hash::Hash;
+
+use crate::composition::random::Rng;
+use crate::composition::{Scale, SnapDirection};
+use crate::errors::GenerationError;
+use crate::events::to_ticks;
+use crate::num::u7;
+use crate::{Note, Phrase, PhraseEntry, Result, TICKS_PER_BEAT};
+
+/// Markov chain of order N that remembers the transitions of all the orders from 0 to N,
+/// to back off to shorter contexts when a context was never seen
+#[derive(Debug, Clone, PartialEq)]
+struct Chain<T: Ord + Hash + Clone> {
+    order: usize,
+    /// Number of occurrences of each symbol after each context (of length 0 to `order`)
+    counts: HashMap<Vec<T>, BTreeMap<T, u32>>,
+    /// The first symbols of each training sequence
+    starts: Vec<Vec<T>>,
+}
+
+impl<T: Ord + Hash + Clone> Chain<T> {
+    fn new(order: usize) -> Self {
+        Self {
+            order,
+            counts: HashMap::new(),
+            starts: Vec::new(),
+        }
+    }
+
+    fn train(&mut self, sequence: &[T]) {
+        if sequence.is_empty() {
+            return;
+        }
+        self.starts
+            .push(sequence[..self.order.min(sequence.len())].to_vec());
+        for (i, symbol) in sequence.iter().enumerate() {
+            for length in 0..=self.order.min(i) {
+                let context = sequence[i - length..i].to_vec();
+                *self
+                    .counts
+                    .entry(context)
+                    .or_default()
+                    .entry(symbol.clone())
+                    .or_default() += 1;
+            }
+        }
+    }
+
+    fn is_trained(&self) -> bool {
+        !self.starts.is_empty()
+    }
+
+    /// Returns a random start of a training sequence
+    fn start(&self, rng: &mut Rng) -> Vec<T> {
+        self.starts[rng.below(self.starts.len())].clone()
+    }
+
+    /// Returns a random symbol following the end of `history` among the symbols accepted
+    /// by `accept`, using the longest known context that has accepted followers
+    fn next<F: Fn(&T) -> bool>(&self, history: &[T], rng: &mut Rng, accept: F) -> Option<T> {
+        for length in (0..=self.order.min(history.len())).rev() {
+            let context = &history[history.len() - length..];
+            let Some(followers) = self.counts.get(context) else {
+                continue;
+            };
+            let candidates: Vec<(&T, u32)> = followers
+                .iter()
+                .filter(|(symbol, _)| accept(symbol))
+                .map(|(symbol, count)| (symbol, *count))
+                .collect();
+            let total: u32 = candidates.iter().map(|c| c.1).sum();
+            if total == 0 {
+                continue;
+            }
+            let mut target = rng.below(total as usize) as u32;
+            for (symbol, count) in candidates {
+                if target < count {
+                    return Some(symbol.clone());
+                }
+                target -= count;
+            }
+        }
+        None
+    }
+}
+
+/// Generates melodies with Markov chains trained on existing phrases.
+///
+/// The pitches and the rhythm values are learnt by two independent chains, so the
+/// generated melodies can combine pitch sequences and rhythms of different training
+/// phrases. When the current context was never seen during training (or only leads to
+/// pitches rejected by the constraints), shorter contexts are used instead.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MarkovMelody {
+    /// The chain of the pitches (`None` for rests)
+    pitches: Chain<Option<u8>>,
+    /// The chain of the rhythm values in ticks
+    rhythms: Chain<u32>,
+    /// The scale the generated pitches must belong to
+    scale: Option<Scale>,
+    /// The lowest and highest generated pitches
+    range: (u7, u7),
+    /// The dynamic of the generated notes
+    dynamic: u7,
+}
+
+impl MarkovMelody {
+    /// Returns a new untrained generator with chains of order `order` for both the
+    /// pitches and the rhythm values
+    pub fn new(order: usize) -> Self {
+        Self::with_orders(order, order)
+    }
+
+    /// Returns a new untrained generator with different orders for the pitch chain and
+    /// for the rhythm chain
+    pub fn with_orders(pitch_order: usize, rhythm_order: usize) -> Self {
+        Self {
+            pitches: Chain::new(pitch_order),
+            rhythms: Chain::new(rhythm_order),
+            scale: None,
+            range: (u7::new(0), u7::new(127)),
+            dynamic: crate::dynamic::MF,
+        }
+    }
+
+    /// Learns the transitions of a `Phrase`. For `Chord`s, only the highest note is used.
+    pub fn train(&mut self, phrase: &Phrase) {
+        let mut pitches = Vec::with_capacity(phrase.entries().len());
+        let mut rhythms = Vec::with_capacity(phrase.entries().len());
+        for entry in phrase.entries() {
+            let pitch = match entry {
+                PhraseEntry::Note(n) => Some(n.pitch().as_int()),
+                PhraseEntry::Chord(c) => c.notes().iter().map(|n| n.pitch().as_int()).max(),
+                PhraseEntry::Rest(_) => None,
+            };
+            let ticks = to_ticks(entry.rhythm()) as u32;
+            if ticks == 0 {
+                continue;
+            }
+            pitches.push(pitch);
+            rhythms.push(ticks);
+        }
+        self.pitches.train(&pitches);
+        self.rhythms.train(&rhythms);
+    }
+
+    /// Sets the `Scale` the generated pitches must belong to (`None` for no constraint).
+    /// Pitches that do not fit are replaced by the closest pitch of the scale.
+    pub fn set_scale(&mut self, scale: Option<Scale>) {
+        self.scale = scale;
+    }
+
+    /// Sets the range of the generated pitches. Pitches outside of the range are moved
+    /// by octaves inside it.
+    pub fn set_range(&mut self, lowest: u7, highest: u7) {
+        self.range = (lowest.min(highest), lowest.max(highest));
+    }
+
+    /// Sets the dynamic of the generated notes
+    pub fn set_dynamic(&mut self, dynamic: u7) {
+        self.dynamic = dynamic;
+    }
+
+    /// Returns a new `Phrase` lasting `beats` beats. The same seed always generates the
+    /// same `Phrase`.
+    ///
+    /// # Errors
+    ///
+    /// * `GenerationError::NotTrained` if no phrase was used to train the generator
+    /// * Any error returned when creating the notes
+    pub fn generate(&self, beats: f64, seed: u64) -> Result<Phrase> {
+        if !self.pitches.is_trained() {
+            return Err(GenerationError::NotTrained.into());
+        }
+        let mut rng = Rng::new(seed);
+        // the melody begins like one of the training phrases
+        let pitch_start = self.pitches.start(&mut rng);
+        let rhythm_start = self.rhythms.start(&mut rng);
+        let mut pitch_history: Vec<Option<u8>> = Vec::new();
+        let mut rhythm_history: Vec<u32> = Vec::new();
+        let mut phrase = Phrase::new();
+        let mut elapsed = 0.;
+        while beats - elapsed >= 0.000_001 {
+            let pitch = match pitch_start.get(pitch_history.len()) {
+                Some(pitch) => *pitch,
+                None => self
+                    .pitches
+                    .next(&pitch_history, &mut rng, |p| self.accepts(*p))
+                    .or_else(|| self.pitches.next(&pitch_history, &mut rng, |_| true))
+                    .unwrap_or(None),
+            }
+            .map(|p| self.constrain(p));
+            let ticks = match rhythm_start.get(rhythm_history.len()) {
+                Some(ticks) => *ticks,
+                None => self
+                    .rhythms
+                    .next(&rhythm_history, &mut rng, |_| true)
+                    .unwrap_or(TICKS_PER_BEAT as u32),
+            };
+            let rhythm = (ticks as f64 / TICKS_PER_BEAT as f64).min(beats - elapsed);
+            match pitch {
+                Some(p) => phrase.add_note(Note::new(u7::new(p), rhythm, self.dynamic)?),
+                None => phrase.add_rest(rhythm),
+            }
+            elapsed += rhythm;
+            pitch_history.push(pitch);
+            rhythm_history.push(ticks);
+        }
+        Ok(phrase)
+    }
+
+    /// Returns true if a pitch (or rest) satisfies the scale and range constraints
+    fn accepts(&self, pitch: Option<u8>) -> bool {
+        let Some(pitch) = pitch else {
+            return true;
+        };
+        (self.range.0.as_int()..=self.range.1.as_int()).contains(&pitch)
+            && self.scale.iter().all(|s| s.contains(u7::new(pitch)))
+    }
+
+    /// Returns the pitch moved by octaves inside the range, then snapped to the scale
+    fn constrain(&self, pitch: u8) -> u8 {
+        let (lowest, highest) = (self.range.0.as_int(), self.range.1.as_int());
+        let mut pitch = pitch;
+        while pitch < lowest && pitch + 12 <= highest {
+            pitch += 12;
+        }
+        while pitch > highest && pitch >= lowest + 12 {
+            pitch -= 12;
+        }
+        let pitch = pitch.clamp(lowest, highest);
+        let Some(scale) = self.scale else {
+            return pitch;
+        };
+        [
+            SnapDirection::NearestUp,
+            SnapDirection::Down,
+            SnapDirection::Up,
+        ]
+        .into_iter()
+        .filter_map(|d| scale.snap(u7::new(pitch), d))
+        .map(u7::as_int)
+        .find(|p| (lowest..=highest).contains(p))
+        .unwrap_or(pitch)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MarkovMelody;
+    use crate::composition::{Scale, ScaleMode};
+    use crate::errors::GenerationError;
+    use crate::num::u7;
+    use crate::*;
+
+    #[test]
+    fn markov_melody() -> Result<()> {
+        let mut generator = MarkovMelody::new(2);
+        assert!(matches!(
+            generator.generate(4., 0),
+            Err(Error::Generation(GenerationError::NotTrained))
+        ));
+
+        let mut phrase = Phrase::new();
+        for (pitch, rhythm) in [
+            (60, 1.),
+            (62, 0.5),
+            (64, 0.5),
+            (65, 1.),
+            (67, 1.),
+            (66, 0.5),
+        ] {
+            phrase.add_note(Note::new(u7::new(pitch), rhythm, dynamic::MF)?);
+        }
+        phrase.add_rest(0.5);
+        generator.train(&phrase);
+        generator.set_scale(Some(Scale::new(u7::new(60), ScaleMode::Ionian)));
+        generator.set_range(u7::new(60), u7::new(67));
+
+        let melody = generator.generate(16., 42)?;
+        assert_eq!(melody, generator.generate(16., 42)?);
+        assert!((melody.duration() - 16.).abs() < 1e-9);
+        for entry in melody.entries() {
+            if let PhraseEntry::Note(n) = entry {
+                assert!((60..=67).contains(&n.pitch().as_int()));
+                assert_ne!(n.pitch().as_int(), 66);
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,223 @@
+use crate::composition::Scale;
+use crate::errors::{GenerationError, ScaleError};
+use crate::num::u7;
+use crate::{Note, Phrase, Result};
+
+/// Describes a melody written as degrees of a `Scale` instead of pitches.
+///
+/// Degrees start at `1` for the tonic of the reference octave. Degrees above the number of
+/// degrees of the `Scale` continue on the next octaves (`8` is the tonic one octave higher
+/// in a heptatonic scale) and negative degrees count down from the tonic (`-1` is the
+/// degree just below the tonic, e.g. the leading tone in major, and `-2` the one below
+/// it). This way, the melody crosses octaves without having to specify them.
+///
+/// The melody can be transposed diatonically, which allows to write sequences of a motif
+/// by restating it from other degrees.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DegreeMelody {
+    /// The `Scale` the degrees refer to
+    scale: Scale,
+    /// The octave of the tonic of degree `1` (same convention as `compute_pitch`)
+    octave: u8,
+    /// The entries of the melody: a number of scale steps above the tonic
+    /// of the reference octave (or `None` for a rest) and a rhythm value
+    entries: Vec<(Option<i32>, f64)>,
+}
+
+impl DegreeMelody {
+    /// Returns a new empty `DegreeMelody`
+    ///
+    /// # Arguments
+    ///
+    /// * `scale` - The `Scale` the degrees refer to
+    /// * `octave` - The octave of the tonic of degree `1` (same convention as `compute_pitch`)
+    pub fn new(scale: Scale, octave: u8) -> Self {
+        Self {
+            scale,
+            octave,
+            entries: Vec::new(),
+        }
+    }
+
+    /// Returns a new `DegreeMelody` with the given degrees
+    ///
+    /// # Arguments
+    ///
+    /// * `scale` - The `Scale` the degrees refer to
+    /// * `octave` - The octave of the tonic of degree `1` (same convention as `compute_pitch`)
+    /// * `degrees` - The degrees of the notes (see `DegreeMelody` for the convention)
+    /// * `rhythms` - The rhythm values of the notes. If there are fewer rhythm values than
+    ///   degrees, they are repeated cyclically (so a single value applies to every note)
+    ///
+    /// # Errors
+    ///
+    /// * `ScaleError::InvalidDegree` if a degree is `0`
+    /// * `GenerationError::EmptyRhythms` if `rhythms` is empty
+    pub fn from_degrees(
+        scale: Scale,
+        octave: u8,
+        degrees: &[i32],
+        rhythms: &[f64],
+    ) -> Result<Self> {
+        if rhythms.is_empty() {
+            return Err(GenerationError::EmptyRhythms.into());
+        }
+        let mut melody = Self::new(scale, octave);
+        for (degree, rhythm) in degrees.iter().zip(rhythms.iter().cycle()) {
+            melody.add_degree(*degree, *rhythm)?;
+        }
+        Ok(melody)
+    }
+
+    /// Adds a note at the given degree to the melody
+    ///
+    /// # Errors
+    ///
+    /// * `ScaleError::InvalidDegree` if `degree` is `0`
+    pub fn add_degree(&mut self, degree: i32, rhythm: f64) -> Result<()> {
+        let step = self.degree_to_step(degree)?;
+        self.entries.push((Some(step), rhythm));
+        Ok(())
+    }
+
+    /// Adds a rest to the melody
+    pub fn add_rest(&mut self, rhythm: f64) {
+        self.entries.push((None, rhythm));
+    }
+
+    /// Appends the entries of another melody at the end of this one.
+    /// The degrees of `other` are interpreted in this melody's `Scale` and octave.
+    pub fn append(&mut self, other: &DegreeMelody) {
+        self.entries.extend_from_slice(&other.entries);
+    }
+
+    /// Returns a copy of the melody moved by `steps` degrees of the `Scale`
+    /// (diatonic transposition). Negative values transpose down.
+    pub fn transposed(&self, steps: i32) -> Self {
+        Self {
+            entries: self
+                .entries
+                .iter()
+                .map(|(step, rhythm)| (step.map(|s| s + steps), *rhythm))
+                .collect(),
+            ..self.clone()
+        }
+    }
+
+    /// Returns a copy of the melody diatonically transposed so that its first note is on
+    /// `degree`. A melody that only contains rests is returned unchanged.
+    ///
+    /// # Errors
+    ///
+    /// * `ScaleError::InvalidDegree` if `degree` is `0`
+    pub fn starting_on(&self, degree: i32) -> Result<Self> {
+        let target = self.degree_to_step(degree)?;
+        Ok(match self.entries.iter().find_map(|(step, _)| *step) {
+            Some(first) => self.transposed(target - first),
+            None => self.clone(),
+        })
+    }
+
+    /// Returns a melody made of the restatement of this melody starting on each of
+    /// the given degrees, one after the other
+    ///
+    /// # Errors
+    ///
+    /// * `ScaleError::InvalidDegree` if one of the degrees is `0`
+    pub fn sequence(&self, start_degrees: &[i32]) -> Result<Self> {
+        let mut sequence = Self::new(self.scale, self.octave);
+        for degree in start_degrees {
+            sequence.append(&self.starting_on(*degree)?);
+        }
+        Ok(sequence)
+    }
+
+    /// Returns the pitches of the melody (`None` for rests)
+    ///
+    /// # Errors
+    ///
+    /// * `NoteError::InvalidPitch` if a degree is outside of the MIDI range
+    pub fn pitches(&self) -> Result<Vec<Option<u7>>> {
+        let octave_steps = self.octave as i32 * self.scale.num_degrees() as i32;
+        self.entries
+            .iter()
+            .map(|(step, _)| {
+                step.map(|s| self.scale.step_pitch(s + octave_steps))
+                    .transpose()
+            })
+            .collect()
+    }
+
+    /// Returns a `Phrase` that plays the melody
+    ///
+    /// # Arguments
+    ///
+    /// * `dynamic` - The dynamic of every note of the `Phrase`
+    ///
+    /// # Errors
+    ///
+    /// * `NoteError::InvalidPitch` if a degree is outside of the MIDI range
+    /// * `NoteError::InvalidRhythm` if a rhythm value is invalid
+    pub fn to_phrase(&self, dynamic: u7) -> Result<Phrase> {
+        let mut phrase = Phrase::new();
+        for (pitch, (_, rhythm)) in self.pitches()?.into_iter().zip(&self.entries) {
+            match pitch {
+                Some(p) => phrase.add_note(Note::new(p, *rhythm, dynamic)?),
+                None => phrase.add_rest(*rhythm),
+            }
+        }
+        Ok(phrase)
+    }
+
+    /// Returns the `Scale` the degrees of the melody refer to
+    pub fn scale(&self) -> Scale {
+        self.scale
+    }
+
+    /// Converts a degree to a number of steps above the tonic of the reference octave
+    fn degree_to_step(&self, degree: i32) -> Result<i32> {
+        match degree {
+            0 => Err(ScaleError::InvalidDegree(0).into()),
+            d if d > 0 => Ok(d - 1),
+            d => Ok(d),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DegreeMelody;
+    use crate::composition::{Scale, ScaleMode};
+    use crate::errors::GenerationError;
+    use crate::num::u7;
+    use crate::*;
+
+    #[test]
+    fn degree_melody() -> Result<()> {
+        let c_major = Scale::new(u7::new(60), ScaleMode::MAJOR);
+        let motif = DegreeMelody::from_degrees(
+            c_major,
+            5,
+            &[1, 3, 5, 4, 3, -2],
+            &[rhythm::QUAVER, rhythm::CROTCHET],
+        )?;
+        let pitches = |m: &DegreeMelody| -> Result<Vec<u8>> {
+            Ok(m.pitches()?.into_iter().flatten().map(u7::as_int).collect())
+        };
+        assert_eq!(pitches(&motif)?, vec![60, 64, 67, 65, 64, 57]);
+
+        let phrase = motif.to_phrase(dynamic::MF)?;
+        assert_eq!(phrase.duration(), 4.5);
+
+        let sequence = motif.sequence(&[1, 6])?;
+        assert_eq!(
+            pitches(&sequence)?,
+            vec![60, 64, 67, 65, 64, 57, 69, 72, 76, 74, 72, 65]
+        );
+        assert!(matches!(
+            DegreeMelody::from_degrees(c_major, 5, &[1], &[]),
+            Err(Error::Generation(GenerationError::EmptyRhythms))
+        ));
+        Ok(())
+    }
+}
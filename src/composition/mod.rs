@@ -1,3 +1,39 @@
+mod arpeggiator;
+mod bassline;
+mod canon;
+mod chorale;
+mod chord_symbol;
+mod constraints;
+mod counterpoint;
+mod evolution;
+mod grammar;
+mod groove;
+mod guitar;
+mod markov;
+mod melody;
+mod random;
+mod rhythm_pattern;
 mod scale;
+mod sequencer;
+mod tablature;
+mod timeline;
+mod voice_leading;
 
+pub use arpeggiator::*;
+pub use bassline::*;
+pub use canon::*;
+pub use chorale::*;
+pub use chord_symbol::*;
+pub use constraints::*;
+pub use counterpoint::*;
+pub use evolution::*;
+pub use grammar::*;
+pub use groove::*;
+pub use guitar::*;
+pub use markov::*;
+pub use melody::*;
+pub use rhythm_pattern::*;
 pub use scale::*;
+pub use sequencer::*;
+pub use tablature::*;
+pub use voice_leading::*;
@@ -0,0 +1,361 @@
+use crate::composition::timeline::phrase_from_timed_notes;
+use crate::errors::NoteError;
+use crate::num::u7;
+use crate::{Note, Phrase, Result};
+
+/// Returns the Euclidean rhythm E(`hits`, `steps`): `hits` onsets spread as evenly as
+/// possible over `steps` steps (`true` for an onset), starting with an onset.
+/// The pattern is then rotated to the left by `rotation` steps (to the right if negative).
+/// `hits` is capped to `steps`.
+///
+/// For example, E(3, 8) is the tresillo `x..x..x.`.
+pub fn euclidean(hits: usize, steps: usize, rotation: isize) -> Vec<bool> {
+    if steps == 0 {
+        return Vec::new();
+    }
+    let hits = hits.min(steps);
+    let shift = rotation.rem_euclid(steps as isize) as usize;
+    (0..steps)
+        .map(|i| ((i + shift) % steps * hits) % steps < hits)
+        .collect()
+}
+
+/// Describes the dynamic of each step of a rhythm, repeated cyclically
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct AccentPattern {
+    dynamics: Vec<u7>,
+}
+
+impl AccentPattern {
+    /// Returns a new `AccentPattern` with the dynamic of each step. An empty pattern is
+    /// treated as `dynamic::MF` on every step.
+    pub fn new(dynamics: Vec<u7>) -> Self {
+        Self { dynamics }
+    }
+
+    /// Returns an `AccentPattern` with the same dynamic on every step
+    pub fn uniform(dynamic: u7) -> Self {
+        Self::new(vec![dynamic])
+    }
+
+    /// Returns an `AccentPattern` that accents one step out of `period`
+    ///
+    /// # Arguments
+    ///
+    /// * `period` - The number of steps between two accents (`0` is treated as `1`)
+    /// * `offset` - The index of the first accented step
+    /// * `accent` - The dynamic of the accented steps
+    /// * `normal` - The dynamic of the other steps
+    pub fn every(period: usize, offset: usize, accent: u7, normal: u7) -> Self {
+        let period = period.max(1);
+        Self::new(
+            (0..period)
+                .map(|i| if i == offset % period { accent } else { normal })
+                .collect(),
+        )
+    }
+
+    /// Returns an `AccentPattern` that accents the onsets of a pattern (for example an
+    /// Euclidean rhythm) and plays the other steps with the `normal` dynamic
+    pub fn from_onsets(onsets: &[bool], accent: u7, normal: u7) -> Self {
+        Self::new(
+            onsets
+                .iter()
+                .map(|o| if *o { accent } else { normal })
+                .collect(),
+        )
+    }
+
+    /// Returns the dynamic of the step at index `step`
+    pub fn dynamic_at(&self, step: usize) -> u7 {
+        if self.dynamics.is_empty() {
+            return crate::dynamic::MF;
+        }
+        self.dynamics[step % self.dynamics.len()]
+    }
+
+    /// Returns the dynamics of the pattern
+    pub fn dynamics(&self) -> &[u7] {
+        &self.dynamics
+    }
+}
+
+/// A cyclic pattern of onsets on a grid of equal steps, that can be rendered as a
+/// `Phrase` on a pitch or a `DrumSound`
+#[derive(Debug, Clone, PartialEq)]
+pub struct HitPattern {
+    /// The steps of the pattern (`true` for an onset)
+    onsets: Vec<bool>,
+    /// The rhythm value of each step
+    step: f64,
+}
+
+impl HitPattern {
+    /// Returns a new `HitPattern`
+    ///
+    /// # Arguments
+    ///
+    /// * `onsets` - The steps of the pattern (`true` for an onset)
+    /// * `step` - The rhythm value of each step
+    ///
+    /// # Errors
+    ///
+    /// * `NoteError::InvalidRhythm` if `step` is below `0.000_001`
+    pub fn new(onsets: Vec<bool>, step: f64) -> Result<Self> {
+        if step < 0.000_001 {
+            return Err(NoteError::InvalidRhythm(step).into());
+        }
+        Ok(Self { onsets, step })
+    }
+
+    /// Returns the `HitPattern` of the Euclidean rhythm E(`hits`, `steps`) rotated by
+    /// `rotation` steps (see `euclidean`)
+    ///
+    /// # Errors
+    ///
+    /// * `NoteError::InvalidRhythm` if `step` is below `0.000_001`
+    pub fn euclidean(hits: usize, steps: usize, rotation: isize, step: f64) -> Result<Self> {
+        Self::new(euclidean(hits, steps, rotation), step)
+    }
+
+    /// Returns the steps of the pattern (`true` for an onset)
+    pub fn onsets(&self) -> &[bool] {
+        &self.onsets
+    }
+
+    /// Returns the rhythm value of each step
+    pub fn step(&self) -> f64 {
+        self.step
+    }
+
+    /// Returns the duration of one cycle of the pattern in beats
+    pub fn duration(&self) -> f64 {
+        self.step * self.onsets.len() as f64
+    }
+
+    /// Returns the pattern rotated to the left by `rotation` steps (to the right if
+    /// negative)
+    pub fn rotated(&self, rotation: isize) -> Self {
+        let mut onsets = self.onsets.clone();
+        if !onsets.is_empty() {
+            let shift = rotation.rem_euclid(onsets.len() as isize) as usize;
+            onsets.rotate_left(shift);
+        }
+        Self {
+            onsets,
+            step: self.step,
+        }
+    }
+
+    /// Returns a `Phrase` that plays `repeats` cycles of the pattern. Each onset is a note
+    /// lasting one step, and the steps between onsets are rests.
+    ///
+    /// # Arguments
+    ///
+    /// * `pitch` - The pitch of the notes, or a `DrumSound`
+    /// * `accents` - The dynamic of each step (counted from the start of the `Phrase`)
+    /// * `repeats` - The number of cycles
+    ///
+    /// # Errors
+    ///
+    /// * Any error returned when creating the notes
+    pub fn to_phrase<P: Into<u7>>(
+        &self,
+        pitch: P,
+        accents: &AccentPattern,
+        repeats: usize,
+    ) -> Result<Phrase> {
+        let pitch = pitch.into();
+        let mut phrase = Phrase::new();
+        let mut rest_steps = 0;
+        let steps = self.onsets.iter().cycle().take(self.onsets.len() * repeats);
+        for (i, onset) in steps.enumerate() {
+            if !onset {
+                rest_steps += 1;
+                continue;
+            }
+            if rest_steps > 0 {
+                phrase.add_rest(self.step * rest_steps as f64);
+                rest_steps = 0;
+            }
+            phrase.add_note(Note::new(pitch, self.step, accents.dynamic_at(i))?);
+        }
+        if rest_steps > 0 {
+            phrase.add_rest(self.step * rest_steps as f64);
+        }
+        Ok(phrase)
+    }
+}
+
+/// Several pulses with different numbers of evenly spaced onsets played over the same
+/// span (for example 3 against 2)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Polyrhythm {
+    /// The number of onsets of each layer during the span
+    divisions: Vec<usize>,
+    /// The duration of the span in beats
+    span: f64,
+}
+
+impl Polyrhythm {
+    /// Returns a new `Polyrhythm`
+    ///
+    /// # Arguments
+    ///
+    /// * `divisions` - The number of onsets of each layer during the span (e.g. `[3, 2]`)
+    /// * `span` - The duration in beats over which the onsets of each layer are spread
+    ///
+    /// # Errors
+    ///
+    /// * `NoteError::InvalidRhythm` if the onsets of a layer are closer than `0.000_001`
+    pub fn new(divisions: &[usize], span: f64) -> Result<Self> {
+        let max_division = divisions.iter().max().copied().unwrap_or(1).max(1);
+        if span / (max_division as f64) < 0.000_001 {
+            return Err(NoteError::InvalidRhythm(span / max_division as f64).into());
+        }
+        Ok(Self {
+            divisions: divisions.to_vec(),
+            span,
+        })
+    }
+
+    /// Returns the number of onsets of each layer
+    pub fn divisions(&self) -> &[usize] {
+        &self.divisions
+    }
+
+    /// Returns the duration of the span in beats
+    pub fn span(&self) -> f64 {
+        self.span
+    }
+
+    /// Returns the beats of the onsets of each layer, relative to the start of the span.
+    /// Each onset is computed from the start of the span so that rounding errors do not
+    /// accumulate.
+    pub fn onsets(&self) -> Vec<Vec<f64>> {
+        self.divisions
+            .iter()
+            .map(|n| (0..*n).map(|i| self.span * i as f64 / *n as f64).collect())
+            .collect()
+    }
+
+    /// Returns one `Phrase` per layer, playing `repeats` spans. The notes last until the
+    /// next onset of their layer.
+    ///
+    /// # Arguments
+    ///
+    /// * `pitches` - The pitch (or `DrumSound`) of each layer. Missing pitches repeat
+    ///   the last one.
+    /// * `accents` - The dynamic of each onset, counted from the start of each layer
+    /// * `repeats` - The number of spans
+    ///
+    /// # Errors
+    ///
+    /// * Any error returned when creating the notes
+    pub fn to_phrases<P: Into<u7> + Copy>(
+        &self,
+        pitches: &[P],
+        accents: &AccentPattern,
+        repeats: usize,
+    ) -> Result<Vec<Phrase>> {
+        let mut phrases = Vec::with_capacity(self.divisions.len());
+        for (layer, onsets) in self.onsets().iter().enumerate() {
+            let pitch = self.layer_pitch(pitches, layer);
+            let mut phrase = Phrase::new();
+            for (i, (start, end)) in self.layer_notes(onsets, repeats).into_iter().enumerate() {
+                let note = Note::new(pitch, end - start, accents.dynamic_at(i))?;
+                phrase.add_note(note);
+            }
+            if onsets.is_empty() && repeats > 0 {
+                phrase.add_rest(self.span * repeats as f64);
+            }
+            phrases.push(phrase);
+        }
+        Ok(phrases)
+    }
+
+    /// Returns a single `Phrase` playing all the layers during `repeats` spans.
+    /// Onsets of different layers at the same time are played as a `Chord`.
+    ///
+    /// # Errors
+    ///
+    /// * Same as `to_phrases`
+    pub fn to_phrase<P: Into<u7> + Copy>(
+        &self,
+        pitches: &[P],
+        accents: &AccentPattern,
+        repeats: usize,
+    ) -> Result<Phrase> {
+        let mut notes = Vec::new();
+        for (layer, onsets) in self.onsets().iter().enumerate() {
+            let pitch = self.layer_pitch(pitches, layer);
+            for (i, (start, end)) in self.layer_notes(onsets, repeats).into_iter().enumerate() {
+                notes.push((start, Note::new(pitch, end - start, accents.dynamic_at(i))?));
+            }
+        }
+        phrase_from_timed_notes(notes, self.span * repeats as f64)
+    }
+
+    /// Returns the pitch of a layer, or the last pitch if there are not enough pitches
+    fn layer_pitch<P: Into<u7> + Copy>(&self, pitches: &[P], layer: usize) -> u7 {
+        pitches
+            .get(layer)
+            .or(pitches.last())
+            .map_or(u7::new(60), |p| (*p).into())
+    }
+
+    /// Returns the `(start, end)` beats of the notes of a layer over `repeats` spans
+    fn layer_notes(&self, onsets: &[f64], repeats: usize) -> Vec<(f64, f64)> {
+        let mut notes = Vec::with_capacity(onsets.len() * repeats);
+        for r in 0..repeats {
+            let offset = self.span * r as f64;
+            for (i, onset) in onsets.iter().enumerate() {
+                let end = onsets.get(i + 1).copied().unwrap_or(self.span);
+                notes.push((offset + onset, offset + end));
+            }
+        }
+        notes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{euclidean, AccentPattern, HitPattern, Polyrhythm};
+    use crate::num::u7;
+    use crate::*;
+
+    #[test]
+    fn rhythm_patterns() -> Result<()> {
+        let pattern = |p: &[bool]| {
+            p.iter()
+                .map(|o| if *o { 'x' } else { '.' })
+                .collect::<String>()
+        };
+        assert_eq!(pattern(&euclidean(3, 8, 0)), "x..x..x.");
+        assert_eq!(pattern(&euclidean(3, 8, 1)), "..x..x.x");
+        assert_eq!(pattern(&euclidean(4, 16, 0)), "x...x...x...x...");
+
+        let tresillo = HitPattern::euclidean(3, 8, 0, rhythm::SEMIQUAVER)?;
+        let accents = AccentPattern::every(8, 0, dynamic::FF, dynamic::MP);
+        let phrase = tresillo.to_phrase(DrumSound::BassDrum, &accents, 2)?;
+        assert_eq!(phrase.duration(), 4.);
+        assert_eq!(phrase.entries().len(), 12);
+        assert_eq!(
+            phrase.entries()[0],
+            PhraseEntry::Note(Note::new(u7::new(36), rhythm::SEMIQUAVER, dynamic::FF)?)
+        );
+
+        let three_two = Polyrhythm::new(&[3, 2], 2.)?;
+        let merged = three_two.to_phrase(
+            &[60, 67].map(u7::new),
+            &AccentPattern::uniform(dynamic::MF),
+            1,
+        )?;
+        let rhythms: Vec<f64> = merged.entries().iter().map(PhraseEntry::rhythm).collect();
+        assert_eq!(rhythms.len(), 4);
+        assert!((rhythms[0] - 2. / 3.).abs() < 1e-9);
+        assert!((rhythms[1] - 1. / 3.).abs() < 1e-9);
+        assert!((merged.duration() - 2.).abs() < 1e-9);
+        Ok(())
+    }
+}
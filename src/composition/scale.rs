@@ -1,4 +1,6 @@
+use crate::errors::{NoteError, ScaleError};
 use crate::num::u7;
+use crate::{Chord, Phrase, PhraseEntry, Result};
 
 mod intervals {
     pub static IONIAN: [u8; 6] = [2, 4, 5, 7, 9, 11];
@@ -12,6 +14,15 @@ mod intervals {
     pub static MELODIC_MINOR: [u8; 6] = [2, 3, 5, 7, 9, 11];
 }
 
+/// Returns the offset in semitones from the tonic of the degree at `index`
+/// (`0` being the tonic)
+fn degree_offset(intervals: &[u8], index: i32) -> i32 {
+    match index {
+        0 => 0,
+        i => intervals[i as usize - 1] as i32,
+    }
+}
+
 // The mode/type of a Scale
 #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum ScaleMode {
@@ -45,6 +56,51 @@ impl ScaleMode {
             Self::MelodicMinor => &intervals::MELODIC_MINOR,
         }
     }
+
+    // Returns the list of intervals used when the mode is played descending.
+    // This only differs from `intervals` for the melodic minor, which descends
+    // as a natural minor.
+    pub fn descending_intervals(&self) -> &'static [u8] {
+        match *self {
+            Self::MelodicMinor => &intervals::AEOLIAN,
+            _ => self.intervals(),
+        }
+    }
+}
+
+/// Describes the direction in which a `ScaleRun` goes through the pitches of a `Scale`
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RunDirection {
+    /// Goes up until the bound
+    #[default]
+    Ascending,
+    /// Goes down until the bound
+    Descending,
+    /// Goes up until the bound, then back down to the starting degree. The descending
+    /// half follows `ScaleMode::descending_intervals`, so it can return to a different
+    /// pitch than the first one (e.g. in melodic minor).
+    AscendingDescending,
+    /// Goes down until the bound, then back up to the starting degree. The ascending
+    /// half follows `ScaleMode::intervals`, not the descending spelling, so it can use
+    /// other pitches than the descent and end on a different pitch than the first one
+    /// (e.g. the raised sixth and seventh degrees in melodic minor).
+    DescendingAscending,
+}
+
+/// Describes how a pitch that does not belong to a `Scale` is moved onto it
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SnapDirection {
+    /// Always moves to the next pitch of the scale above
+    Up,
+    /// Always moves to the next pitch of the scale below
+    Down,
+    /// Moves to the closest pitch of the scale, going up if both
+    /// neighbours are equally distant
+    #[default]
+    NearestUp,
+    /// Moves to the closest pitch of the scale, going down if both
+    /// neighbours are equally distant
+    NearestDown,
 }
 
 // A Scale defined by a starting pitch and a mode
@@ -75,6 +131,263 @@ impl Scale {
     pub fn n_pitches(&self, num_pitches: usize) -> ScalePitchesIterator<'static> {
         ScalePitchesIterator::new(self.tonic_pitch, self.scale_mode.intervals(), num_pitches)
     }
+
+    /// Returns an iterator that goes through the pitches of the `Scale` from any degree,
+    /// up and/or down, until a pitch bound is reached.
+    /// Descending passages use the descending form of the mode (see
+    /// `ScaleMode::descending_intervals`).
+    ///
+    /// # Arguments
+    ///
+    /// * `degree` - The degree of the first pitch, starting at `1` for the tonic
+    /// * `octave` - The octave of the tonic of the first pitch (same convention as
+    ///   `compute_pitch`)
+    /// * `direction` - The direction of the run
+    /// * `bound` - The highest pitch for `Ascending` and `AscendingDescending` runs,
+    ///   or the lowest pitch for `Descending` and `DescendingAscending` runs. The bound
+    ///   is included if it belongs to the `Scale`.
+    ///
+    /// # Errors
+    ///
+    /// * `ScaleError::InvalidDegree` if `degree` is `0`
+    /// * `ScaleError::InvalidRunBound` if `bound` is below the first pitch of an
+    ///   ascending run, or above the first pitch of a descending run
+    pub fn run(
+        &self,
+        degree: usize,
+        octave: u8,
+        direction: RunDirection,
+        bound: u7,
+    ) -> Result<ScaleRun> {
+        if degree == 0 {
+            return Err(ScaleError::InvalidDegree(degree).into());
+        }
+        let step = (degree - 1) as i32 + octave as i32 * self.num_degrees() as i32;
+        let descending = matches!(
+            direction,
+            RunDirection::Descending | RunDirection::DescendingAscending
+        );
+        let ascending_start = self.step_to_pitch(step);
+        let descending_start =
+            self.step_to_pitch_with(self.scale_mode.descending_intervals(), step);
+        let start = if descending {
+            descending_start
+        } else {
+            ascending_start
+        };
+        let bound = bound.as_int() as i32;
+        if (descending && bound > start) || (!descending && bound < start) {
+            return Err(ScaleError::InvalidRunBound(bound as u8, start).into());
+        }
+        // the return of a run stops on the start degree, in the form of the return direction
+        let (lowest, highest) = match direction {
+            RunDirection::Ascending => (0, bound),
+            RunDirection::Descending => (bound, 127),
+            RunDirection::AscendingDescending => (descending_start, bound),
+            RunDirection::DescendingAscending => (bound, ascending_start),
+        };
+        Ok(ScaleRun {
+            scale: *self,
+            step,
+            lowest,
+            highest,
+            descending,
+            can_turn: matches!(
+                direction,
+                RunDirection::AscendingDescending | RunDirection::DescendingAscending
+            ),
+            started: false,
+            done: false,
+        })
+    }
+
+    /// Returns the pitch of the tonic the `Scale` was created with
+    pub fn tonic_pitch(&self) -> u7 {
+        self.tonic_pitch
+    }
+
+    /// Returns the mode of the `Scale`
+    pub fn mode(&self) -> ScaleMode {
+        self.scale_mode
+    }
+
+    /// Returns the number of degrees in one octave of the `Scale` (`7` for all
+    /// the modes currently available)
+    pub fn num_degrees(&self) -> usize {
+        self.scale_mode.intervals().len() + 1
+    }
+
+    /// Returns `true` if the pitch class of `pitch` belongs to the `Scale`,
+    /// regardless of its octave
+    pub fn contains(&self, pitch: u7) -> bool {
+        self.step_of(pitch.as_int() as i32).is_some()
+    }
+
+    /// Returns the degree of `pitch` in the `Scale`, starting at `1` for the tonic,
+    /// or `None` if the pitch does not belong to the `Scale`
+    pub fn degree_of(&self, pitch: u7) -> Option<usize> {
+        self.step_of(pitch.as_int() as i32)
+            .map(|step| step.rem_euclid(self.num_degrees() as i32) as usize + 1)
+    }
+
+    /// Returns the pitch of a degree of the `Scale` on a given octave
+    ///
+    /// # Arguments
+    ///
+    /// * `degree` - The degree of the pitch, starting at `1` for the tonic. Degrees higher
+    ///   than the number of degrees of the `Scale` continue on the next octaves (e.g. degree
+    ///   `8` of a major scale is the tonic one octave higher, `9` is the ninth).
+    /// * `octave` - The octave of the tonic (same convention as `compute_pitch`)
+    ///
+    /// # Errors
+    ///
+    /// * `ScaleError::InvalidDegree` if `degree` is `0`
+    /// * `NoteError::InvalidPitch` if the resulting pitch is above `127`
+    pub fn pitch_at_degree(&self, degree: usize, octave: u8) -> Result<u7> {
+        if degree == 0 {
+            return Err(ScaleError::InvalidDegree(degree).into());
+        }
+        let step = (degree - 1) as i32 + octave as i32 * self.num_degrees() as i32;
+        self.step_pitch(step)
+    }
+
+    /// Returns the pitch of the `Scale` closest to `pitch`, or `pitch` itself if it
+    /// already belongs to the `Scale`.
+    /// Returns `None` if no pitch of the `Scale` can be found in the MIDI range
+    /// in the requested direction.
+    pub fn snap(&self, pitch: u7, direction: SnapDirection) -> Option<u7> {
+        let pitch = pitch.as_int() as i32;
+        if self.step_of(pitch).is_some() {
+            return Some(u7::new(pitch as u8));
+        }
+        let in_scale = |p: &i32| self.step_of(*p).is_some();
+        let above = (pitch + 1..=127).find(in_scale);
+        let below = (0..pitch).rev().find(in_scale);
+        let snapped = match (direction, below, above) {
+            (SnapDirection::Up, _, a) => a,
+            (SnapDirection::Down, b, _) => b,
+            (_, Some(b), Some(a)) => match (pitch - b).cmp(&(a - pitch)) {
+                std::cmp::Ordering::Less => Some(b),
+                std::cmp::Ordering::Greater => Some(a),
+                std::cmp::Ordering::Equal if direction == SnapDirection::NearestUp => Some(a),
+                std::cmp::Ordering::Equal => Some(b),
+            },
+            (_, b, a) => a.or(b),
+        };
+        snapped.map(|p| u7::new(p as u8))
+    }
+
+    /// Returns a copy of `phrase` in which every `Note` (including the notes of each
+    /// `Chord`) that does not belong to the `Scale` is moved onto it. Rhythms,
+    /// dynamics, rests and the name of the `Phrase` are preserved.
+    ///
+    /// # Errors
+    ///
+    /// * `ScaleError::NoPitchInRange` if a pitch cannot be snapped in the given direction
+    ///   (e.g. snapping up a note that is above the highest pitch of the `Scale`)
+    pub fn snap_phrase(&self, phrase: &Phrase, direction: SnapDirection) -> Result<Phrase> {
+        let snap_note = |n: &crate::Note| {
+            self.snap(n.pitch(), direction)
+                .map(|p| n.with_pitch(p))
+                .ok_or(ScaleError::NoPitchInRange(n.pitch().as_int()))
+        };
+        let mut snapped = Phrase::new();
+        snapped.set_name(phrase.name());
+        for entry in phrase.entries() {
+            match entry {
+                PhraseEntry::Note(n) => snapped.add_note(snap_note(n)?),
+                PhraseEntry::Chord(c) => {
+                    let notes = c
+                        .notes()
+                        .iter()
+                        .map(snap_note)
+                        .collect::<std::result::Result<Vec<_>, _>>()?;
+                    snapped.add_chord(Chord::new(c.rhythm(), notes)?);
+                }
+                PhraseEntry::Rest(r) => snapped.add_rest(*r),
+            }
+        }
+        Ok(snapped)
+    }
+
+    /// Returns the pitch at the given number of scale steps above the tonic
+    /// pitch class on octave `0` (which can be negative)
+    pub(crate) fn step_to_pitch(&self, step: i32) -> i32 {
+        self.step_to_pitch_with(self.scale_mode.intervals(), step)
+    }
+
+    /// Same as `step_to_pitch` but uses the given list of intervals instead of
+    /// the ascending intervals of the mode
+    fn step_to_pitch_with(&self, intervals: &[u8], step: i32) -> i32 {
+        let len = intervals.len() as i32 + 1;
+        let tonic_class = (self.tonic_pitch.as_int() % 12) as i32;
+        tonic_class + 12 * step.div_euclid(len) + degree_offset(intervals, step.rem_euclid(len))
+    }
+
+    /// Same as `step_to_pitch` but fails if the pitch is not a valid MIDI pitch
+    pub(crate) fn step_pitch(&self, step: i32) -> Result<u7> {
+        let pitch = self.step_to_pitch(step);
+        if !(0..=127).contains(&pitch) {
+            return Err(NoteError::InvalidPitch(pitch.max(0) as u32).into());
+        }
+        Ok(u7::new(pitch as u8))
+    }
+
+    /// Returns the number of scale steps between the tonic pitch class on octave `0`
+    /// and `pitch`, or `None` if `pitch` does not belong to the `Scale`
+    pub(crate) fn step_of(&self, pitch: i32) -> Option<i32> {
+        let len = self.num_degrees() as i32;
+        let tonic_class = (self.tonic_pitch.as_int() % 12) as i32;
+        let relative = pitch - tonic_class;
+        let octave = relative.div_euclid(12);
+        let offset = relative.rem_euclid(12);
+        (0..len as usize)
+            .position(|i| degree_offset(self.scale_mode.intervals(), i as i32) == offset)
+            .map(|i| octave * len + i as i32)
+    }
+}
+
+/// Iterates over the pitches of a `Scale` in a given direction, stopping at a pitch bound
+/// instead of a number of pitches. Can be created with `Scale::run()`.
+#[derive(Debug, Clone)]
+pub struct ScaleRun {
+    scale: Scale,
+    step: i32,
+    lowest: i32,
+    highest: i32,
+    descending: bool,
+    can_turn: bool,
+    started: bool,
+    done: bool,
+}
+
+impl Iterator for ScaleRun {
+    type Item = u7;
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.done {
+            let intervals = if self.descending {
+                self.scale.scale_mode.descending_intervals()
+            } else {
+                self.scale.scale_mode.intervals()
+            };
+            let pitch = self.scale.step_to_pitch_with(intervals, self.step);
+            let move_by = if self.descending { -1 } else { 1 };
+            if (self.lowest..=self.highest).contains(&pitch) && (0..=127).contains(&pitch) {
+                self.step += move_by;
+                self.started = true;
+                return Some(u7::new(pitch as u8));
+            }
+            if self.can_turn && self.started {
+                // go back to the pitch before the extreme one, which was already issued
+                self.can_turn = false;
+                self.descending = !self.descending;
+                self.step -= 2 * move_by;
+            } else {
+                self.done = true;
+            }
+        }
+        None
+    }
 }
 
 /// Generates a series of pitches from a given series of intervals and a base (tonic) pitch.
@@ -140,9 +453,98 @@ impl Iterator for ScalePitchesIterator<'_> {
 
 #[cfg(test)]
 mod tests {
-    use super::ScalePitchesIterator;
+    use super::{RunDirection, Scale, ScaleMode, ScalePitchesIterator, SnapDirection};
+    use crate::errors::ScaleError;
+    use crate::num::u7;
     use crate::*;
 
+    #[test]
+    fn scale_queries() -> Result<()> {
+        let d_major = Scale::new(
+            compute_pitch(NoteName::D, Accidental::Natural, 4)?,
+            ScaleMode::MAJOR,
+        );
+        assert!(d_major.contains(compute_pitch(NoteName::F, Accidental::Sharp, 2)?));
+        assert!(!d_major.contains(compute_pitch(NoteName::F, Accidental::Natural, 2)?));
+        assert_eq!(
+            d_major.degree_of(compute_pitch(NoteName::C, Accidental::Sharp, 7)?),
+            Some(7)
+        );
+        assert_eq!(d_major.degree_of(u7::new(62)), Some(1));
+        assert_eq!(d_major.pitch_at_degree(3, 4)?, u7::new(54));
+        assert_eq!(d_major.pitch_at_degree(9, 4)?, u7::new(64));
+        assert!(d_major.pitch_at_degree(0, 4).is_err());
+
+        // F natural is exactly between E and F#
+        let f = u7::new(65);
+        assert_eq!(d_major.snap(f, SnapDirection::Up), Some(u7::new(66)));
+        assert_eq!(d_major.snap(f, SnapDirection::Down), Some(u7::new(64)));
+        assert_eq!(d_major.snap(f, SnapDirection::NearestUp), Some(u7::new(66)));
+        assert_eq!(
+            d_major.snap(f, SnapDirection::NearestDown),
+            Some(u7::new(64))
+        );
+
+        let mut phrase = Phrase::new();
+        phrase.add_note(Note::new(f, rhythm::CROTCHET, dynamic::MF)?);
+        phrase.add_rest(rhythm::QUAVER);
+        phrase.add_chord(Chord::from_pitches(
+            rhythm::MINIM,
+            dynamic::MF,
+            &[u7::new(60), u7::new(62)],
+        )?);
+        let snapped = d_major.snap_phrase(&phrase, SnapDirection::NearestDown)?;
+        let mut expected = Phrase::new();
+        expected.add_note(Note::new(u7::new(64), rhythm::CROTCHET, dynamic::MF)?);
+        expected.add_rest(rhythm::QUAVER);
+        expected.add_chord(Chord::from_pitches(
+            rhythm::MINIM,
+            dynamic::MF,
+            &[u7::new(59), u7::new(62)],
+        )?);
+        assert_eq!(snapped, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn scale_runs() -> Result<()> {
+        let a_melodic = Scale::new(u7::new(57), ScaleMode::MelodicMinor);
+        let up_down = a_melodic
+            .run(5, 4, RunDirection::AscendingDescending, u7::new(69))?
+            .map(u7::as_int)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            up_down,
+            vec![64, 66, 68, 69, 67, 65, 64],
+            "descending run should use the natural minor"
+        );
+
+        let c_major = Scale::new(u7::new(60), ScaleMode::MAJOR);
+        let down = c_major
+            .run(3, 5, RunDirection::Descending, u7::new(57))?
+            .map(u7::as_int)
+            .collect::<Vec<_>>();
+        assert_eq!(down, vec![64, 62, 60, 59, 57]);
+        // from the natural 7th of A melodic minor, the run comes back up to the raised 7th
+        let down_up = a_melodic
+            .run(7, 4, RunDirection::DescendingAscending, u7::new(64))?
+            .map(u7::as_int)
+            .collect::<Vec<_>>();
+        assert_eq!(down_up, vec![67, 65, 64, 66, 68]);
+        assert!(matches!(
+            c_major.run(1, 5, RunDirection::Ascending, u7::new(59)),
+            Err(Error::Scale(ScaleError::InvalidRunBound(59, 60)))
+        ));
+        assert_eq!(
+            c_major
+                .run(1, 10, RunDirection::Ascending, u7::new(127))?
+                .map(u7::as_int)
+                .collect::<Vec<_>>(),
+            vec![120, 122, 124, 125, 127]
+        );
+        Ok(())
+    }
+
     #[test]
     fn scale_pitches_iterator() -> Result<()> {
         let pitches = vec![3, 5, 7];
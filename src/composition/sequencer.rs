@@ -0,0 +1,366 @@
+use crate::composition::random::Rng;
+use crate::composition::timeline::phrase_from_timed_notes;
+use crate::errors::NoteError;
+use crate::num::u7;
+use crate::{Instrument, Note, Part, Phrase, Result};
+
+/// Describes an active step of a `StepPattern` row
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Step {
+    /// The velocity (dynamic) of the note
+    pub velocity: u7,
+    /// The probability that the step plays, between `0.0` and `1.0`
+    pub probability: f64,
+    /// The number of notes played during the step (`1` for a single note, `0` is
+    /// treated as `1`)
+    pub ratchets: u8,
+    /// If true, the step continues the note of the previous step of the row instead of
+    /// playing a new one. A tied step after an inactive step plays normally.
+    pub tie: bool,
+}
+
+impl Step {
+    /// Returns a `Step` that always plays a single note with the given velocity
+    pub fn new(velocity: u7) -> Self {
+        Self {
+            velocity,
+            probability: 1.,
+            ratchets: 1,
+            tie: false,
+        }
+    }
+
+    /// Returns a `Step` that continues the note of the previous step
+    pub fn tie() -> Self {
+        Self {
+            tie: true,
+            ..Self::new(crate::dynamic::MF)
+        }
+    }
+}
+
+/// A row of a `StepPattern`: the steps played on a pitch or a `DrumSound`
+#[derive(Debug, Clone, PartialEq)]
+pub struct SequencerRow {
+    /// The pitch of the notes of the row
+    pitch: u7,
+    /// The steps of the row (`None` for inactive steps)
+    steps: Vec<Option<Step>>,
+}
+
+impl SequencerRow {
+    /// Returns the pitch of the notes of the row
+    pub fn pitch(&self) -> u7 {
+        self.pitch
+    }
+
+    /// Returns the steps of the row (`None` for inactive steps)
+    pub fn steps(&self) -> &[Option<Step>] {
+        &self.steps
+    }
+}
+
+/// A step-sequencer pattern: a grid of steps of equal length with one row per pitch
+/// (or `DrumSound`)
+#[derive(Debug, Clone, PartialEq)]
+pub struct StepPattern {
+    /// The number of steps of the pattern
+    num_steps: usize,
+    /// The rhythm value of each step
+    step: f64,
+    /// The rows of the grid
+    rows: Vec<SequencerRow>,
+}
+
+impl StepPattern {
+    /// Returns a new empty `StepPattern`
+    ///
+    /// # Arguments
+    ///
+    /// * `num_steps` - The number of steps of the pattern (e.g. `16` for a bar of
+    ///   semiquavers in 4/4)
+    /// * `step` - The rhythm value of each step
+    ///
+    /// # Errors
+    ///
+    /// * `NoteError::InvalidRhythm` if `step` is below `0.000_001`
+    pub fn new(num_steps: usize, step: f64) -> Result<Self> {
+        if step < 0.000_001 {
+            return Err(NoteError::InvalidRhythm(step).into());
+        }
+        Ok(Self {
+            num_steps,
+            step,
+            rows: Vec::new(),
+        })
+    }
+
+    /// Sets a step of the row of `pitch` (the row is created if needed).
+    /// Steps beyond the number of steps of the pattern are ignored.
+    pub fn set_step<P: Into<u7>>(&mut self, pitch: P, index: usize, step: Step) {
+        if let Some(s) = self.row_mut(pitch.into()).steps.get_mut(index) {
+            *s = Some(step);
+        }
+    }
+
+    /// Deactivates a step of the row of `pitch`
+    pub fn clear_step<P: Into<u7>>(&mut self, pitch: P, index: usize) {
+        if let Some(s) = self.row_mut(pitch.into()).steps.get_mut(index) {
+            *s = None;
+        }
+    }
+
+    /// Sets the steps of the row of `pitch` from a text grid with one character per
+    /// step: `x` plays a note with the `velocity`, `X` plays an accented note
+    /// (`dynamic::FF`), `-` ties the step to the previous one, and any other character
+    /// (e.g. `.`) is an inactive step. Whitespaces are ignored, so steps can be grouped
+    /// by beat (`"x... x.x. ..x- x..."`).
+    pub fn set_row<P: Into<u7>>(&mut self, pitch: P, grid: &str, velocity: u7) {
+        let row = self.row_mut(pitch.into());
+        let cells = grid.chars().filter(|c| !c.is_whitespace());
+        for (step, cell) in row.steps.iter_mut().zip(cells) {
+            *step = match cell {
+                'x' => Some(Step::new(velocity)),
+                'X' => Some(Step::new(crate::dynamic::FF)),
+                '-' => Some(Step::tie()),
+                _ => None,
+            };
+        }
+    }
+
+    /// Returns the number of steps of the pattern
+    pub fn num_steps(&self) -> usize {
+        self.num_steps
+    }
+
+    /// Returns the rhythm value of each step
+    pub fn step(&self) -> f64 {
+        self.step
+    }
+
+    /// Returns the duration of the pattern in beats
+    pub fn duration(&self) -> f64 {
+        self.step * self.num_steps as f64
+    }
+
+    /// Returns the rows of the pattern
+    pub fn rows(&self) -> &[SequencerRow] {
+        &self.rows
+    }
+
+    /// Returns a `Phrase` playing the pattern once.
+    /// `seed` is used to decide if the steps with a probability below `1.0` are played.
+    ///
+    /// # Errors
+    ///
+    /// * Any error returned when creating the notes
+    pub fn to_phrase(&self, seed: u64) -> Result<Phrase> {
+        let mut notes = Vec::new();
+        self.add_notes(&mut notes, 0., &mut Rng::new(seed))?;
+        phrase_from_timed_notes(notes, self.duration())
+    }
+
+    /// Returns the row of `pitch`, created if needed
+    fn row_mut(&mut self, pitch: u7) -> &mut SequencerRow {
+        let index = match self.rows.iter().position(|r| r.pitch == pitch) {
+            Some(index) => index,
+            None => {
+                self.rows.push(SequencerRow {
+                    pitch,
+                    steps: vec![None; self.num_steps],
+                });
+                self.rows.len() - 1
+            }
+        };
+        &mut self.rows[index]
+    }
+
+    /// Adds the notes of the pattern starting at beat `offset` to `notes`
+    fn add_notes(&self, notes: &mut Vec<(f64, Note)>, offset: f64, rng: &mut Rng) -> Result<()> {
+        for row in self.rows.iter() {
+            // (start, length, velocity) of the note that can be extended by ties
+            let mut current: Option<(f64, f64, u7)> = None;
+            for (i, step) in row.steps.iter().enumerate() {
+                let start = offset + self.step * i as f64;
+                let Some(step) = step else {
+                    Self::flush(notes, row.pitch, current.take())?;
+                    continue;
+                };
+                if step.tie {
+                    if let Some(note) = current.as_mut() {
+                        note.1 += self.step;
+                        continue;
+                    }
+                }
+                Self::flush(notes, row.pitch, current.take())?;
+                if step.probability < 1. && rng.next_f64() >= step.probability {
+                    continue;
+                }
+                let ratchets = step.ratchets.max(1);
+                let length = self.step / ratchets as f64;
+                for r in 0..ratchets - 1 {
+                    let onset = start + length * r as f64;
+                    notes.push((onset, Note::new(row.pitch, length, step.velocity)?));
+                }
+                // the last ratchet can be extended by the next tied steps
+                current = Some((
+                    start + length * (ratchets - 1) as f64,
+                    length,
+                    step.velocity,
+                ));
+            }
+            Self::flush(notes, row.pitch, current)?;
+        }
+        Ok(())
+    }
+
+    /// Adds the note `(start, length, velocity)` to `notes` if there is one
+    fn flush(notes: &mut Vec<(f64, Note)>, pitch: u7, note: Option<(f64, f64, u7)>) -> Result<()> {
+        if let Some((start, length, velocity)) = note {
+            notes.push((start, Note::new(pitch, length, velocity)?));
+        }
+        Ok(())
+    }
+}
+
+/// A chain of `StepPattern`s, each repeated a number of times, played one after the other
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct StepSequence {
+    patterns: Vec<(StepPattern, usize)>,
+}
+
+impl StepSequence {
+    /// Returns a new empty `StepSequence`
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends a `StepPattern` played `repeats` times at the end of the sequence
+    pub fn add_pattern(&mut self, pattern: StepPattern, repeats: usize) {
+        self.patterns.push((pattern, repeats));
+    }
+
+    /// Returns the patterns of the sequence with their number of repeats
+    pub fn patterns(&self) -> &[(StepPattern, usize)] {
+        &self.patterns
+    }
+
+    /// Returns the duration of the sequence in beats
+    pub fn duration(&self) -> f64 {
+        self.patterns
+            .iter()
+            .map(|(p, repeats)| p.duration() * *repeats as f64)
+            .sum()
+    }
+
+    /// Returns a `Phrase` playing the whole sequence. `seed` is used to decide if the
+    /// steps with a probability below `1.0` are played (each repeat is drawn again).
+    ///
+    /// # Errors
+    ///
+    /// * Any error returned when creating the notes
+    pub fn to_phrase(&self, seed: u64) -> Result<Phrase> {
+        let mut rng = Rng::new(seed);
+        let mut notes = Vec::new();
+        let mut offset = 0.;
+        for (pattern, repeats) in self.patterns.iter() {
+            for _ in 0..*repeats {
+                pattern.add_notes(&mut notes, offset, &mut rng)?;
+                offset += pattern.duration();
+            }
+        }
+        phrase_from_timed_notes(notes, offset)
+    }
+
+    /// Returns a `Part` playing the whole sequence with `instrument`
+    ///
+    /// # Errors
+    ///
+    /// * Same as `to_phrase`
+    pub fn to_part(&self, instrument: Instrument, seed: u64) -> Result<Part> {
+        let mut part = Part::new(instrument);
+        part.add_phrase(self.to_phrase(seed)?, 0.);
+        Ok(part)
+    }
+
+    /// Returns a percussion `Part` playing the whole sequence, for drum patterns
+    /// (see `Part::new_percussion`)
+    ///
+    /// # Errors
+    ///
+    /// * Same as `to_phrase`
+    pub fn to_percussion_part(&self, seed: u64) -> Result<Part> {
+        let mut part = Part::new_percussion();
+        part.add_phrase(self.to_phrase(seed)?, 0.);
+        Ok(part)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Step, StepPattern, StepSequence};
+    use crate::*;
+
+    #[test]
+    fn step_sequencer() -> Result<()> {
+        let mut beat = StepPattern::new(8, rhythm::QUAVER)?;
+        beat.set_row(DrumSound::BassDrum, "x... x...", dynamic::F);
+        beat.set_row(DrumSound::AcousticSnare, "..X- ..x.", dynamic::MF);
+        beat.set_step(
+            DrumSound::ClosedHiHat,
+            7,
+            Step {
+                ratchets: 2,
+                ..Step::new(dynamic::P)
+            },
+        );
+        let phrase = beat.to_phrase(0)?;
+        assert_eq!(phrase.duration(), 4.);
+        let hits: Vec<(f64, u8, f64)> = {
+            let mut time = 0.;
+            let mut hits = Vec::new();
+            for entry in phrase.entries() {
+                let notes = match entry {
+                    PhraseEntry::Note(n) => vec![n.clone()],
+                    PhraseEntry::Chord(c) => c.notes().to_vec(),
+                    PhraseEntry::Rest(_) => vec![],
+                };
+                hits.extend(notes.iter().map(|n| (time, n.pitch().as_int(), n.rhythm())));
+                time += entry.rhythm();
+            }
+            hits
+        };
+        assert_eq!(
+            hits,
+            vec![
+                (0., 36, 0.5),
+                (1., 38, 1.),
+                (2., 36, 0.5),
+                (3., 38, 0.5),
+                (3.5, 42, 0.25),
+                (3.75, 42, 0.25)
+            ]
+        );
+
+        let mut ghost = StepPattern::new(16, rhythm::SEMIQUAVER)?;
+        for i in 0..16 {
+            ghost.set_step(
+                DrumSound::ClosedHiHat,
+                i,
+                Step {
+                    probability: 0.5,
+                    ..Step::new(dynamic::MP)
+                },
+            );
+        }
+        let mut sequence = StepSequence::new();
+        sequence.add_pattern(beat, 2);
+        sequence.add_pattern(ghost, 1);
+        assert_eq!(sequence.duration(), 12.);
+        let part = sequence.to_percussion_part(7)?;
+        assert!(part.is_percussion());
+        assert_eq!(part.duration(), 12.);
+        assert_eq!(sequence.to_phrase(7)?, sequence.to_phrase(7)?);
+        Ok(())
+    }
+}
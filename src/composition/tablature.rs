@@ -0,0 +1,382 @@
+use std::fmt::Write;
+
+use crate::composition::guitar::HAND_SPAN;
+use crate::composition::Guitar;
+use crate::errors::TablatureError;
+use crate::note::pitch_info;
+use crate::num::u7;
+use crate::{Accidental, NoteEvents, Part, Phrase, Result, TICKS_PER_BEAT};
+/// Cost of moving the hand by one fret between two consecutive positions
+const MOVE_COST: f64 = 1.;
+/// Cost of each fret between the capo and the hand position
+const POSITION_COST: f64 = 0.1;
+/// Cost of each fret beyond the hand span in a chord
+const STRETCH_COST: f64 = 10.;
+
+/// Describes a note of a `Tablature`
+#[derive(Debug, Clone, PartialEq)]
+pub struct TabNote {
+    /// The beat at which the note starts
+    pub beat: f64,
+    /// The length of the note in beats
+    pub length: f64,
+    /// The pitch of the note
+    pub pitch: u7,
+    /// The string on which the note is played, from `0` for the lowest string
+    pub string: usize,
+    /// The fret at which the note is played, counted from the nut
+    pub fret: u8,
+}
+
+/// A tablature: the notes of a `Part` or `Phrase` placed on the strings and frets of
+/// a `Guitar` (or any fretted instrument, like a bass)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tablature {
+    /// The pitches of the open strings, from the lowest string
+    strings: Vec<u7>,
+    /// The notes sorted by beat, then from the lowest string
+    notes: Vec<TabNote>,
+}
+
+impl Tablature {
+    /// Returns the `Tablature` of a `Part` played on `guitar`. The phrases of the `Part`
+    /// are placed at their start beat.
+    ///
+    /// The strings and frets are chosen to minimize the movements of the fretting hand
+    /// over the whole `Part`, while keeping the notes played together within the span of
+    /// the hand whenever possible. Positions close to the nut (or the capo) are preferred.
+    ///
+    /// # Errors
+    ///
+    /// * `TablatureError::UnreachablePitch` if a pitch is not available on any string
+    /// * `TablatureError::NoFingering` if notes played together need the same string
+    pub fn from_part(part: &Part, guitar: &Guitar) -> Result<Self> {
+        Self::from_notes(
+            ticked_notes(NoteEvents::from_parts([(0, part)], 60)),
+            guitar,
+        )
+    }
+
+    /// Returns the `Tablature` of a `Phrase` played on `guitar`
+    ///
+    /// # Errors
+    ///
+    /// * Same as `from_part`
+    pub fn from_phrase(phrase: &Phrase, guitar: &Guitar) -> Result<Self> {
+        Self::from_notes(ticked_notes(NoteEvents::from_phrase(phrase, 0.)), guitar)
+    }
+
+    /// Returns the pitches of the open strings, from the lowest string
+    pub fn strings(&self) -> &[u7] {
+        &self.strings
+    }
+
+    /// Returns the notes of the `Tablature`, sorted by beat
+    pub fn notes(&self) -> &[TabNote] {
+        &self.notes
+    }
+
+    /// Returns the tablature as ASCII text, with the highest string on top and bar lines.
+    /// Long tablatures are split in several systems separated by an empty line.
+    ///
+    /// # Arguments
+    ///
+    /// * `subdivisions` - The number of columns per beat (notes are placed on the closest
+    ///   column)
+    /// * `beats_per_bar` - The number of beats between two bar lines
+    /// * `bars_per_line` - The number of bars in each system
+    pub fn to_ascii(&self, subdivisions: u32, beats_per_bar: u32, bars_per_line: usize) -> String {
+        let subdivisions = subdivisions.max(1) as usize;
+        let steps_per_bar = subdivisions * beats_per_bar.max(1) as usize;
+        let bars_per_line = bars_per_line.max(1);
+        let end = self
+            .notes
+            .iter()
+            .map(|n| n.beat + n.length)
+            .fold(0., f64::max);
+        let num_bars = ((end * subdivisions as f64 - 0.000_001) / steps_per_bar as f64)
+            .ceil()
+            .max(1.) as usize;
+        let width = if self.notes.iter().any(|n| n.fret >= 10) {
+            2
+        } else {
+            1
+        };
+
+        // grid[string][step]
+        let mut grid = vec![vec![None; num_bars * steps_per_bar]; self.strings.len()];
+        for note in self.notes.iter() {
+            let step = (note.beat * subdivisions as f64).round() as usize;
+            if let Some(cell) = grid[note.string].get_mut(step) {
+                *cell = Some(note.fret);
+            }
+        }
+
+        let labels = self.string_labels();
+        let label_width = labels.iter().map(String::len).max().unwrap_or(0);
+        let mut text = String::new();
+        for (line, bars) in (0..num_bars)
+            .collect::<Vec<_>>()
+            .chunks(bars_per_line)
+            .enumerate()
+        {
+            if line > 0 {
+                text.push('\n');
+            }
+            for string in (0..self.strings.len()).rev() {
+                let _ = write!(text, "{:<label_width$}|", labels[string]);
+                for bar in bars {
+                    for cell in grid[string][bar * steps_per_bar..(bar + 1) * steps_per_bar].iter()
+                    {
+                        match cell {
+                            Some(fret) => {
+                                let _ = write!(text, "{:->width$}-", fret);
+                            }
+                            None => text.push_str(&"-".repeat(width + 1)),
+                        }
+                    }
+                    text.push('|');
+                }
+                text.push('\n');
+            }
+        }
+        text
+    }
+
+    /// Returns the tablature as text annotated with strings and frets: one line per onset
+    /// with the beat followed by the notes played as `string/fret`, strings numbered from
+    /// `1` for the highest string. For example, a C major chord in standard tuning at
+    /// beat 2 is `2 5/3 4/2 3/0 2/1 1/0`.
+    pub fn to_annotated(&self) -> String {
+        let mut text = String::new();
+        let mut previous_beat = None;
+        for note in self.notes.iter() {
+            if previous_beat != Some(note.beat) {
+                if previous_beat.is_some() {
+                    text.push('\n');
+                }
+                let _ = write!(text, "{}", note.beat);
+                previous_beat = Some(note.beat);
+            }
+            let _ = write!(text, " {}/{}", self.strings.len() - note.string, note.fret);
+        }
+        if previous_beat.is_some() {
+            text.push('\n');
+        }
+        text
+    }
+
+    /// Returns the names of the open strings, from the lowest. The name of a higher
+    /// string is in lower case if a lower string has the same name (e.g. `e` for the
+    /// highest string in standard tuning).
+    fn string_labels(&self) -> Vec<String> {
+        let mut labels: Vec<String> = Vec::with_capacity(self.strings.len());
+        for pitch in self.strings.iter() {
+            let (name, accidental, _) = pitch_info(*pitch, true);
+            let mut label = name.letter().to_string();
+            if accidental == Accidental::Sharp {
+                label.push('#');
+            }
+            if labels.iter().any(|l| l.eq_ignore_ascii_case(&label)) {
+                label = label.to_lowercase();
+            }
+            labels.push(label);
+        }
+        labels
+    }
+
+    /// Places the notes `(start, end, pitch)` (in ticks) on the strings of `guitar`
+    fn from_notes(mut notes: Vec<(u64, u64, u8)>, guitar: &Guitar) -> Result<Self> {
+        notes.sort_unstable();
+        let mut groups: Vec<Vec<(u64, u64, u8)>> = Vec::new();
+        for note in notes {
+            match groups.last_mut() {
+                Some(group) if group[0].0 == note.0 => group.push(note),
+                _ => groups.push(vec![note]),
+            }
+        }
+
+        let mut candidates: Vec<Vec<Fingering>> = Vec::with_capacity(groups.len());
+        for group in groups.iter() {
+            let beat = group[0].0 as f64 / TICKS_PER_BEAT as f64;
+            let mut fingerings = Vec::new();
+            for (_, _, pitch) in group.iter() {
+                if positions(guitar, *pitch).is_empty() {
+                    return Err(TablatureError::UnreachablePitch(*pitch, beat).into());
+                }
+            }
+            let mut current = Vec::with_capacity(group.len());
+            collect_fingerings(guitar, group, &mut current, &mut fingerings);
+            if fingerings.is_empty() {
+                return Err(TablatureError::NoFingering(beat).into());
+            }
+            candidates.push(fingerings);
+        }
+
+        // Viterbi search of the cheapest sequence of fingerings. Groups with only open
+        // strings keep the hand where it was.
+        let mut costs: Vec<Vec<(f64, usize, Option<u8>)>> = Vec::with_capacity(groups.len());
+        for (g, fingerings) in candidates.iter().enumerate() {
+            let layer = fingerings
+                .iter()
+                .map(|f| {
+                    let Some(previous) = g.checked_sub(1).map(|p| &costs[p]) else {
+                        return (f.cost, 0, f.position);
+                    };
+                    let (index, (cost, position)) = previous
+                        .iter()
+                        .map(|(cost, _, position)| match (position, f.position) {
+                            (Some(from), Some(to)) => (
+                                cost + MOVE_COST * (*from as f64 - to as f64).abs(),
+                                Some(to),
+                            ),
+                            (from, to) => (*cost, to.or(*from)),
+                        })
+                        .enumerate()
+                        .min_by(|a, b| a.1 .0.total_cmp(&b.1 .0))
+                        .unwrap_or((0, (0., f.position)));
+                    (cost + f.cost, index, position)
+                })
+                .collect();
+            costs.push(layer);
+        }
+
+        let mut notes = Vec::new();
+        let mut index = costs
+            .last()
+            .and_then(|layer| (0..layer.len()).min_by(|a, b| layer[*a].0.total_cmp(&layer[*b].0)))
+            .unwrap_or(0);
+        for g in (0..groups.len()).rev() {
+            for ((start, end, pitch), (string, fret)) in
+                groups[g].iter().zip(candidates[g][index].frets.iter())
+            {
+                notes.push(TabNote {
+                    beat: *start as f64 / TICKS_PER_BEAT as f64,
+                    length: (end - start) as f64 / TICKS_PER_BEAT as f64,
+                    pitch: u7::new(*pitch),
+                    string: *string,
+                    fret: *fret,
+                });
+            }
+            index = costs[g][index].1;
+        }
+        notes.sort_by(|a, b| a.beat.total_cmp(&b.beat).then(a.string.cmp(&b.string)));
+        Ok(Self {
+            strings: guitar.tuning().strings().to_vec(),
+            notes,
+        })
+    }
+}
+
+/// Describes a way to play notes starting together
+struct Fingering {
+    /// The string and fret of each note
+    frets: Vec<(usize, u8)>,
+    /// The lowest fretted fret (`None` if all the strings are open)
+    position: Option<u8>,
+    /// The cost of playing the notes in this position
+    cost: f64,
+}
+
+/// Returns the notes as `(start, end, pitch)` with times in ticks
+fn ticked_notes(events: NoteEvents) -> Vec<(u64, u64, u8)> {
+    events
+        .map(|e| (e.start_tick, e.end_tick, e.pitch().as_int()))
+        .collect()
+}
+
+/// Returns the `(string, fret)` positions at which `pitch` can be played
+fn positions(guitar: &Guitar, pitch: u8) -> Vec<(usize, u8)> {
+    guitar
+        .tuning()
+        .strings()
+        .iter()
+        .enumerate()
+        .filter_map(|(string, open)| {
+            let fret = pitch.checked_sub(open.as_int())?;
+            (guitar.pitch_at(string, fret).is_some()).then_some((string, fret))
+        })
+        .collect()
+}
+
+/// Recursively assigns a different string to each note of the group and stores
+/// the resulting fingerings with their cost
+fn collect_fingerings(
+    guitar: &Guitar,
+    group: &[(u64, u64, u8)],
+    current: &mut Vec<(usize, u8)>,
+    fingerings: &mut Vec<Fingering>,
+) {
+    let Some((_, _, pitch)) = group.get(current.len()) else {
+        let fretted = current
+            .iter()
+            .map(|(_, f)| *f)
+            .filter(|f| *f > guitar.capo());
+        let (lowest, highest) = fretted.fold((None, 0), |(low, high), f| {
+            (Some(low.map_or(f, |l: u8| l.min(f))), high.max(f))
+        });
+        let stretch = lowest.map_or(0, |l| (highest - l).saturating_sub(HAND_SPAN));
+        let position = lowest.map_or(0, |l| l - guitar.capo());
+        fingerings.push(Fingering {
+            frets: current.clone(),
+            position: lowest,
+            cost: POSITION_COST * position as f64 + STRETCH_COST * stretch as f64,
+        });
+        return;
+    };
+    for (string, fret) in positions(guitar, *pitch) {
+        if current.iter().any(|(s, _)| *s == string) {
+            continue;
+        }
+        current.push((string, fret));
+        collect_fingerings(guitar, group, current, fingerings);
+        current.pop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Tablature;
+    use crate::composition::{Guitar, Tuning};
+    use crate::errors::TablatureError;
+    use crate::num::u7;
+    use crate::*;
+
+    #[test]
+    fn tablature() -> Result<()> {
+        let guitar = Guitar::new(Tuning::standard());
+        let mut phrase = Phrase::from_notes_sequence(Note::new_sequence(
+            rhythm::QUAVER,
+            dynamic::MF,
+            [55, 57, 59, 60].map(u7::new),
+        ))?;
+        phrase.add_chord(Chord::from_pitches(
+            rhythm::MINIM,
+            dynamic::MF,
+            &[48, 52, 55, 60, 64].map(u7::new),
+        )?);
+        let tab = Tablature::from_phrase(&phrase, &guitar)?;
+        assert_eq!(
+            tab.to_ascii(2, 4, 1),
+            "e|--------0-------|\n\
+             B|----0-1-1-------|\n\
+             G|0-2-----0-------|\n\
+             D|--------2-------|\n\
+             A|--------3-------|\n\
+             E|----------------|\n"
+        );
+        assert_eq!(
+            tab.to_annotated(),
+            "0 3/0\n0.5 3/2\n1 2/0\n1.5 2/1\n2 5/3 4/2 3/0 2/1 1/0\n"
+        );
+
+        let mut bass = Phrase::new();
+        bass.add_note(Note::new(u7::new(20), rhythm::CROTCHET, dynamic::MF)?);
+        let error = Tablature::from_phrase(&bass, &Guitar::new(Tuning::bass()));
+        assert!(matches!(
+            error,
+            Err(Error::Tablature(TablatureError::UnreachablePitch(20, _)))
+        ));
+        Ok(())
+    }
+}
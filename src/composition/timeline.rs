@@ -0,0 +1,50 @@
+use crate::{Chord, Note, Phrase, Result};
+
+/// Returns a `Phrase` playing notes placed at arbitrary beats, lasting `duration` beats.
+///
+/// Notes starting together are grouped in a `Chord`, and rests fill the gaps between the
+/// end of the longest note of a group and the next onset. Notes can overlap the next
+/// onsets. Onsets closer than `0.000_001` beats are considered simultaneous.
+///
+/// # Arguments
+///
+/// * `notes` - The start beat of each note and the note
+/// * `duration` - The duration of the `Phrase` (extended to the end of the last onset
+///   group if needed)
+///
+/// # Errors
+///
+/// * Any error returned when creating the chords
+pub(crate) fn phrase_from_timed_notes(
+    mut notes: Vec<(f64, Note)>,
+    duration: f64,
+) -> Result<Phrase> {
+    notes.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let mut phrase = Phrase::new();
+    let mut time = 0.;
+    let mut i = 0;
+    while i < notes.len() {
+        let start = notes[i].0;
+        if start - time >= 0.000_001 {
+            phrase.add_rest(start - time);
+        }
+        let mut group = Vec::new();
+        while i < notes.len() && notes[i].0 - start < 0.000_001 {
+            group.push(notes[i].1.clone());
+            i += 1;
+        }
+        let longest = group.iter().map(Note::rhythm).fold(0., f64::max);
+        let next = notes.get(i).map_or(duration.max(start + longest), |n| n.0);
+        let advance = (next - start).min(longest);
+        if group.len() == 1 && (group[0].rhythm() - advance).abs() < 0.000_001 {
+            phrase.add_note(group.remove(0));
+        } else {
+            phrase.add_chord(Chord::new(advance, group)?);
+        }
+        time = start + advance;
+    }
+    if duration - time >= 0.000_001 {
+        phrase.add_rest(duration - time);
+    }
+    Ok(phrase)
+}
@@ -0,0 +1,465 @@
+use crate::composition::ChordSymbol;
+use crate::errors::{GenerationError, VoiceLeadingError};
+use crate::num::u7;
+use crate::{Chord, Instrument, Note, Part, Phrase, Result};
+
+/// Penalty added for each pair of voices moving in parallel fifths or octaves. They are
+/// not forbidden: a progression where every voicing has parallels is still voiced.
+const PARALLEL_PENALTY: i32 = 100;
+/// Penalty added for each common tone that is not kept in the same voice
+const COMMON_TONE_PENALTY: i32 = 3;
+/// Penalty added for each voice leaping more than a fifth
+const LEAP_PENALTY: i32 = 4;
+/// Maximum number of voicings of a chord that are considered
+const MAX_VOICINGS: usize = 4096;
+/// Number of cheapest partial sequences kept after each chord of the search
+const BEAM_WIDTH: usize = 64;
+
+/// Describes a chord to voice: the pitch classes that must be played and optionally
+/// the pitch class that must be in the lowest voice
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Harmony {
+    /// Distinct pitch classes (between `0` and `11`), the first one being the most
+    /// important (root) which is never omitted
+    classes: Vec<u8>,
+    /// Pitch class required in the lowest voice
+    bass: Option<u8>,
+}
+
+impl Harmony {
+    /// Returns a new `Harmony` from a set of pitch classes (`0` is C, values are taken
+    /// modulo `12`). The first pitch class is considered the root: it is never omitted
+    /// when there are more pitch classes than voices. Any of them can be in the bass.
+    pub fn new(classes: &[u8]) -> Self {
+        let mut distinct = Vec::new();
+        for c in classes.iter().map(|c| c % 12) {
+            if !distinct.contains(&c) {
+                distinct.push(c);
+            }
+        }
+        Self {
+            classes: distinct,
+            bass: None,
+        }
+    }
+
+    /// Returns a copy of the `Harmony` that requires `bass` (a pitch class) in the
+    /// lowest voice. The pitch class is added to the `Harmony` if needed.
+    pub fn with_bass(&self, bass: u8) -> Self {
+        let mut harmony = self.clone();
+        if !harmony.classes.contains(&(bass % 12)) {
+            harmony.classes.push(bass % 12);
+        }
+        harmony.bass = Some(bass % 12);
+        harmony
+    }
+
+    /// Returns the pitch classes of the `Harmony`
+    pub fn classes(&self) -> &[u8] {
+        &self.classes
+    }
+
+    /// Returns the pitch class required in the lowest voice, if any
+    pub fn bass(&self) -> Option<u8> {
+        self.bass
+    }
+}
+
+/// The bass of a `ChordSymbol` (its root, unless it is a slash chord) is
+/// required in the lowest voice
+impl From<ChordSymbol> for Harmony {
+    fn from(symbol: ChordSymbol) -> Self {
+        Self::new(&symbol.pitch_classes()).with_bass(symbol.bass_class())
+    }
+}
+
+/// The pitch classes of the notes of a `Chord`, its root first, with its lowest
+/// note required in the lowest voice
+impl From<&Chord> for Harmony {
+    fn from(chord: &Chord) -> Self {
+        let mut classes = vec![chord.root().as_int()];
+        classes.extend(chord.notes().iter().map(|n| n.pitch().as_int()));
+        let bass = chord
+            .notes()
+            .iter()
+            .map(Note::pitch)
+            .min()
+            .unwrap_or_default();
+        Self::new(&classes).with_bass(bass.as_int())
+    }
+}
+
+impl From<&[u8]> for Harmony {
+    fn from(classes: &[u8]) -> Self {
+        Self::new(classes)
+    }
+}
+
+impl From<Vec<u8>> for Harmony {
+    fn from(classes: Vec<u8>) -> Self {
+        Self::new(&classes)
+    }
+}
+
+/// Voices a sequence of chords for a fixed number of voices, each with its own range,
+/// so that the total movement of the voices is minimal.
+///
+/// The voices never cross, upper adjacent voices stay within an octave of each other,
+/// and common tones are kept in the same voice when possible. Parallel fifths and
+/// octaves are heavily penalized, so they are only used when every other voicing of
+/// a chord has them.
+///
+/// To keep the search fast with many voices or wide ranges, at most a few thousand
+/// voicings are considered per chord (from the lowest ones), and only the cheapest
+/// sequences are extended at each chord, so the result may not be the best one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VoiceLeader {
+    /// The range (lowest and highest pitch) of each voice, from the lowest voice
+    ranges: Vec<(u7, u7)>,
+}
+
+impl VoiceLeader {
+    /// Returns a new `VoiceLeader`
+    ///
+    /// # Arguments
+    ///
+    /// * `ranges` - The lowest and highest pitch of each voice, from the lowest voice
+    ///   to the highest
+    pub fn new(ranges: Vec<(u7, u7)>) -> Self {
+        Self { ranges }
+    }
+
+    /// Returns a `VoiceLeader` for the four voices of a choir, from the lowest:
+    /// bass (E3-C5), tenor (C4-G5), alto (G4-D6), and soprano (C5-G6)
+    pub fn satb() -> Self {
+        Self::new(
+            [(40, 60), (48, 67), (55, 74), (60, 79)]
+                .map(|(l, h)| (u7::new(l), u7::new(h)))
+                .to_vec(),
+        )
+    }
+
+    /// Returns the ranges of the voices, from the lowest voice
+    pub fn ranges(&self) -> &[(u7, u7)] {
+        &self.ranges
+    }
+
+    /// Returns the pitches of each chord of the sequence, from the lowest voice
+    ///
+    /// # Arguments
+    ///
+    /// * `harmonies` - The chords to voice: `ChordSymbol`s, pitch class sets, or `Harmony`s
+    ///
+    /// # Errors
+    ///
+    /// * `VoiceLeadingError::NoVoices` if the `VoiceLeader` has no voice
+    /// * `VoiceLeadingError::NoVoicing` if a chord cannot be voiced in the voice ranges
+    pub fn lead<H: Into<Harmony> + Clone>(&self, harmonies: &[H]) -> Result<Vec<Vec<u7>>> {
+        let harmonies: Vec<Harmony> = harmonies.iter().cloned().map(Into::into).collect();
+        self.lead_filtered(&harmonies, |_, _| true)
+    }
+
+    /// Returns a `Phrase` of `Chord`s that plays the voiced sequence
+    ///
+    /// # Arguments
+    ///
+    /// * `harmonies` - The chords to voice: `ChordSymbol`s, pitch class sets, or `Harmony`s
+    /// * `rhythms` - The rhythm values of the chords, repeated cyclically if there are
+    ///   fewer rhythm values than chords
+    /// * `dynamic` - The dynamic of every note
+    ///
+    /// # Errors
+    ///
+    /// * `GenerationError::EmptyRhythms` if `rhythms` is empty
+    /// * `NoteError::InvalidRhythm` if `rhythms` contains an invalid value
+    /// * Same errors as `lead`
+    pub fn to_phrase<H: Into<Harmony> + Clone>(
+        &self,
+        harmonies: &[H],
+        rhythms: &[f64],
+        dynamic: u7,
+    ) -> Result<Phrase> {
+        if rhythms.is_empty() {
+            return Err(GenerationError::EmptyRhythms.into());
+        }
+        let mut phrase = Phrase::new();
+        for (pitches, rhythm) in self.lead(harmonies)?.iter().zip(rhythms.iter().cycle()) {
+            phrase.add_chord(Chord::from_pitches(*rhythm, dynamic, pitches)?);
+        }
+        Ok(phrase)
+    }
+
+    /// Returns one `Part` per voice (from the lowest voice) that plays the voiced sequence
+    ///
+    /// # Arguments
+    ///
+    /// * `harmonies` - The chords to voice: `ChordSymbol`s, pitch class sets, or `Harmony`s
+    /// * `rhythms` - The rhythm values of the chords, repeated cyclically if there are
+    ///   fewer rhythm values than chords
+    /// * `dynamic` - The dynamic of every note
+    /// * `instrument` - The instrument of every `Part`
+    ///
+    /// # Errors
+    ///
+    /// * `GenerationError::EmptyRhythms` if `rhythms` is empty
+    /// * `NoteError::InvalidRhythm` if `rhythms` contains an invalid value
+    /// * Same errors as `lead`
+    pub fn to_parts<H: Into<Harmony> + Clone>(
+        &self,
+        harmonies: &[H],
+        rhythms: &[f64],
+        dynamic: u7,
+        instrument: Instrument,
+    ) -> Result<Vec<Part>> {
+        if rhythms.is_empty() {
+            return Err(GenerationError::EmptyRhythms.into());
+        }
+        voices_to_parts(&self.lead(harmonies)?, rhythms, dynamic, instrument)
+    }
+
+    /// Voices the sequence of harmonies, only considering the voicings for which
+    /// `filter(chord_index, pitches)` returns `true`
+    pub(crate) fn lead_filtered<F: Fn(usize, &[i32]) -> bool>(
+        &self,
+        harmonies: &[Harmony],
+        filter: F,
+    ) -> Result<Vec<Vec<u7>>> {
+        if self.ranges.is_empty() {
+            return Err(VoiceLeadingError::NoVoices.into());
+        }
+        let candidates = harmonies
+            .iter()
+            .enumerate()
+            .map(|(i, h)| {
+                let voicings: Vec<Vec<i32>> = self
+                    .voicings(h)
+                    .into_iter()
+                    .filter(|v| filter(i, v))
+                    .collect();
+                if voicings.is_empty() {
+                    return Err(VoiceLeadingError::NoVoicing(i));
+                }
+                Ok(voicings)
+            })
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        if candidates.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        // Viterbi search of the sequence of voicings with the lowest total cost
+        // (beam search), only extending the cheapest sequences
+        let mut costs: Vec<i32> = candidates[0].iter().map(|v| self.initial_cost(v)).collect();
+        let mut back: Vec<Vec<usize>> = Vec::with_capacity(candidates.len());
+        for pair in candidates.windows(2) {
+            let (prev, cur) = (&pair[0], &pair[1]);
+            let mut beam: Vec<usize> = (0..prev.len()).collect();
+            beam.sort_by_key(|i| costs[*i]);
+            beam.truncate(BEAM_WIDTH);
+            let mut new_costs = Vec::with_capacity(cur.len());
+            let mut pointers = Vec::with_capacity(cur.len());
+            for voicing in cur {
+                let (best, cost) = beam
+                    .iter()
+                    .map(|i| (*i, costs[*i] + transition_cost(&prev[*i], voicing)))
+                    .min_by_key(|(_, c)| *c)
+                    .unwrap_or_default();
+                new_costs.push(cost);
+                pointers.push(best);
+            }
+            costs = new_costs;
+            back.push(pointers);
+        }
+        let mut idx = (0..costs.len()).min_by_key(|i| costs[*i]).unwrap_or(0);
+        let mut result = vec![Vec::new(); candidates.len()];
+        for step in (0..candidates.len()).rev() {
+            result[step] = candidates[step][idx]
+                .iter()
+                .map(|p| u7::new(*p as u8))
+                .collect();
+            if step > 0 {
+                idx = back[step - 1][idx];
+            }
+        }
+        Ok(result)
+    }
+
+    /// Returns the valid voicings of a harmony in the voice ranges, from the lowest
+    /// ones, up to `MAX_VOICINGS` voicings
+    pub(crate) fn voicings(&self, harmony: &Harmony) -> Vec<Vec<i32>> {
+        let mut voicings = Vec::new();
+        let mut current = Vec::with_capacity(self.ranges.len());
+        self.collect_voicings(harmony, &mut current, &mut voicings);
+        voicings
+    }
+
+    /// Recursively builds the voicings from the lowest voice, stopping once
+    /// `MAX_VOICINGS` voicings are found
+    fn collect_voicings(
+        &self,
+        harmony: &Harmony,
+        current: &mut Vec<i32>,
+        voicings: &mut Vec<Vec<i32>>,
+    ) {
+        let voice = current.len();
+        let Some((low, high)) = self.ranges.get(voice) else {
+            if is_complete(harmony, current, self.ranges.len()) {
+                voicings.push(current.clone());
+            }
+            return;
+        };
+        let mut low = low.as_int() as i32;
+        let mut high = high.as_int() as i32;
+        if let Some(previous) = current.last() {
+            low = low.max(previous + 1);
+            if voice >= 2 {
+                high = high.min(previous + 12);
+            }
+        }
+        for pitch in low..=high {
+            let class = (pitch % 12) as u8;
+            let allowed = if voice == 0 && harmony.bass.is_some() {
+                harmony.bass == Some(class)
+            } else {
+                harmony.classes.contains(&class)
+            };
+            if allowed && voicings.len() < MAX_VOICINGS {
+                current.push(pitch);
+                self.collect_voicings(harmony, current, voicings);
+                current.pop();
+            }
+        }
+    }
+
+    /// Returns the cost of the first voicing, favoring the middle of the voice ranges
+    fn initial_cost(&self, voicing: &[i32]) -> i32 {
+        voicing
+            .iter()
+            .zip(self.ranges.iter())
+            .map(|(p, (l, h))| (2 * p - l.as_int() as i32 - h.as_int() as i32).abs() / 4)
+            .sum()
+    }
+}
+
+/// Returns true if a voicing contains the root of the harmony and enough of its
+/// pitch classes for the number of voices
+fn is_complete(harmony: &Harmony, voicing: &[i32], num_voices: usize) -> bool {
+    let mut classes: Vec<i32> = voicing.iter().map(|p| p % 12).collect();
+    classes.sort_unstable();
+    classes.dedup();
+    classes.len() >= harmony.classes.len().min(num_voices)
+        && harmony
+            .classes
+            .first()
+            .iter()
+            .all(|r| classes.contains(&(**r as i32)))
+}
+
+/// Returns the cost of moving from one voicing to the next
+fn transition_cost(previous: &[i32], next: &[i32]) -> i32 {
+    let mut cost = 0;
+    for (p, n) in previous.iter().zip(next) {
+        let movement = (n - p).abs();
+        cost += movement;
+        if movement > 7 {
+            cost += LEAP_PENALTY;
+        }
+    }
+    for (i, p) in previous.iter().enumerate() {
+        if next.iter().any(|n| n % 12 == p % 12) && next[i] != *p {
+            cost += COMMON_TONE_PENALTY;
+        }
+    }
+    cost + PARALLEL_PENALTY * count_parallels(previous, next) as i32
+}
+
+/// Returns the number of pairs of voices that move in parallel fifths or octaves
+/// (including unisons) from `previous` to `next`
+pub(crate) fn count_parallels(previous: &[i32], next: &[i32]) -> usize {
+    let mut count = 0;
+    for i in 0..previous.len().min(next.len()) {
+        for j in i + 1..previous.len().min(next.len()) {
+            let before = (previous[j] - previous[i]).rem_euclid(12);
+            let after = (next[j] - next[i]).rem_euclid(12);
+            let moved = previous[i] != next[i] && previous[j] != next[j];
+            if moved && before == after && (before == 0 || before == 7) {
+                count += 1;
+            }
+        }
+    }
+    count
+}
+
+/// Returns one `Part` per voice playing the given pitches (one vec per chord,
+/// from the lowest voice)
+pub(crate) fn voices_to_parts(
+    voicings: &[Vec<u7>],
+    rhythms: &[f64],
+    dynamic: u7,
+    instrument: Instrument,
+) -> Result<Vec<Part>> {
+    let num_voices = voicings.first().map_or(0, Vec::len);
+    (0..num_voices)
+        .map(|voice| {
+            let phrase = Phrase::from_notes_sequence(
+                voicings
+                    .iter()
+                    .zip(rhythms.iter().cycle())
+                    .map(|(v, r)| Note::new(v[voice], *r, dynamic)),
+            )?;
+            let mut part = Part::new(instrument);
+            part.add_phrase(phrase, 0.);
+            Ok(part)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{count_parallels, VoiceLeader};
+    use crate::composition::ChordSymbol;
+    use crate::errors::GenerationError;
+    use crate::*;
+
+    #[test]
+    fn voice_leading() -> Result<()> {
+        let progression = ["C", "Am", "F", "G7", "C"]
+            .iter()
+            .map(|s| s.parse::<ChordSymbol>())
+            .collect::<Result<Vec<_>>>()?;
+        let voicings = VoiceLeader::satb().lead(&progression)?;
+        assert_eq!(voicings.len(), 5);
+        let as_i32 = |v: &Vec<num::u7>| v.iter().map(|p| p.as_int() as i32).collect::<Vec<_>>();
+        for (voicing, symbol) in voicings.iter().zip(&progression) {
+            assert_eq!(voicing[0].as_int() % 12, symbol.root_class());
+            assert!(voicing.windows(2).all(|w| w[0] < w[1]));
+        }
+        for pair in voicings.windows(2) {
+            assert_eq!(count_parallels(&as_i32(&pair[0]), &as_i32(&pair[1])), 0);
+            // upper voices move by step or keep common tones
+            for voice in 1..4 {
+                let movement =
+                    (pair[0][voice].as_int() as i32 - pair[1][voice].as_int() as i32).abs();
+                assert!(movement <= 5, "{pair:?}");
+            }
+        }
+
+        let parts = VoiceLeader::satb().to_parts(
+            &[vec![0, 4, 7], vec![5, 9, 0]],
+            &[rhythm::MINIM],
+            dynamic::MF,
+            Instrument::VoiceAahs,
+        )?;
+        assert_eq!(parts.len(), 4);
+        assert_eq!(parts[3].duration(), 4.);
+        assert!(matches!(
+            VoiceLeader::satb().to_phrase(&progression, &[], dynamic::MF),
+            Err(Error::Generation(GenerationError::EmptyRhythms))
+        ));
+
+        // many voices with wide ranges are voiced without enumerating every voicing
+        let ranges = vec![(num::u7::new(36), num::u7::new(96)); 8];
+        let voicings = VoiceLeader::new(ranges).lead(&progression)?;
+        assert!(voicings.iter().all(|v| v.len() == 8));
+        Ok(())
+    }
+}
@@ -10,6 +10,18 @@ pub enum Error {
     Chord(#[from] ChordError),
     #[error("invalid score: {0}")]
     Score(#[from] ScoreError),
+    #[error("invalid scale operation: {0}")]
+    Scale(#[from] ScaleError),
+    #[error("voice leading failed: {0}")]
+    VoiceLeading(#[from] VoiceLeadingError),
+    #[error("harmonization failed: {0}")]
+    Harmonization(#[from] HarmonizationError),
+    #[error("generation failed: {0}")]
+    Generation(#[from] GenerationError),
+    #[error("tablature failed: {0}")]
+    Tablature(#[from] TablatureError),
+    #[error("rendering failed: {0}")]
+    Render(#[from] RenderError),
     #[error("error converting to MIDI: {0}")]
     ToMidiConversion(#[from] ToMidiConversionError),
 }
@@ -28,16 +40,88 @@ pub enum ChordError {
     EmptyChord,
     #[error("rhythm value is longer than its notes, use a rest")]
     RhythmTooLong,
+    #[error("chord has no inversion {0}")]
+    InvalidInversion(usize),
+    #[error("voicing requires at least {0} notes")]
+    NotEnoughNotes(usize),
+    #[error("chord does not fit between pitches {0} and {1}")]
+    RangeTooSmall(u8, u8),
+    #[error("invalid chord symbol: {0}")]
+    InvalidSymbol(String),
 }
 
 #[derive(Error, Debug, PartialEq)]
 pub enum ScoreError {
     #[error("tempo cannot be 0")]
     InvalidTempo,
+    #[error("invalid time signature: {0}/{1}")]
+    InvalidTimeSignature(u8, u8),
+}
+
+#[derive(Error, Debug, PartialEq)]
+pub enum ScaleError {
+    #[error("invalid scale degree: {0}")]
+    InvalidDegree(usize),
+    #[error("no pitch of the scale can be reached from pitch {0}")]
+    NoPitchInRange(u8),
+    #[error("run bound {0} is on the wrong side of the first pitch {1}")]
+    InvalidRunBound(u8, i32),
+}
+
+#[derive(Error, Debug, PartialEq)]
+pub enum VoiceLeadingError {
+    #[error("no voice to lead")]
+    NoVoices,
+    #[error("chord {0} cannot be voiced within the voice ranges")]
+    NoVoicing(usize),
+}
+
+#[derive(Error, Debug, PartialEq)]
+pub enum HarmonizationError {
+    #[error("melody contains no note")]
+    EmptyMelody,
+    #[error("melody pitch {0} is out of the voice range")]
+    MelodyOutOfRange(u8),
+    #[error("no chord of the scale can harmonize note {0} of the melody")]
+    NoChord(usize),
+}
+
+#[derive(Error, Debug, PartialEq)]
+pub enum GenerationError {
+    #[error("generator has not been trained")]
+    NotTrained,
+    #[error("no solution satisfies the constraints")]
+    NoSolution,
+    #[error("search budget exhausted after {0} iterations without solution")]
+    BudgetExhausted(usize),
+    #[error("initial population is empty")]
+    EmptyPopulation,
+    #[error("no rhythm value given")]
+    EmptyRhythms,
+}
+
+#[derive(Error, Debug, PartialEq)]
+pub enum RenderError {
+    #[error("image size {0}x{1} cannot be rasterized")]
+    InvalidImageSize(u32, u32),
+    #[error("invalid SVG document: {0}")]
+    InvalidSvg(String),
+    #[error("PNG encoding failed: {0}")]
+    PngEncoding(String),
+}
+
+#[derive(Error, Debug, PartialEq)]
+pub enum TablatureError {
+    #[error("pitch {0} at beat {1} cannot be played on the instrument")]
+    UnreachablePitch(u8, f64),
+    #[error("notes at beat {0} cannot be played together on the instrument")]
+    NoFingering(f64),
 }
 
 #[derive(Error, Debug, PartialEq)]
 pub enum ToMidiConversionError {
     #[error("too many parts (16 max): {0}")]
     TooManyParts(usize),
+    #[error("too many non-percussion parts alongside percussion parts (15 max): {0}")]
+    TooManyMelodicParts(usize),
 }
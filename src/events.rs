@@ -0,0 +1,249 @@
+use crate::{Note, Part, Phrase, PhraseEntry, Score};
+
+/// Number of MIDI ticks per beat, used by the MIDI export and to place notes in time
+pub const TICKS_PER_BEAT: u64 = 480;
+
+/// Returns the number of ticks of a duration in beats, rounded to the nearest tick
+pub(crate) fn to_ticks(beats: f64) -> u64 {
+    (beats * TICKS_PER_BEAT as f64).round() as u64
+}
+
+/// Describes a `Note` placed at its absolute position in time
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NoteEvent<'a> {
+    /// The note, with its rhythm value and dynamic
+    pub note: &'a Note,
+    /// The index of the `Part` of the note in the `Score`
+    pub part: usize,
+    /// The index of the `Phrase` of the note in its `Part`
+    pub phrase: usize,
+    /// The index of the entry (`Note` or `Chord`) of the note in its `Phrase`
+    pub entry: usize,
+    /// The tick at which the note starts
+    pub start_tick: u64,
+    /// The tick at which the note ends
+    pub end_tick: u64,
+    /// The beat at which the note starts
+    pub start: f64,
+    /// The beat at which the note ends
+    pub end: f64,
+    /// The time in seconds at which the note starts
+    pub start_seconds: f64,
+    /// The time in seconds at which the note ends
+    pub end_seconds: f64,
+}
+
+impl NoteEvent<'_> {
+    /// Returns the pitch of the note
+    pub fn pitch(&self) -> crate::num::u7 {
+        self.note.pitch()
+    }
+
+    /// Returns the velocity (dynamic) of the note
+    pub fn velocity(&self) -> crate::num::u7 {
+        self.note.dynamic()
+    }
+}
+
+/// Iterator over the notes of a `Score` placed in time, as played by the MIDI export.
+///
+/// The notes are yielded part by part, phrase by phrase, in the order of the entries
+/// (use `Score::sorted_events` to get them sorted by time). Each entry starts when the
+/// previous one ends, the notes of a `Chord` last for their own rhythm value, and times
+/// are rounded to the nearest tick (`TICKS_PER_BEAT` ticks per beat), the beats and
+/// seconds being computed from the ticks.
+#[derive(Debug, Clone)]
+pub struct NoteEvents<'a> {
+    /// The phrases as `(part index, phrase index, start beat, phrase)`
+    phrases: Vec<(usize, usize, f64, &'a Phrase)>,
+    /// The tempo in beats per minute
+    tempo: u32,
+    /// The index in `phrases` of the current phrase
+    phrase: usize,
+    /// The index of the current entry in the current phrase
+    entry: usize,
+    /// The index of the next note in the current entry
+    note: usize,
+    /// The tick at which the current entry starts
+    tick: u64,
+}
+
+impl<'a> NoteEvents<'a> {
+    /// Returns the events of the given parts, identified by their index, at `tempo`
+    pub(crate) fn from_parts<I: IntoIterator<Item = (usize, &'a Part)>>(
+        parts: I,
+        tempo: u32,
+    ) -> Self {
+        let phrases = parts
+            .into_iter()
+            .flat_map(|(index, part)| {
+                part.phrases()
+                    .iter()
+                    .enumerate()
+                    .map(move |(p, (start, phrase))| (index, p, *start, phrase))
+            })
+            .collect();
+        Self::new(phrases, tempo)
+    }
+
+    /// Returns the events of a single `Phrase` starting at `start_beat` (at 60 beats per
+    /// minute, so seconds are beats)
+    #[cfg(feature = "composition")]
+    pub(crate) fn from_phrase(phrase: &'a Phrase, start_beat: f64) -> Self {
+        Self::new(vec![(0, 0, start_beat, phrase)], 60)
+    }
+
+    /// Returns the events keeping only the highest note of each `Chord`, as heard in a
+    /// melodic line
+    #[cfg(feature = "composition")]
+    pub(crate) fn highest_per_entry(self) -> Vec<NoteEvent<'a>> {
+        let mut line: Vec<NoteEvent> = Vec::new();
+        for event in self {
+            match line.last_mut() {
+                Some(last)
+                    if (last.part, last.phrase, last.entry)
+                        == (event.part, event.phrase, event.entry) =>
+                {
+                    if event.pitch() > last.pitch() {
+                        *last = event;
+                    }
+                }
+                _ => line.push(event),
+            }
+        }
+        line
+    }
+
+    fn new(phrases: Vec<(usize, usize, f64, &'a Phrase)>, tempo: u32) -> Self {
+        let tick = phrases.first().map_or(0, |p| to_ticks(p.2));
+        Self {
+            phrases,
+            tempo: tempo.max(1),
+            phrase: 0,
+            entry: 0,
+            note: 0,
+            tick,
+        }
+    }
+}
+
+impl<'a> Iterator for NoteEvents<'a> {
+    type Item = NoteEvent<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (part, phrase_index, _, phrase) = *self.phrases.get(self.phrase)?;
+            let Some(entry) = phrase.entries().get(self.entry) else {
+                self.phrase += 1;
+                self.entry = 0;
+                self.note = 0;
+                self.tick = self.phrases.get(self.phrase).map_or(0, |p| to_ticks(p.2));
+                continue;
+            };
+            let notes = match entry {
+                PhraseEntry::Note(n) => std::slice::from_ref(n),
+                PhraseEntry::Chord(c) => c.notes(),
+                PhraseEntry::Rest(_) => &[],
+            };
+            let Some(note) = notes.get(self.note) else {
+                self.tick += to_ticks(entry.rhythm());
+                self.entry += 1;
+                self.note = 0;
+                continue;
+            };
+            self.note += 1;
+            let (start_tick, end_tick) = (self.tick, self.tick + to_ticks(note.rhythm()));
+            let beats = |tick: u64| tick as f64 / TICKS_PER_BEAT as f64;
+            let seconds = |tick: u64| beats(tick) * 60. / self.tempo as f64;
+            return Some(NoteEvent {
+                note,
+                part,
+                phrase: phrase_index,
+                entry: self.entry,
+                start_tick,
+                end_tick,
+                start: beats(start_tick),
+                end: beats(end_tick),
+                start_seconds: seconds(start_tick),
+                end_seconds: seconds(end_tick),
+            });
+        }
+    }
+}
+
+impl Score {
+    /// Returns an iterator over the notes of the `Score` placed in time (see
+    /// `NoteEvents`), part by part
+    pub fn events(&self) -> NoteEvents<'_> {
+        NoteEvents::from_parts(self.parts().iter().enumerate(), self.tempo())
+    }
+
+    /// Returns the notes of the `Score` placed in time, sorted by start time (notes
+    /// starting together keep the order of `events`)
+    pub fn sorted_events(&self) -> Vec<NoteEvent<'_>> {
+        let mut events: Vec<NoteEvent> = self.events().collect();
+        events.sort_by_key(|e| e.start_tick);
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::num::u7;
+    use crate::*;
+
+    #[test]
+    fn events() -> Result<()> {
+        let mut melody = Phrase::new();
+        melody.add_rest(rhythm::CROTCHET);
+        melody.add_chord(Chord::new(
+            rhythm::CROTCHET,
+            vec![
+                Note::new(u7::new(60), rhythm::MINIM, dynamic::F)?,
+                Note::new(u7::new(64), rhythm::CROTCHET, dynamic::MF)?,
+            ],
+        )?);
+        melody.add_note(Note::new(u7::new(62), rhythm::QUAVER, dynamic::P)?);
+        let mut piano = Part::new(Instrument::AcousticGrandPiano);
+        piano.add_phrase(melody, 0.);
+        let mut bass = Part::new(Instrument::AcousticBass);
+        bass.add_phrase(
+            Phrase::from_notes_sequence([Note::new(u7::new(36), rhythm::MINIM, dynamic::MF)])?,
+            0.5,
+        );
+        let mut score = Score::new("events", Tempo::new(120)?, None);
+        score.add_part(piano);
+        score.add_part(bass);
+
+        let events: Vec<(u8, usize, usize, u64, u64)> = score
+            .events()
+            .map(|e| {
+                (
+                    e.pitch().as_int(),
+                    e.part,
+                    e.entry,
+                    e.start_tick,
+                    e.end_tick,
+                )
+            })
+            .collect();
+        assert_eq!(
+            events,
+            vec![
+                (60, 0, 1, 480, 1440),
+                (64, 0, 1, 480, 960),
+                (62, 0, 2, 960, 1200),
+                (36, 1, 0, 240, 1200),
+            ]
+        );
+        let sorted = score.sorted_events();
+        assert_eq!(sorted[0].pitch(), u7::new(36));
+        assert_eq!((sorted[0].start, sorted[0].end), (0.5, 2.5));
+        assert_eq!(
+            (sorted[0].start_seconds, sorted[0].end_seconds),
+            (0.25, 1.25)
+        );
+        assert_eq!(sorted[1].velocity(), dynamic::F);
+        Ok(())
+    }
+}
@@ -1,3 +1,5 @@
+use crate::num::u7;
+
 /// Describes a MIDI instrument and stores valid standard MIDI instrument code
 #[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
 pub enum Instrument {
@@ -132,3 +134,69 @@ pub enum Instrument {
     Applause,
     Gunshot,
 }
+
+/// Describes a sound of the General MIDI percussion kit, played by the notes of a
+/// percussion `Part` (see `Part::new_percussion`). The value of each sound is its MIDI pitch.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum DrumSound {
+    AcousticBassDrum = 35,
+    BassDrum,
+    SideStick,
+    AcousticSnare,
+    HandClap,
+    ElectricSnare,
+    LowFloorTom,
+    ClosedHiHat,
+    HighFloorTom,
+    PedalHiHat,
+    LowTom,
+    OpenHiHat,
+    LowMidTom,
+    HighMidTom,
+    CrashCymbal1,
+    HighTom,
+    RideCymbal1,
+    ChineseCymbal,
+    RideBell,
+    Tambourine,
+    SplashCymbal,
+    Cowbell,
+    CrashCymbal2,
+    Vibraslap,
+    RideCymbal2,
+    HighBongo,
+    LowBongo,
+    MuteHighConga,
+    OpenHighConga,
+    LowConga,
+    HighTimbale,
+    LowTimbale,
+    HighAgogo,
+    LowAgogo,
+    Cabasa,
+    Maracas,
+    ShortWhistle,
+    LongWhistle,
+    ShortGuiro,
+    LongGuiro,
+    Claves,
+    HighWoodBlock,
+    LowWoodBlock,
+    MuteCuica,
+    OpenCuica,
+    MuteTriangle,
+    OpenTriangle,
+}
+
+impl DrumSound {
+    /// Returns the pitch that plays the sound on the percussion channel
+    pub fn pitch(self) -> u7 {
+        u7::new(self as u8)
+    }
+}
+
+impl From<DrumSound> for u7 {
+    fn from(sound: DrumSound) -> Self {
+        sound.pitch()
+    }
+}
@@ -1,11 +1,14 @@
 mod chord;
 mod constants;
 pub mod errors;
+mod events;
 mod instrument;
+mod measure;
 mod note;
 mod part;
 mod phrase;
 pub mod score;
+mod slice;
 
 /// The `composition` feature enables the composition module which contains various
 /// utilities that simplify music composition, for example by streamlining the creation
@@ -13,17 +16,25 @@ pub mod score;
 #[cfg(feature = "composition")]
 pub mod composition;
 
+/// The `render` feature enables the render module which draws scores as piano rolls and
+/// staff notation (SVG, or PNG with the `png` feature) and as text.
+#[cfg(feature = "render")]
+pub mod render;
+
 pub use crate::errors::Error;
 pub type Result<T> = core::result::Result<T, Error>;
 
 pub use chord::Chord;
 pub use constants::dynamic;
 pub use constants::rhythm;
-pub use instrument::Instrument;
+pub use events::{NoteEvent, NoteEvents, TICKS_PER_BEAT};
+pub use instrument::{DrumSound, Instrument};
+pub use measure::{BarSegment, Measures, TimeSignature};
 pub use note::{compute_pitch, pitch_info, Accidental, Note, NoteName};
 pub use part::Part;
 pub use phrase::{Phrase, PhraseEntry};
 pub use score::{Metadata, Mode, Score, Tempo};
+pub use slice::{Slice, Slices};
 
 pub use midly;
 pub use midly::num;
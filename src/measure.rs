@@ -0,0 +1,474 @@
+use crate::errors::ScoreError;
+use crate::{Chord, Metadata, Note, Part, Phrase, PhraseEntry, Result, Score};
+
+/// Tolerance on positions in beats, so that rounding errors do not create tiny bars or notes
+const EPSILON: f64 = 0.000_001;
+
+/// Describes a time signature, e.g. 3/4 or 6/8
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TimeSignature {
+    numerator: u8,
+    denominator: u8,
+}
+
+impl Default for TimeSignature {
+    fn default() -> Self {
+        Self {
+            numerator: 4,
+            denominator: 4,
+        }
+    }
+}
+
+impl TimeSignature {
+    /// Returns a new `TimeSignature` of `numerator` units per bar, a unit being a
+    /// `1 / denominator` of a semibreve (e.g. a quaver in 6/8)
+    ///
+    /// # Errors
+    ///
+    /// * `ScoreError::InvalidTimeSignature` if the numerator or the denominator is 0
+    pub fn new(numerator: u8, denominator: u8) -> Result<Self> {
+        if numerator == 0 || denominator == 0 {
+            return Err(ScoreError::InvalidTimeSignature(numerator, denominator).into());
+        }
+        Ok(Self {
+            numerator,
+            denominator,
+        })
+    }
+
+    /// Returns the numerator of the time signature
+    pub fn numerator(&self) -> u8 {
+        self.numerator
+    }
+
+    /// Returns the denominator of the time signature
+    pub fn denominator(&self) -> u8 {
+        self.denominator
+    }
+
+    /// Returns the length in beats of a unit of the time signature (e.g. 0.5 in 6/8)
+    pub fn unit_length(&self) -> f64 {
+        4. / self.denominator as f64
+    }
+
+    /// Returns the length in beats of a bar (e.g. 3 in 6/8)
+    pub fn bar_length(&self) -> f64 {
+        self.numerator as f64 * self.unit_length()
+    }
+}
+
+/// Maps positions in beats to bars and back, with the time signature of each bar.
+///
+/// Bars are counted from 0: the bar numbered 17 in a score is the bar 16. The time
+/// signature of the first bar applies until the first meter change, which applies until
+/// the next one, and so on.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Measures {
+    /// The time signatures and the bars at which they start, sorted by bar, starting at bar 0
+    meters: Vec<(usize, TimeSignature)>,
+}
+
+impl Default for Measures {
+    fn default() -> Self {
+        Self::new(TimeSignature::default())
+    }
+}
+
+impl Measures {
+    /// Returns new `Measures` with the same time signature for every bar
+    pub fn new(time_signature: TimeSignature) -> Self {
+        Self {
+            meters: vec![(0, time_signature)],
+        }
+    }
+
+    /// Returns new `Measures` with the time signature of the `Metadata` for every bar
+    /// (4/4 if there is no `Metadata` or if its time signature is invalid)
+    pub fn from_metadata(metadata: Option<&Metadata>) -> Self {
+        let time_signature = metadata
+            .and_then(|m| TimeSignature::new(m.time_numerator, m.time_denominator).ok())
+            .unwrap_or_default();
+        Self::new(time_signature)
+    }
+
+    /// Changes the time signature from the bar `bar` until the next meter change
+    pub fn add_meter_change(&mut self, bar: usize, time_signature: TimeSignature) {
+        match self.meters.binary_search_by_key(&bar, |m| m.0) {
+            Ok(index) => self.meters[index].1 = time_signature,
+            Err(index) => self.meters.insert(index, (bar, time_signature)),
+        }
+    }
+
+    /// Returns the meter changes as the bars at which they start with their time
+    /// signature, starting with the time signature of bar 0
+    pub fn meter_changes(&self) -> &[(usize, TimeSignature)] {
+        &self.meters
+    }
+
+    /// Returns the time signature of a bar
+    pub fn time_signature(&self, bar: usize) -> TimeSignature {
+        let index = self.meters.partition_point(|m| m.0 <= bar);
+        self.meters[index - 1].1
+    }
+
+    /// Returns the length in beats of a bar
+    pub fn bar_length(&self, bar: usize) -> f64 {
+        self.time_signature(bar).bar_length()
+    }
+
+    /// Returns the beat at which a bar starts
+    pub fn bar_start(&self, bar: usize) -> f64 {
+        let mut start = 0.;
+        for (index, (first, time_signature)) in self.meters.iter().enumerate() {
+            let next = self.meters.get(index + 1).map_or(usize::MAX, |m| m.0);
+            if bar < next {
+                return start + (bar - first) as f64 * time_signature.bar_length();
+            }
+            start += (next - first) as f64 * time_signature.bar_length();
+        }
+        start
+    }
+
+    /// Returns the beat at `offset` beats after the start of a bar
+    pub fn beat(&self, bar: usize, offset: f64) -> f64 {
+        self.bar_start(bar) + offset
+    }
+
+    /// Returns the bar containing a beat and the position of the beat in the bar, in
+    /// beats from the start of the bar. Negative beats are in bar 0.
+    pub fn position(&self, beat: f64) -> (usize, f64) {
+        let beat = beat.max(0.);
+        let mut start = 0.;
+        for (index, (first, time_signature)) in self.meters.iter().enumerate() {
+            let length = time_signature.bar_length();
+            let bars = ((beat - start) / length + EPSILON).floor().max(0.) as usize;
+            let next = self.meters.get(index + 1).map(|m| m.0);
+            match next {
+                Some(next) if first + bars >= next => {
+                    start += (next - first) as f64 * length;
+                }
+                _ => {
+                    let offset = (beat - start - bars as f64 * length).max(0.);
+                    return (first + bars, offset);
+                }
+            }
+        }
+        (0, beat)
+    }
+
+    /// Returns the number of bars needed to contain `duration` beats
+    pub fn bar_count(&self, duration: f64) -> usize {
+        match self.position(duration) {
+            (bar, offset) if offset > EPSILON => bar + 1,
+            (bar, _) => bar,
+        }
+    }
+
+    /// Splits a `Phrase` starting at `start_beat` into one segment per bar. Notes and
+    /// chords crossing a barline are split into notes tied over the barline, and rests
+    /// are split in several rests.
+    ///
+    /// The notes of a `Chord` that last longer than the `Chord` are only split with the
+    /// `Chord` itself. Use `join_segments` to merge the tied notes back.
+    ///
+    /// # Errors
+    ///
+    /// * `NoteError::InvalidRhythm` if a part of a note is too short to be a `Note`
+    pub fn split_phrase(&self, phrase: &Phrase, start_beat: f64) -> Result<Vec<BarSegment>> {
+        let mut segments: Vec<BarSegment> = Vec::new();
+        let mut beat = start_beat;
+        for entry in phrase.entries() {
+            let mut done = 0.;
+            while entry.rhythm() - done > EPSILON {
+                let (bar, offset) = self.position(beat);
+                let length = (entry.rhythm() - done).min(self.bar_length(bar) - offset);
+                let end = done + length;
+                if !matches!(segments.last(), Some(s) if s.bar == bar) {
+                    let mut segment = Phrase::new();
+                    segment.set_name(phrase.name());
+                    segments.push(BarSegment {
+                        bar,
+                        offset,
+                        phrase: segment,
+                        tied_to_next: Vec::new(),
+                    });
+                }
+                let index = segments.len() - 1;
+                let segment = &mut segments[index];
+                let last_piece = entry.rhythm() - end <= EPSILON;
+                match entry {
+                    PhraseEntry::Rest(_) => {
+                        segment.phrase.add_rest(length);
+                        segment.tied_to_next.push(false);
+                    }
+                    PhraseEntry::Note(n) => {
+                        segment
+                            .phrase
+                            .add_note(Note::new(n.pitch(), length, n.dynamic())?);
+                        segment.tied_to_next.push(!last_piece);
+                    }
+                    PhraseEntry::Chord(c) => {
+                        // the notes still sounding, until the end of the piece or their end
+                        let notes = c
+                            .notes()
+                            .iter()
+                            .filter(|n| n.rhythm() - done > EPSILON)
+                            .map(|n| {
+                                let rhythm = if last_piece {
+                                    n.rhythm()
+                                } else {
+                                    n.rhythm().min(end)
+                                };
+                                Note::new(n.pitch(), rhythm - done, n.dynamic())
+                            })
+                            .collect::<Result<Vec<Note>>>()?;
+                        let longest = notes.iter().map(Note::rhythm).fold(0., f64::max);
+                        if notes.is_empty() {
+                            segment.phrase.add_rest(length);
+                            segment.tied_to_next.push(false);
+                        } else if longest < length - EPSILON {
+                            segment.phrase.add_chord(Chord::new(longest, notes)?);
+                            segment.phrase.add_rest(length - longest);
+                            segment.tied_to_next.extend([false, false]);
+                        } else {
+                            segment.phrase.add_chord(Chord::new(length, notes)?);
+                            segment.tied_to_next.push(
+                                c.notes().iter().any(|n| n.rhythm() - end > EPSILON) && !last_piece,
+                            );
+                        }
+                    }
+                }
+                done = end;
+                beat += length;
+            }
+        }
+        Ok(segments)
+    }
+
+    /// Joins consecutive segments (e.g. from `split_phrase`) into a `Phrase` starting at
+    /// the position of the first segment. The entries tied over a barline are merged back
+    /// into single notes and chords, and the gaps between segments become rests.
+    ///
+    /// # Errors
+    ///
+    /// * `NoteError::InvalidRhythm` or `ChordError::RhythmTooLong` if tied entries of
+    ///   segments that were not made by `split_phrase` cannot be merged
+    pub fn join_segments(&self, segments: &[BarSegment]) -> Result<Phrase> {
+        let mut phrase = Phrase::new();
+        let Some(first) = segments.first() else {
+            return Ok(phrase);
+        };
+        phrase.set_name(first.phrase.name());
+        let start = self.beat(first.bar, first.offset);
+        // the last entry added to the phrase, if it is tied to the next one
+        let mut pending: Option<PhraseEntry> = None;
+        for segment in segments {
+            let pending_duration = pending.as_ref().map_or(0., PhraseEntry::rhythm);
+            let end = start + phrase.duration() + pending_duration;
+            let gap = self.beat(segment.bar, segment.offset) - end;
+            if gap > EPSILON {
+                if let Some(entry) = pending.take() {
+                    add_entry(&mut phrase, entry);
+                }
+                phrase.add_rest(gap);
+            }
+            let ties = segment
+                .tied_to_next
+                .iter()
+                .copied()
+                .chain(std::iter::repeat(false));
+            for (entry, tied) in segment.phrase.entries().iter().zip(ties) {
+                let entry = match pending.take() {
+                    Some(previous) => match tie(&previous, entry)? {
+                        Some(merged) => merged,
+                        None => {
+                            add_entry(&mut phrase, previous);
+                            entry.clone()
+                        }
+                    },
+                    None => entry.clone(),
+                };
+                if tied {
+                    pending = Some(entry);
+                } else {
+                    add_entry(&mut phrase, entry);
+                }
+            }
+        }
+        if let Some(entry) = pending {
+            add_entry(&mut phrase, entry);
+        }
+        Ok(phrase)
+    }
+}
+
+/// Returns the entry made of `previous` tied to `next`: a `Note` tied to a `Note` of the
+/// same pitch, or a `Chord` tied to a `Chord` whose notes continue notes lasting until
+/// the end of `previous`. Returns `None` if the entries cannot be tied.
+fn tie(previous: &PhraseEntry, next: &PhraseEntry) -> Result<Option<PhraseEntry>> {
+    match (previous, next) {
+        (PhraseEntry::Note(p), PhraseEntry::Note(n)) if p.pitch() == n.pitch() => Ok(Some(
+            PhraseEntry::Note(Note::new(p.pitch(), p.rhythm() + n.rhythm(), p.dynamic())?),
+        )),
+        (PhraseEntry::Chord(p), PhraseEntry::Chord(n)) => {
+            let held = |note: &Note| {
+                p.notes()
+                    .iter()
+                    .any(|h| h.pitch() == note.pitch() && h.rhythm() >= p.rhythm() - EPSILON)
+            };
+            if !n.notes().iter().all(held) {
+                return Ok(None);
+            }
+            let notes = p
+                .notes()
+                .iter()
+                .map(
+                    |h| match n.notes().iter().find(|c| c.pitch() == h.pitch()) {
+                        Some(c) if h.rhythm() >= p.rhythm() - EPSILON => {
+                            Note::new(h.pitch(), p.rhythm() + c.rhythm(), h.dynamic())
+                        }
+                        _ => Ok(h.clone()),
+                    },
+                )
+                .collect::<Result<Vec<Note>>>()?;
+            Ok(Some(PhraseEntry::Chord(Chord::new(
+                p.rhythm() + n.rhythm(),
+                notes,
+            )?)))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Adds an entry at the end of a `Phrase`
+fn add_entry(phrase: &mut Phrase, entry: PhraseEntry) {
+    match entry {
+        PhraseEntry::Note(n) => phrase.add_note(n),
+        PhraseEntry::Chord(c) => phrase.add_chord(c),
+        PhraseEntry::Rest(r) => phrase.add_rest(r),
+    }
+}
+
+/// Describes the part of a `Phrase` that is in a bar (see `Measures::split_phrase`)
+#[derive(Debug, Clone, PartialEq)]
+pub struct BarSegment {
+    /// The bar of the segment, counted from 0
+    pub bar: usize,
+    /// The position in the bar at which the segment starts, in beats from the start of
+    /// the bar
+    pub offset: f64,
+    /// The entries of the `Phrase` in the bar
+    pub phrase: Phrase,
+    /// For each entry of `phrase`, true if it is tied over the barline to the first entry
+    /// of the next segment
+    pub tied_to_next: Vec<bool>,
+}
+
+impl Part {
+    /// Inserts a `Phrase` in the `Part`, starting `offset` beats after the start of the
+    /// bar `bar` (counted from 0) of `measures`. This allows to build a `Part` bar by bar,
+    /// for example from the segments of `Measures::split_phrase`.
+    pub fn add_phrase_at_bar(
+        &mut self,
+        phrase: Phrase,
+        measures: &Measures,
+        bar: usize,
+        offset: f64,
+    ) {
+        self.add_phrase(phrase, measures.beat(bar, offset))
+    }
+}
+
+impl Score {
+    /// Returns the `Measures` of the `Score`, with the time signature of its `Metadata`
+    /// (4/4 if it has none)
+    pub fn measures(&self) -> Measures {
+        Measures::from_metadata(self.metadata())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::num::u7;
+    use crate::*;
+
+    #[test]
+    fn measures() -> Result<()> {
+        // 2 bars of 4/4, then 6/8 from bar 2 and 2/4 from bar 4
+        let mut measures = Measures::default();
+        measures.add_meter_change(4, TimeSignature::new(2, 4)?);
+        measures.add_meter_change(2, TimeSignature::new(6, 8)?);
+        assert_eq!(measures.bar_start(2), 8.);
+        assert_eq!(measures.bar_start(4), 14.);
+        assert_eq!(measures.bar_start(6), 18.);
+        assert_eq!(measures.position(9.5), (2, 1.5));
+        assert_eq!(measures.position(14.), (4, 0.));
+        assert_eq!(measures.position(19.), (6, 1.));
+        assert_eq!(measures.beat(3, 2.5), 13.5);
+        assert_eq!(measures.time_signature(3).denominator(), 8);
+        assert_eq!(measures.bar_count(14.5), 5);
+        assert!(TimeSignature::new(3, 0).is_err());
+
+        // a minim crossing the barline from 4/4 to 6/8, then a chord and a rest
+        let mut phrase = Phrase::new();
+        phrase.add_note(Note::new(u7::new(60), rhythm::MINIM, dynamic::MF)?);
+        phrase.add_chord(Chord::from_pitches(
+            rhythm::DOTTED_MINIM,
+            dynamic::MF,
+            &[64, 67].map(u7::new),
+        )?);
+        phrase.add_rest(rhythm::CROTCHET);
+        let segments = measures.split_phrase(&phrase, 7.)?;
+        let bars: Vec<(usize, f64, &[bool])> = segments
+            .iter()
+            .map(|s| (s.bar, s.offset, s.tied_to_next.as_slice()))
+            .collect();
+        assert_eq!(
+            bars,
+            vec![
+                (1, 3., &[true][..]),
+                (2, 0., &[false, true][..]),
+                (3, 0., &[false, false][..])
+            ]
+        );
+        assert_eq!(
+            segments[1].phrase.entries(),
+            &[
+                PhraseEntry::Note(Note::new(u7::new(60), rhythm::CROTCHET, dynamic::MF)?),
+                PhraseEntry::Chord(Chord::from_pitches(
+                    rhythm::MINIM,
+                    dynamic::MF,
+                    &[64, 67].map(u7::new),
+                )?),
+            ]
+        );
+        assert_eq!(segments[2].phrase.duration(), 2.);
+
+        // rebuilt from the joined segments, the part plays the same notes
+        let joined = measures.join_segments(&segments)?;
+        let mut rebuilt = Part::new(Instrument::Violin);
+        rebuilt.add_phrase_at_bar(joined, &measures, segments[0].bar, segments[0].offset);
+        let mut original = Part::new(Instrument::Violin);
+        original.add_phrase(phrase, 7.);
+        let mut scores = Vec::new();
+        for part in [rebuilt, original] {
+            let mut score = Score::new("", Tempo::new(60)?, None);
+            score.add_part(part);
+            scores.push(score);
+        }
+        let events = |score: &Score| {
+            score
+                .sorted_events()
+                .iter()
+                .map(|e| (e.start_tick, e.end_tick, e.pitch(), e.velocity()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(events(&scores[0]), events(&scores[1]));
+        // a missing segment becomes a rest
+        let gapped = measures.join_segments(&[segments[0].clone(), segments[2].clone()])?;
+        assert_eq!(gapped.entries().len(), 4);
+        assert_eq!(gapped.duration(), 6.);
+        Ok(())
+    }
+}
@@ -36,6 +36,19 @@ impl NoteName {
     pub const G: NoteName = NoteName::Sol;
     pub const A: NoteName = NoteName::La;
     pub const B: NoteName = NoteName::Si;
+
+    /// Returns the letter of the note name (between `A` and `G`)
+    pub fn letter(&self) -> char {
+        match self {
+            NoteName::Do => 'C',
+            NoteName::Re => 'D',
+            NoteName::Mi => 'E',
+            NoteName::Fa => 'F',
+            NoteName::Sol => 'G',
+            NoteName::La => 'A',
+            NoteName::Si => 'B',
+        }
+    }
 }
 
 /// Represents a note accidental
@@ -98,6 +111,15 @@ impl Note {
         self.dynamic
     }
 
+    /// Returns a copy of the `Note` with a different pitch, keeping
+    /// the same rhythm and dynamic
+    pub fn with_pitch(&self, pitch: u7) -> Note {
+        Note {
+            pitch,
+            ..self.clone()
+        }
+    }
+
     /// Returns the note name, accidental, and octave of the `Note`'s pitch
     ///
     /// # Arguments
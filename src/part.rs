@@ -9,6 +9,8 @@ pub struct Part {
     phrases: Vec<(f64, Phrase)>,
     /// The instrument playing the `Part`
     instrument: Instrument,
+    /// True if the `Part` is played by the General MIDI percussion kit
+    percussion: bool,
     /// The length in beats of the `Part`
     duration: f64,
     /// The end time in beat of the last added `Phrase`
@@ -28,12 +30,23 @@ impl Part {
         Part {
             phrases: Vec::new(),
             instrument,
+            percussion: false,
             duration: 0.,
             previous_phrase_end: 0.,
             name: String::default(),
         }
     }
 
+    /// Returns a new empty `Part` played by the General MIDI percussion kit. It is
+    /// exported on the MIDI percussion channel (channel 10) and the pitch of each note
+    /// selects a sound of the kit (see `DrumSound`).
+    pub fn new_percussion() -> Part {
+        Part {
+            percussion: true,
+            ..Part::new(Instrument::None)
+        }
+    }
+
     /// Sets a name for the `Part`. The name does not have to be unique.
     pub fn set_name<S: ToString>(&mut self, name: S) {
         self.name = name.to_string();
@@ -78,6 +91,11 @@ impl Part {
         self.instrument
     }
 
+    /// Returns true if the `Part` is played by the percussion kit (see `new_percussion`)
+    pub fn is_percussion(&self) -> bool {
+        self.percussion
+    }
+
     // Returns the total duration (in beats, i.e. the "rhythm" unit) of the `Part`.
     // This corresponds to the end of the `Phrase` that finishes the latest.
     pub fn duration(&self) -> f64 {
@@ -0,0 +1,8 @@
+mod piano_roll;
+mod staff;
+mod svg;
+mod text;
+
+pub use piano_roll::*;
+pub use staff::*;
+pub use text::*;
@@ -0,0 +1,263 @@
+use crate::num::u7;
+use crate::render::svg::{escape, SvgWriter};
+use crate::render::text::pitch_name;
+use crate::Score;
+
+/// Width of the pitch axis in pixels
+const PITCH_AXIS_WIDTH: f64 = 44.;
+/// Height of the time axis in pixels
+const TIME_AXIS_HEIGHT: f64 = 20.;
+
+/// Describes the labels of the time axis of a `PianoRoll`
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TimeAxis {
+    /// One label per beat, starting at beat 0
+    #[default]
+    Beats,
+    /// One label per bar, starting at bar 1, using the time signature of the `Metadata`
+    /// of the `Score` (4/4 if it has none)
+    Bars,
+}
+
+/// Draws a `Score` as a piano roll: one row per pitch and one rectangle per note, in the
+/// colour of its `Part`, more opaque for louder notes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PianoRoll {
+    /// The width of a beat in pixels
+    beat_width: f64,
+    /// The height of a pitch row in pixels
+    row_height: f64,
+    /// The labels of the time axis
+    time_axis: TimeAxis,
+    /// The colour of each part (repeated cyclically), as SVG colours
+    colours: Vec<String>,
+    /// True if the start and end of each `Phrase` are drawn
+    phrase_boundaries: bool,
+}
+
+impl Default for PianoRoll {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PianoRoll {
+    /// Returns a new `PianoRoll` with 40 pixels per beat, 10 pixels per pitch, a time
+    /// axis in beats and phrase boundaries
+    pub fn new() -> Self {
+        Self {
+            beat_width: 40.,
+            row_height: 10.,
+            time_axis: TimeAxis::Beats,
+            colours: [
+                "#1f77b4", "#d62728", "#2ca02c", "#ff7f0e", "#9467bd", "#8c564b", "#e377c2",
+                "#17becf",
+            ]
+            .map(String::from)
+            .to_vec(),
+            phrase_boundaries: true,
+        }
+    }
+
+    /// Sets the width of a beat in pixels
+    pub fn set_beat_width(&mut self, width: f64) {
+        self.beat_width = width.max(1.);
+    }
+
+    /// Sets the height of a pitch row in pixels
+    pub fn set_row_height(&mut self, height: f64) {
+        self.row_height = height.max(1.);
+    }
+
+    /// Sets the labels of the time axis
+    pub fn set_time_axis(&mut self, time_axis: TimeAxis) {
+        self.time_axis = time_axis;
+    }
+
+    /// Sets the colour of each part, as SVG colours (e.g. `"#1f77b4"` or `"teal"`).
+    /// If there are fewer colours than parts, they are repeated cyclically.
+    pub fn set_colours(&mut self, colours: Vec<String>) {
+        self.colours = colours;
+    }
+
+    /// Sets if the start and end of each `Phrase` are drawn as dashed lines
+    pub fn set_phrase_boundaries(&mut self, phrase_boundaries: bool) {
+        self.phrase_boundaries = phrase_boundaries;
+    }
+
+    /// Returns the piano roll of the `Score` as an SVG document
+    pub fn to_svg(&self, score: &Score) -> String {
+        let events = score.sorted_events();
+        let (lowest, highest) = events
+            .iter()
+            .map(|e| e.pitch().as_int())
+            .fold(None, |range: Option<(u8, u8)>, p| match range {
+                Some((low, high)) => Some((low.min(p), high.max(p))),
+                None => Some((p, p)),
+            })
+            .unwrap_or((60, 72));
+        // one row of margin around the notes
+        let (lowest, highest) = (lowest.saturating_sub(1), (highest + 1).min(127));
+        let rows = (highest - lowest + 1) as f64;
+        let beats = score.duration().ceil().max(1.);
+        let width = PITCH_AXIS_WIDTH + beats * self.beat_width;
+        let height = TIME_AXIS_HEIGHT + rows * self.row_height;
+        let x = |beat: f64| PITCH_AXIS_WIDTH + beat * self.beat_width;
+        let y = |pitch: u8| TIME_AXIS_HEIGHT + (highest - pitch) as f64 * self.row_height;
+
+        let mut svg = SvgWriter::new(width, height);
+        // pitch axis
+        let font_size = (self.row_height * 0.8).min(12.);
+        for pitch in lowest..=highest {
+            if matches!(pitch % 12, 1 | 3 | 6 | 8 | 10) {
+                svg.rect(
+                    PITCH_AXIS_WIDTH,
+                    y(pitch),
+                    width - PITCH_AXIS_WIDTH,
+                    self.row_height,
+                    "fill=\"#f0f0f0\"",
+                );
+            }
+            svg.text(
+                PITCH_AXIS_WIDTH - 4.,
+                y(pitch) + self.row_height * 0.8,
+                &pitch_name(u7::new(pitch)),
+                &format!("font-size=\"{font_size:.1}\" text-anchor=\"end\""),
+            );
+        }
+        // time axis
+        let bar_length = match self.time_axis {
+            TimeAxis::Bars => score.measures().bar_length(0),
+            TimeAxis::Beats => 1.,
+        };
+        for beat in 0..=beats as usize {
+            svg.line(
+                x(beat as f64),
+                TIME_AXIS_HEIGHT,
+                x(beat as f64),
+                height,
+                "stroke=\"#dddddd\" stroke-width=\"1\"",
+            );
+        }
+        let mut bar = 0;
+        while bar as f64 * bar_length < beats {
+            let start = bar as f64 * bar_length;
+            svg.line(
+                x(start),
+                TIME_AXIS_HEIGHT,
+                x(start),
+                height,
+                "stroke=\"#999999\" stroke-width=\"1\"",
+            );
+            let label = match self.time_axis {
+                TimeAxis::Beats => bar.to_string(),
+                TimeAxis::Bars => (bar + 1).to_string(),
+            };
+            svg.text(
+                x(start) + 2.,
+                TIME_AXIS_HEIGHT - 6.,
+                &label,
+                "font-size=\"11\"",
+            );
+            bar += 1;
+        }
+        // notes
+        for event in events.iter() {
+            let colour = self.colour(event.part);
+            let opacity = 0.25 + 0.75 * event.velocity().as_int() as f64 / 127.;
+            let title = match score.parts()[event.part].name() {
+                "" => pitch_name(event.pitch()),
+                name => format!("{name}: {}", pitch_name(event.pitch())),
+            };
+            svg.raw(&format!(
+                "<rect x=\"{:.2}\" y=\"{:.2}\" width=\"{:.2}\" height=\"{:.2}\" fill=\"{colour}\" \
+                 fill-opacity=\"{opacity:.2}\" stroke=\"{colour}\" stroke-width=\"1\">\
+                 <title>{} at beat {} (velocity {})</title></rect>",
+                x(event.start),
+                y(event.pitch().as_int()),
+                (event.end - event.start) * self.beat_width,
+                self.row_height,
+                escape(&title),
+                event.start,
+                event.velocity(),
+            ));
+        }
+        // phrase boundaries
+        if self.phrase_boundaries {
+            for (index, part) in score.parts().iter().enumerate() {
+                let attributes = format!(
+                    "stroke=\"{}\" stroke-width=\"1.5\" stroke-dasharray=\"4 3\"",
+                    self.colour(index)
+                );
+                for (start, phrase) in part.phrases() {
+                    for beat in [*start, start + phrase.duration()] {
+                        svg.line(x(beat), TIME_AXIS_HEIGHT, x(beat), height, &attributes);
+                    }
+                }
+            }
+        }
+        svg.finish()
+    }
+
+    /// Returns the piano roll of the `Score` as a PNG image (see `to_svg`)
+    ///
+    /// # Errors
+    ///
+    /// * `RenderError::InvalidImageSize` if the image is too large to be rasterized
+    /// * `RenderError::PngEncoding` if the image cannot be encoded
+    #[cfg(feature = "png")]
+    pub fn to_png(&self, score: &Score) -> crate::Result<Vec<u8>> {
+        crate::render::svg::svg_to_png(&self.to_svg(score))
+    }
+
+    /// Returns the colour of a part
+    fn colour(&self, part: usize) -> &str {
+        self.colours
+            .get(part % self.colours.len().max(1))
+            .map_or("black", String::as_str)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PianoRoll, TimeAxis};
+    use crate::num::u7;
+    use crate::*;
+
+    #[test]
+    fn piano_roll() -> Result<()> {
+        let mut melody = Phrase::from_notes_sequence(Note::new_sequence(
+            rhythm::CROTCHET,
+            dynamic::MF,
+            [60, 62, 64, 65].map(u7::new),
+        ))?;
+        melody.set_name("melody");
+        let mut part = Part::new(Instrument::AcousticGrandPiano);
+        part.set_name("Piano & voice");
+        part.add_phrase(melody.clone(), 0.);
+        part.add_phrase(melody, 4.);
+        let metadata = Metadata {
+            time_numerator: 3,
+            time_denominator: 4,
+            ..Default::default()
+        };
+        let mut score = Score::new("roll", Tempo::new(120)?, Some(metadata));
+        score.add_part(part);
+
+        let mut roll = PianoRoll::new();
+        roll.set_time_axis(TimeAxis::Bars);
+        let svg = roll.to_svg(&score);
+        assert!(svg.starts_with("<svg"));
+        // 8 beats of 40 pixels after the pitch axis, 8 pitch rows of 10 pixels
+        assert!(svg.contains("width=\"364\" height=\"100\""));
+        assert_eq!(svg.matches("<title>").count(), 8);
+        assert!(svg.contains("Piano &amp; voice: E5 at beat 2 (velocity 70)"));
+        assert!(svg.contains(">F5</text>") && svg.contains(">C#5</text>"));
+        // bars 1 to 3 in 3/4
+        assert!(svg.contains(">3</text>") && !svg.contains(">4</text>"));
+        assert_eq!(svg.matches("stroke-dasharray").count(), 4);
+        #[cfg(feature = "png")]
+        assert!(roll.to_png(&score)?.starts_with(b"\x89PNG"));
+        Ok(())
+    }
+}
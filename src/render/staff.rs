@@ -0,0 +1,1219 @@
+use std::collections::{BTreeMap, HashMap};
+
+use crate::events::to_ticks;
+use crate::num::u7;
+use crate::render::svg::SvgWriter;
+use crate::{Accidental, Mode, Part, Score, Slice, TICKS_PER_BEAT};
+
+/// Number of ticks of a demisemiquaver, the shortest value written
+const SHORTEST_TICKS: u64 = TICKS_PER_BEAT / 8;
+/// Pitch classes of the natural notes, by letter (0 for C to 6 for B)
+const NATURAL_CLASSES: [i32; 7] = [0, 2, 4, 5, 7, 9, 11];
+/// Letters (0 for C to 6 for B) of the sharps of the key signatures, in order, which is
+/// also the order of the letters on the circle of fifths from F
+const SHARP_LETTERS: [i32; 7] = [3, 0, 4, 1, 5, 2, 6];
+/// Letters (0 for C to 6 for B) of the flats of the key signatures, in order
+const FLAT_LETTERS: [i32; 7] = [6, 2, 5, 1, 4, 0, 3];
+/// Staff steps of the sharps of the key signatures on a treble staff
+const SHARP_STEPS: [i32; 7] = [45, 42, 46, 43, 40, 44, 41];
+/// Staff steps of the flats of the key signatures on a treble staff
+const FLAT_STEPS: [i32; 7] = [41, 44, 40, 43, 39, 42, 38];
+/// Staff step of middle C (pitch 60)
+const MIDDLE_C_STEP: i32 = 35;
+
+/// Describes the clef of a staff
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Clef {
+    Treble,
+    Bass,
+}
+
+impl Clef {
+    /// Returns the step of the bottom line of the staff. Steps count the note names (7
+    /// per octave) with the octaves of `pitch_info`.
+    fn bottom_step(self) -> i32 {
+        match self {
+            Clef::Treble => 37,
+            Clef::Bass => 25,
+        }
+    }
+}
+
+/// Describes a written rhythm value
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Value {
+    /// The duration in ticks
+    ticks: u64,
+    /// The shape of the value: 0 for a breve, 1 for a semibreve, ..., 6 for a
+    /// demisemiquaver
+    shape: u8,
+    /// True if the value is dotted
+    dotted: bool,
+    /// True if the value is part of a triplet
+    triplet: bool,
+}
+
+impl Value {
+    /// Returns the values that can be written, longest first
+    fn all() -> Vec<Value> {
+        let mut values = Vec::new();
+        for shape in 0..=6 {
+            let ticks = (8 * TICKS_PER_BEAT) >> shape;
+            values.push(Value::new(ticks, shape, false, false));
+            if (1..=5).contains(&shape) {
+                values.push(Value::new(ticks * 3 / 2, shape, true, false));
+                values.push(Value::new(ticks * 2 / 3, shape, false, true));
+            }
+        }
+        values.sort_by_key(|v| std::cmp::Reverse(v.ticks));
+        values
+    }
+
+    fn new(ticks: u64, shape: u8, dotted: bool, triplet: bool) -> Self {
+        Self {
+            ticks,
+            shape,
+            dotted,
+            triplet,
+        }
+    }
+
+    /// Splits a duration in ticks into written values, longest first. The part of the
+    /// duration that cannot be written (e.g. the end of a quintuplet) is written as the
+    /// nearest value, so that no element is silently shortened.
+    // `u64::is_multiple_of` needs Rust 1.87
+    #[allow(clippy::manual_is_multiple_of)]
+    fn split(ticks: u64) -> Vec<Value> {
+        let values = Self::all();
+        let mut rest = ticks;
+        let mut split = Vec::new();
+        while rest > 0 {
+            let fits = |v: &&Value| v.ticks <= rest;
+            let value = values.iter().find(|v| v.ticks == rest).or_else(|| {
+                if rest % SHORTEST_TICKS == 0 {
+                    values.iter().filter(|v| !v.triplet).find(fits)
+                } else {
+                    values
+                        .iter()
+                        .filter(|v| v.triplet)
+                        .filter(fits)
+                        .find(|v| (rest - v.ticks) % SHORTEST_TICKS == 0)
+                        .or_else(|| values.iter().find(fits))
+                }
+            });
+            let Some(value) = value else {
+                split.extend(values.iter().min_by_key(|v| v.ticks.abs_diff(rest)));
+                break;
+            };
+            split.push(*value);
+            rest -= value.ticks;
+        }
+        split
+    }
+
+    /// Returns the number of flags (or beams) of the value
+    fn flags(&self) -> usize {
+        self.shape.saturating_sub(3) as usize
+    }
+}
+
+/// Describes a notehead
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Head {
+    /// The staff step of the note (see `Clef::bottom_step`)
+    step: i32,
+    /// The accidental written before the head, if any
+    accidental: Option<Accidental>,
+    /// True if the note is tied to the same note in the next element
+    tie: bool,
+    /// True if the note is tied from the same note in the previous element
+    tied: bool,
+}
+
+/// Describes a note, a chord or a rest written on a staff
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Element {
+    /// The tick at which the element starts
+    start: u64,
+    /// The written rhythm value
+    value: Value,
+    /// The heads from the lowest, empty for a rest
+    heads: Vec<Head>,
+    /// True for a rest lasting a whole bar
+    bar_rest: bool,
+}
+
+/// Returns the staff step and the accidental of a pitch in the key signature `key`, in
+/// a minor key if `minor`. The notes of the key are spelled as in its signature and the
+/// other notes take the spelling closest to the key on the circle of fifths (e.g. C#,
+/// Eb, F#, Ab and Bb in C major, but G# in A minor).
+fn spell(pitch: u7, key: i8, minor: bool) -> (i32, Accidental) {
+    let pitch = pitch.as_int() as i32;
+    // positions on the line of fifths count from C (F is -1, G is 1, F# is 6, Bb is -2)
+    // and the notes of a major key are between the key position - 1 and + 5
+    let centre = key as i32 + if minor { 3 } else { 2 };
+    let fifths = 7 * (pitch % 12) % 12;
+    let position = [fifths - 12, fifths, fifths + 12]
+        .into_iter()
+        .filter(|p| (-8..=12).contains(p))
+        .min_by_key(|p| ((p - centre).abs(), *p))
+        .unwrap_or(fifths);
+    let letter = SHARP_LETTERS[(position + 1).rem_euclid(7) as usize];
+    let alteration = (position + 1).div_euclid(7);
+    let accidental = match alteration {
+        -1 => Accidental::Flat,
+        0 => Accidental::Natural,
+        _ => Accidental::Sharp,
+    };
+    let octave = (pitch - alteration - NATURAL_CLASSES[letter as usize]).div_euclid(12);
+    (octave * 7 + letter, accidental)
+}
+
+/// Returns the accidental of a staff step in a key signature
+fn key_accidental(step: i32, key: i8) -> Accidental {
+    let letter = step.rem_euclid(7);
+    let count = key.unsigned_abs() as usize;
+    if key > 0 && SHARP_LETTERS[..count].contains(&letter) {
+        Accidental::Sharp
+    } else if key < 0 && FLAT_LETTERS[..count].contains(&letter) {
+        Accidental::Flat
+    } else {
+        Accidental::Natural
+    }
+}
+
+/// Returns the elements of a `Part` in bars of `bar_ticks` ticks, completed with rests
+/// up to the tick `end`, in the key signature `key` (in a minor key if `minor`)
+fn elements(part: &Part, bar_ticks: u64, end: u64, key: i8, minor: bool) -> Vec<Element> {
+    let slices: Vec<Slice> = part.slices().collect();
+    let mut segments: Vec<(u64, u64, Option<&Slice>)> = slices
+        .iter()
+        .map(|s| (to_ticks(s.start), to_ticks(s.end), Some(s)))
+        .collect();
+    let last = segments.last().map_or(0, |s| s.1);
+    if last < end {
+        segments.push((last, end, None));
+    }
+
+    let mut elements = Vec::new();
+    // the accidentals of the steps altered in the current bar
+    let mut accidentals: HashMap<i32, Accidental> = HashMap::new();
+    for (index, &(start, end, slice)) in segments.iter().enumerate() {
+        let mut notes = slice.map_or(Vec::new(), |s| s.notes.clone());
+        notes.dedup_by_key(|n| n.pitch());
+        let next = segments.get(index + 1).and_then(|s| s.2);
+        let mut tick = start;
+        while tick < end {
+            let bar_start = tick / bar_ticks * bar_ticks;
+            if tick == bar_start {
+                accidentals.clear();
+            }
+            let piece_end = end.min(bar_start + bar_ticks);
+            if notes.is_empty() && tick == bar_start && piece_end == bar_start + bar_ticks {
+                elements.push(Element {
+                    start: tick,
+                    value: Value::new(bar_ticks, 1, false, false),
+                    heads: Vec::new(),
+                    bar_rest: true,
+                });
+                tick = piece_end;
+                continue;
+            }
+            let values = Value::split(piece_end - tick);
+            let count = values.len();
+            for (v, value) in values.into_iter().enumerate() {
+                let last = v + 1 == count && piece_end == end;
+                let heads = notes
+                    .iter()
+                    .map(|n| {
+                        let (step, accidental) = spell(n.pitch(), key, minor);
+                        let tied = tick > start || n.start_tick < start;
+                        let current = *accidentals.get(&step).unwrap_or(&key_accidental(step, key));
+                        let accidental = (!tied && accidental != current).then(|| {
+                            accidentals.insert(step, accidental);
+                            accidental
+                        });
+                        Head {
+                            step,
+                            accidental,
+                            tie: !last || next.is_some_and(|s| s.notes.contains(n)),
+                            tied,
+                        }
+                    })
+                    .collect();
+                elements.push(Element {
+                    start: tick,
+                    value,
+                    heads,
+                    bar_rest: false,
+                });
+                tick += value.ticks;
+            }
+            tick = piece_end;
+        }
+    }
+    elements
+}
+
+/// Draws the parts of a `Score` as staff notation: one staff per `Part` with its notes,
+/// chords, rests, accidentals, beams and ties, in the key and time signatures of the
+/// `Metadata` of the `Score` (C major in 4/4 if it has none).
+///
+/// The engraving is minimal: each staff has a single voice, so overlapping phrases and
+/// chords of notes of different lengths are written as chords with tied notes, durations
+/// are split into tied written values (triplets included), and the clef of each staff is
+/// treble or bass depending on the average pitch of its part. The elements of the SVG
+/// have a class (`staff`, `notehead`, `stem`, `beam`, `tie`, `rest`, ...) for styling.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StaffNotation {
+    /// The distance between two lines of a staff in pixels
+    staff_space: f64,
+    /// The number of bars per line of staves
+    bars_per_line: usize,
+}
+
+impl Default for StaffNotation {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StaffNotation {
+    /// Returns a new `StaffNotation` with 8 pixels between staff lines and 4 bars per line
+    pub fn new() -> Self {
+        Self {
+            staff_space: 8.,
+            bars_per_line: 4,
+        }
+    }
+
+    /// Sets the distance between two lines of a staff in pixels
+    pub fn set_staff_space(&mut self, space: f64) {
+        self.staff_space = space.max(2.);
+    }
+
+    /// Sets the number of bars per line of staves
+    pub fn set_bars_per_line(&mut self, bars: usize) {
+        self.bars_per_line = bars.max(1);
+    }
+
+    /// Returns the staff notation of the `Score` as an SVG document
+    pub fn to_svg(&self, score: &Score) -> String {
+        let sp = self.staff_space;
+        let time_signature = score.measures().time_signature(0);
+        let (numerator, denominator) = (time_signature.numerator(), time_signature.denominator());
+        let key = score.metadata().map_or(0, |m| m.key_signature.clamp(-7, 7));
+        let minor = score.metadata().is_some_and(|m| m.mode == Mode::Minor);
+        let bar_ticks = to_ticks(time_signature.bar_length()).max(1);
+        // quavers are beamed by beat, or by dotted crotchet in compound time
+        let beat_ticks = if denominator == 8 && numerator % 3 == 0 {
+            3 * TICKS_PER_BEAT / 2
+        } else {
+            (4 * TICKS_PER_BEAT / denominator as u64).max(1)
+        };
+        let bars = to_ticks(score.duration()).div_ceil(bar_ticks).max(1);
+        let staves: Vec<(Clef, Vec<Element>)> = score
+            .parts()
+            .iter()
+            .map(|part| {
+                let elements = elements(part, bar_ticks, bars * bar_ticks, key, minor);
+                let steps: Vec<i32> = elements
+                    .iter()
+                    .flat_map(|e| e.heads.iter().map(|h| h.step))
+                    .collect();
+                let average = steps.iter().sum::<i32>() as f64 / steps.len().max(1) as f64;
+                let clef = if !steps.is_empty() && average < MIDDLE_C_STEP as f64 {
+                    Clef::Bass
+                } else {
+                    Clef::Treble
+                };
+                (clef, elements)
+            })
+            .collect();
+
+        // position of the onsets of each bar from the start of the bar, and bar widths
+        let mut columns: Vec<BTreeMap<u64, f64>> = Vec::new();
+        let mut widths = Vec::new();
+        for bar in 0..bars {
+            let (start, end) = (bar * bar_ticks, (bar + 1) * bar_ticks);
+            // the number of accidental columns and the dots at each onset
+            let mut onsets: BTreeMap<u64, (usize, bool)> = BTreeMap::new();
+            let bar_elements = staves.iter().flat_map(|s| &s.1);
+            for element in bar_elements.filter(|e| (start..end).contains(&e.start)) {
+                let onset = onsets.entry(element.start).or_default();
+                let accidentals = element.heads.iter().filter(|h| h.accidental.is_some());
+                onset.0 = onset.0.max(accidentals.count().min(2));
+                onset.1 |= element.value.dotted;
+            }
+            let ticks: Vec<u64> = onsets.keys().copied().chain([end]).collect();
+            let mut positions = BTreeMap::new();
+            let mut x = sp;
+            for (tick, next) in ticks.iter().zip(&ticks[1..]) {
+                let (accidentals, dotted) = onsets[tick];
+                x += accidentals as f64 * 1.1 * sp;
+                positions.insert(*tick, x);
+                let beats = (next - tick) as f64 / TICKS_PER_BEAT as f64;
+                x += sp * (1.8 + 2.2 * beats.sqrt()) + if dotted { 0.6 * sp } else { 0. };
+            }
+            columns.push(positions);
+            widths.push(x);
+        }
+
+        let font_size = 1.4 * sp;
+        let longest_name = score.parts().iter().map(|p| p.name().chars().count());
+        let names_width = longest_name.max().unwrap_or(0) as f64 * 0.6 * font_size;
+        let left = 2. * sp
+            + if names_width > 0. {
+                names_width + sp
+            } else {
+                0.
+            };
+        let header = 4. * sp + key.unsigned_abs() as f64 * sp;
+        let time_width = 3. * sp;
+        let staff_distance = 10. * sp;
+        let system_height = staves.len().max(1) as f64 * staff_distance + 2. * sp;
+        let top = if score.name().is_empty() { 4. } else { 8. } * sp;
+        let lines: Vec<(u64, u64)> = (0..bars)
+            .step_by(self.bars_per_line)
+            .map(|first| (first, (first + self.bars_per_line as u64).min(bars)))
+            .collect();
+        let line_width = |(line, (first, last)): (usize, &(u64, u64))| {
+            let bars_width: f64 = widths[*first as usize..*last as usize].iter().sum();
+            header + if line == 0 { time_width } else { 0. } + bars_width
+        };
+        let content_width = lines.iter().enumerate().map(line_width).fold(0., f64::max);
+        let width = left + content_width + 2. * sp;
+        let height = top + lines.len() as f64 * system_height;
+
+        let mut engraver = Engraver {
+            svg: SvgWriter::new(width, height),
+            sp,
+        };
+        if !score.name().is_empty() {
+            engraver.svg.text(
+                width / 2.,
+                4.5 * sp,
+                score.name(),
+                &format!("font-size=\"{:.1}\" text-anchor=\"middle\"", 2.2 * sp),
+            );
+        }
+        for (line, &(first, last)) in lines.iter().enumerate() {
+            let system_top = top + line as f64 * system_height;
+            let header_end = left + header + if line == 0 { time_width } else { 0. };
+            let mut bar_x = Vec::new();
+            let mut x = header_end;
+            for width in &widths[first as usize..last as usize] {
+                bar_x.push(x);
+                x += width;
+            }
+            let end_x = x;
+            for (index, (clef, elements)) in staves.iter().enumerate() {
+                let staff = Staff {
+                    clef: *clef,
+                    top: system_top + index as f64 * staff_distance,
+                };
+                engraver.staff_lines(left, end_x, staff.top);
+                engraver.clef(staff, left + 1.5 * sp);
+                engraver.key_signature(staff, key, left + 3.5 * sp);
+                if line == 0 {
+                    engraver.time_signature(numerator, denominator, header_end - 1.8 * sp, staff);
+                    let name = score.parts()[index].name();
+                    engraver.svg.text(
+                        left - sp,
+                        staff.top + 2.4 * sp,
+                        name,
+                        &format!("font-size=\"{font_size:.1}\" text-anchor=\"end\""),
+                    );
+                }
+                for (bar, x) in bar_x.iter().enumerate() {
+                    let final_bar = first + bar as u64 + 1 == bars;
+                    engraver.barline(x + widths[first as usize + bar], staff.top, final_bar);
+                }
+                // the elements of the line with their position
+                let placed: Vec<(usize, f64)> = elements
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, e)| (first..last).contains(&(e.start / bar_ticks)))
+                    .map(|(i, e)| {
+                        let bar = e.start / bar_ticks;
+                        let x = if e.bar_rest {
+                            bar_x[(bar - first) as usize] + widths[bar as usize] / 2.
+                                - engraver.head_width() / 2.
+                        } else {
+                            bar_x[(bar - first) as usize] + columns[bar as usize][&e.start]
+                        };
+                        (i, x)
+                    })
+                    .collect();
+                let line_elements = LineElements {
+                    elements,
+                    placed: &placed,
+                    bar_ticks,
+                    beat_ticks,
+                };
+                engraver.elements(staff, &line_elements, (header_end, end_x));
+            }
+            if staves.len() > 1 {
+                let bottom = system_top + (staves.len() - 1) as f64 * staff_distance + 4. * sp;
+                engraver.svg.line(
+                    left,
+                    system_top,
+                    left,
+                    bottom,
+                    &format!(
+                        "class=\"barline\" stroke=\"black\" stroke-width=\"{:.2}\"",
+                        0.15 * sp
+                    ),
+                );
+            }
+        }
+        engraver.svg.finish()
+    }
+
+    /// Returns the staff notation of the `Score` as a PNG image (see `to_svg`)
+    ///
+    /// # Errors
+    ///
+    /// * `RenderError::InvalidImageSize` if the image is too large to be rasterized
+    /// * `RenderError::PngEncoding` if the image cannot be encoded
+    #[cfg(feature = "png")]
+    pub fn to_png(&self, score: &Score) -> crate::Result<Vec<u8>> {
+        crate::render::svg::svg_to_png(&self.to_svg(score))
+    }
+}
+
+/// Describes the position and the clef of a staff
+#[derive(Debug, Clone, Copy)]
+struct Staff {
+    clef: Clef,
+    /// The vertical position of the top line
+    top: f64,
+}
+
+/// The elements of a staff drawn on a line of staves
+struct LineElements<'a> {
+    /// All the elements of the staff
+    elements: &'a [Element],
+    /// The index and horizontal position of the elements of the line
+    placed: &'a [(usize, f64)],
+    bar_ticks: u64,
+    /// The duration of the groups of beamed notes
+    beat_ticks: u64,
+}
+
+/// Draws the symbols of the staff notation
+struct Engraver {
+    svg: SvgWriter,
+    /// The distance between two lines of a staff
+    sp: f64,
+}
+
+impl Engraver {
+    /// Returns the width of a notehead
+    fn head_width(&self) -> f64 {
+        1.3 * self.sp
+    }
+
+    /// Returns the vertical position of a staff step
+    fn y(&self, staff: Staff, step: i32) -> f64 {
+        staff.top + (staff.clef.bottom_step() + 8 - step) as f64 * self.sp / 2.
+    }
+
+    /// Returns the stroke attributes of a line of the given width in staff spaces
+    fn stroke(&self, class: &str, width: f64) -> String {
+        format!(
+            "class=\"{class}\" stroke=\"black\" stroke-width=\"{:.2}\" fill=\"none\"",
+            width * self.sp
+        )
+    }
+
+    fn staff_lines(&mut self, x1: f64, x2: f64, top: f64) {
+        let attributes = self.stroke("staff", 0.1);
+        for line in 0..5 {
+            let y = top + line as f64 * self.sp;
+            self.svg.line(x1, y, x2, y, &attributes);
+        }
+    }
+
+    fn clef(&mut self, staff: Staff, x: f64) {
+        let sp = self.sp;
+        let (origin, data, dots): (i32, &str, &[(f64, f64, f64)]) = match staff.clef {
+            Clef::Treble => (
+                2,
+                "M 0.1 0.7 C -0.7 0.6 -0.7 -0.6 0.1 -0.7 C 0.9 -0.8 1.2 0.6 0.1 0.9 \
+                 C -1.3 1.1 -1.4 -0.9 -0.2 -1.9 C 0.6 -2.6 0.9 -3.5 0.6 -4.3 \
+                 C 0.3 -4.9 -0.3 -4.1 -0.2 -3.0 L 0.4 2.3 C 0.5 3.0 -0.3 3.2 -0.6 2.8",
+                &[(-0.45, 2.65, 0.28)],
+            ),
+            Clef::Bass => (
+                6,
+                "M -0.5 0 C -0.5 -1.1 1.4 -1.3 1.5 0 C 1.6 1.4 0.4 2.4 -0.8 2.9",
+                &[(-0.5, 0., 0.35), (2., -0.5, 0.17), (2., 0.5, 0.17)],
+            ),
+        };
+        let y = self.y(staff, staff.clef.bottom_step() + origin);
+        self.svg.path(
+            data,
+            &format!(
+                "class=\"clef\" transform=\"translate({x:.2} {y:.2}) scale({sp:.2})\" \
+                 stroke=\"black\" stroke-width=\"0.2\" fill=\"none\""
+            ),
+        );
+        for (dx, dy, r) in dots {
+            self.svg.ellipse(
+                x + dx * sp,
+                y + dy * sp,
+                r * sp,
+                r * sp,
+                "class=\"clef\" fill=\"black\"",
+            );
+        }
+    }
+
+    fn key_signature(&mut self, staff: Staff, key: i8, x: f64) {
+        let (steps, accidental) = if key >= 0 {
+            (SHARP_STEPS, Accidental::Sharp)
+        } else {
+            (FLAT_STEPS, Accidental::Flat)
+        };
+        let offset = match staff.clef {
+            Clef::Treble => 0,
+            Clef::Bass => -14,
+        };
+        for (index, step) in steps.iter().take(key.unsigned_abs() as usize).enumerate() {
+            let y = self.y(staff, step + offset);
+            self.accidental(accidental, x + index as f64 * self.sp, y);
+        }
+    }
+
+    fn time_signature(&mut self, numerator: u8, denominator: u8, x: f64, staff: Staff) {
+        let attributes = format!(
+            "class=\"time\" font-size=\"{:.1}\" font-weight=\"bold\" text-anchor=\"middle\"",
+            2.6 * self.sp
+        );
+        for (number, y) in [(numerator, 1.95), (denominator, 3.95)] {
+            let y = staff.top + y * self.sp;
+            self.svg.text(x, y, &number.to_string(), &attributes);
+        }
+    }
+
+    fn barline(&mut self, x: f64, top: f64, final_bar: bool) {
+        let bottom = top + 4. * self.sp;
+        if final_bar {
+            let thick = 0.5 * self.sp;
+            self.svg.rect(
+                x - thick,
+                top,
+                thick,
+                bottom - top,
+                "class=\"barline\" fill=\"black\"",
+            );
+            let x = x - thick - 0.5 * self.sp;
+            self.svg
+                .line(x, top, x, bottom, &self.stroke("barline", 0.15));
+        } else {
+            self.svg
+                .line(x, top, x, bottom, &self.stroke("barline", 0.15));
+        }
+    }
+
+    /// Draws an accidental centered on `(x, y)`
+    fn accidental(&mut self, accidental: Accidental, x: f64, y: f64) {
+        let sp = self.sp;
+        let thin = self.stroke("accidental", 0.12);
+        let thick = self.stroke("accidental", 0.3);
+        match accidental {
+            Accidental::Sharp => {
+                for dx in [-0.22, 0.22] {
+                    let x = x + dx * sp;
+                    self.svg
+                        .line(x, y - 1.3 * sp, x, y + 1.3 * sp - dx * 0.4 * sp, &thin);
+                }
+                for dy in [-0.4, 0.4] {
+                    let y = y + dy * sp;
+                    self.svg.line(
+                        x - 0.5 * sp,
+                        y + 0.15 * sp,
+                        x + 0.5 * sp,
+                        y - 0.15 * sp,
+                        &thick,
+                    );
+                }
+            }
+            Accidental::Flat => {
+                let x = x - 0.25 * sp;
+                self.svg.line(x, y - 1.8 * sp, x, y + 0.5 * sp, &thin);
+                let bowl = format!(
+                    "M {x:.2} {:.2} C {:.2} {:.2} {:.2} {:.2} {x:.2} {:.2}",
+                    y + 0.5 * sp,
+                    x + 1.1 * sp,
+                    y - 0.1 * sp,
+                    x + 0.9 * sp,
+                    y - 0.8 * sp,
+                    y - 0.2 * sp
+                );
+                self.svg.path(&bowl, &self.stroke("accidental", 0.2));
+            }
+            Accidental::Natural => {
+                let (x1, x2) = (x - 0.3 * sp, x + 0.3 * sp);
+                self.svg.line(x1, y - 1.3 * sp, x1, y + 0.6 * sp, &thin);
+                self.svg.line(x2, y - 0.6 * sp, x2, y + 1.3 * sp, &thin);
+                for dy in [-0.4, 0.4] {
+                    let y = y + dy * sp;
+                    self.svg.line(x1, y + 0.12 * sp, x2, y - 0.12 * sp, &thick);
+                }
+            }
+        }
+    }
+
+    /// Draws a rest whose column starts at `x`
+    fn rest(&mut self, value: Value, x: f64, top: f64) {
+        let sp = self.sp;
+        let x = x + self.head_width() / 2.;
+        match value.shape {
+            0 => self.svg.rect(
+                x - 0.25 * sp,
+                top + sp,
+                0.5 * sp,
+                sp,
+                "class=\"rest\" fill=\"black\"",
+            ),
+            1 => self.svg.rect(
+                x - 0.6 * sp,
+                top + sp,
+                1.2 * sp,
+                0.5 * sp,
+                "class=\"rest\" fill=\"black\"",
+            ),
+            2 => self.svg.rect(
+                x - 0.6 * sp,
+                top + 1.5 * sp,
+                1.2 * sp,
+                0.5 * sp,
+                "class=\"rest\" fill=\"black\"",
+            ),
+            3 => {
+                let points = [(-0.3, 0.5), (0.3, 1.3), (-0.2, 2.0), (0.3, 2.7)]
+                    .map(|(dx, dy)| format!("{:.2} {:.2}", x + dx * sp, top + dy * sp));
+                let data = format!(
+                    "M {} L {} L {} L {} C {:.2} {:.2} {:.2} {:.2} {:.2} {:.2}",
+                    points[0],
+                    points[1],
+                    points[2],
+                    points[3],
+                    x - 0.5 * sp,
+                    top + 2.4 * sp,
+                    x - 0.5 * sp,
+                    top + 3.2 * sp,
+                    x,
+                    top + 3.5 * sp
+                );
+                self.svg.path(&data, &self.stroke("rest", 0.3));
+            }
+            _ => {
+                let flags = value.flags();
+                let stem_x = x + 0.5 * sp;
+                let bottom = top + (2.5 + flags as f64 * 0.5) * sp;
+                self.svg.line(
+                    stem_x,
+                    top + 1.2 * sp,
+                    stem_x - 0.4 * sp * flags as f64,
+                    bottom,
+                    &self.stroke("rest", 0.15),
+                );
+                for flag in 0..flags {
+                    let y = top + (1.5 + flag as f64) * sp;
+                    let dot_x = x - 0.2 * sp - 0.4 * sp * flag as f64;
+                    self.svg.ellipse(
+                        dot_x,
+                        y,
+                        0.3 * sp,
+                        0.3 * sp,
+                        "class=\"rest\" fill=\"black\"",
+                    );
+                    let data = format!(
+                        "M {dot_x:.2} {:.2} Q {:.2} {:.2} {:.2} {:.2}",
+                        y + 0.2 * sp,
+                        dot_x + 0.5 * sp,
+                        y + 0.3 * sp,
+                        stem_x - 0.4 * sp * flag as f64,
+                        y - 0.3 * sp
+                    );
+                    self.svg.path(&data, &self.stroke("rest", 0.12));
+                }
+            }
+        }
+        if value.dotted {
+            self.dot(x + 1.1 * sp, top + 1.5 * sp);
+        }
+    }
+
+    fn dot(&mut self, x: f64, y: f64) {
+        let r = 0.2 * self.sp;
+        self.svg.ellipse(x, y, r, r, "class=\"dot\" fill=\"black\"");
+    }
+
+    /// Draws the heads of a note or chord whose column starts at `x`, with their ledger
+    /// lines, accidentals and dots, and its stem up to `stem_end`
+    fn heads(&mut self, staff: Staff, element: &Element, x: f64, stem: (bool, f64)) {
+        let sp = self.sp;
+        let hw = self.head_width();
+        let bottom = staff.clef.bottom_step();
+        let (up, stem_end) = stem;
+        let (lowest, highest) = (
+            element.heads[0].step,
+            element.heads[element.heads.len() - 1].step,
+        );
+
+        // heads a second apart are drawn on both sides of the stem
+        let mut shifted = vec![false; element.heads.len()];
+        let order: Vec<usize> = if up {
+            (0..element.heads.len()).collect()
+        } else {
+            (0..element.heads.len()).rev().collect()
+        };
+        for pair in order.windows(2) {
+            let (previous, current) = (pair[0], pair[1]);
+            let (a, b) = (&element.heads[previous], &element.heads[current]);
+            shifted[current] = (a.step - b.step).abs() == 1 && !shifted[previous];
+        }
+        let shift = if up { hw - 0.12 * sp } else { 0.12 * sp - hw };
+
+        // ledger lines
+        let (left, right) = if shifted.contains(&true) {
+            (x.min(x + shift), x.max(x + shift) + hw)
+        } else {
+            (x, x + hw)
+        };
+        let ledger = self.stroke("ledger", 0.12);
+        let ledger_steps = (lowest..bottom - 1)
+            .chain(bottom + 9..=highest)
+            .filter(|s| (s - bottom).rem_euclid(2) == 0);
+        for step in ledger_steps {
+            let y = self.y(staff, step);
+            self.svg
+                .line(left - 0.4 * sp, y, right + 0.4 * sp, y, &ledger);
+        }
+
+        let mut accidental_column = 0;
+        for (index, head) in element.heads.iter().enumerate().rev() {
+            let y = self.y(staff, head.step);
+            let cx = x + hw / 2. + if shifted[index] { shift } else { 0. };
+            match element.value.shape {
+                0 | 1 => {
+                    self.svg
+                        .ellipse(cx, y, 0.7 * sp, 0.45 * sp, &self.stroke("notehead", 0.22));
+                    if element.value.shape == 0 {
+                        for dx in [-0.95, 0.95] {
+                            let x = cx + dx * sp;
+                            let side = self.stroke("notehead", 0.12);
+                            self.svg.line(x, y - 0.6 * sp, x, y + 0.6 * sp, &side);
+                        }
+                    }
+                }
+                shape => {
+                    let fill = if shape == 2 { "none" } else { "black" };
+                    self.svg.ellipse(
+                        cx,
+                        y,
+                        0.62 * sp,
+                        0.42 * sp,
+                        &format!(
+                            "class=\"notehead\" transform=\"rotate(-20 {cx:.2} {y:.2})\" \
+                             stroke=\"black\" stroke-width=\"{:.2}\" fill=\"{fill}\"",
+                            0.15 * sp
+                        ),
+                    );
+                }
+            }
+            if let Some(accidental) = head.accidental {
+                let column = accidental_column as f64 * 1.1 * sp;
+                self.accidental(accidental, x - 0.8 * sp - column, y);
+                accidental_column = (accidental_column + 1) % 2;
+            }
+            if element.value.dotted {
+                let on_line = (head.step - bottom).rem_euclid(2) == 0;
+                let dot_y = if on_line { y - sp / 2. } else { y };
+                let dot_x = x
+                    + hw
+                    + 0.5 * sp
+                    + if up && shifted.contains(&true) {
+                        hw
+                    } else {
+                        0.
+                    };
+                self.dot(dot_x, dot_y);
+            }
+        }
+
+        if element.value.shape >= 2 {
+            let (stem_x, from) = if up {
+                (x + hw - 0.07 * sp, self.y(staff, lowest))
+            } else {
+                (x + 0.07 * sp, self.y(staff, highest))
+            };
+            self.svg
+                .line(stem_x, from, stem_x, stem_end, &self.stroke("stem", 0.13));
+        }
+    }
+
+    /// Returns the horizontal position of the stem of a note whose column starts at `x`
+    fn stem_x(&self, x: f64, up: bool) -> f64 {
+        if up {
+            x + self.head_width() - 0.07 * self.sp
+        } else {
+            x + 0.07 * self.sp
+        }
+    }
+
+    /// Returns true if the stem of notes between two staff steps goes up
+    fn stem_up(staff: Staff, lowest: i32, highest: i32) -> bool {
+        let middle = staff.clef.bottom_step() + 4;
+        middle - lowest > highest - middle
+    }
+
+    /// Returns the end of a stem of a single note or chord
+    fn stem_end(&self, staff: Staff, element: &Element, up: bool) -> f64 {
+        let middle = self.y(staff, staff.clef.bottom_step() + 4);
+        if up {
+            let highest = element.heads[element.heads.len() - 1].step;
+            (self.y(staff, highest) - 3.5 * self.sp).min(middle)
+        } else {
+            (self.y(staff, element.heads[0].step) + 3.5 * self.sp).max(middle)
+        }
+    }
+
+    fn flags(&mut self, count: usize, stem_x: f64, stem_end: f64, up: bool) {
+        let sp = self.sp;
+        let direction = if up { 1. } else { -1. };
+        for flag in 0..count {
+            let y = stem_end + direction * flag as f64 * 0.8 * sp;
+            let data = format!(
+                "M {stem_x:.2} {y:.2} c 0 {:.2} {:.2} {:.2} {:.2} {:.2}",
+                direction * 1.2 * sp,
+                1.4 * sp,
+                direction * 1.4 * sp,
+                0.9 * sp,
+                direction * 3. * sp
+            );
+            self.svg.path(&data, &self.stroke("flag", 0.25));
+        }
+    }
+
+    /// Draws a beam from `(x1, y1)` to `(x2, y2)` at the given level (0 for the main beam)
+    fn beam(&mut self, from: (f64, f64), to: (f64, f64), up: bool, level: usize) {
+        let sp = self.sp;
+        let (thickness, offset) = if up {
+            (0.5 * sp, level as f64 * 0.8 * sp)
+        } else {
+            (-0.5 * sp, -(level as f64) * 0.8 * sp)
+        };
+        let (y1, y2) = (from.1 + offset, to.1 + offset);
+        let data = format!(
+            "M {:.2} {y1:.2} L {:.2} {y2:.2} L {:.2} {:.2} L {:.2} {:.2} Z",
+            from.0,
+            to.0,
+            to.0,
+            y2 + thickness,
+            from.0,
+            y1 + thickness
+        );
+        self.svg.path(&data, "class=\"beam\" fill=\"black\"");
+    }
+
+    /// Draws a tie between two horizontal positions at the height `y`
+    fn tie(&mut self, x1: f64, x2: f64, y: f64, above: bool) {
+        let sp = self.sp;
+        let direction = if above { -1. } else { 1. };
+        let y = y + direction * 0.6 * sp;
+        let middle = (x1 + x2) / 2.;
+        let data = format!(
+            "M {x1:.2} {y:.2} Q {middle:.2} {:.2} {x2:.2} {y:.2} Q {middle:.2} {:.2} {x1:.2} {y:.2} Z",
+            y + direction * 1.2 * sp,
+            y + direction * 0.9 * sp
+        );
+        self.svg.path(&data, "class=\"tie\" fill=\"black\"");
+    }
+
+    /// Draws the elements of a staff on a line of staves spanning from `bounds.0` (after
+    /// the clef and signatures) to `bounds.1`
+    fn elements(&mut self, staff: Staff, line: &LineElements, bounds: (f64, f64)) {
+        let sp = self.sp;
+        let hw = self.head_width();
+        let placed = line.placed;
+        let element = |k: usize| &line.elements[placed[k].0];
+
+        // stem direction and end of each note, beamed by beat
+        let mut stems: Vec<Option<(bool, f64)>> = (0..placed.len())
+            .map(|k| {
+                let e = element(k);
+                (!e.heads.is_empty()).then(|| {
+                    let up = Self::stem_up(staff, e.heads[0].step, e.heads[e.heads.len() - 1].step);
+                    (up, self.stem_end(staff, e, up))
+                })
+            })
+            .collect();
+        let mut beamed = vec![false; placed.len()];
+        let group_key = |k: usize| {
+            let e = element(k);
+            let beamable = !e.heads.is_empty() && e.value.flags() > 0;
+            beamable.then(|| {
+                (
+                    e.start / line.bar_ticks,
+                    e.start % line.bar_ticks / line.beat_ticks,
+                )
+            })
+        };
+        let mut start = 0;
+        while start < placed.len() {
+            let mut end = start + 1;
+            while end < placed.len()
+                && group_key(start).is_some()
+                && group_key(end) == group_key(start)
+            {
+                end += 1;
+            }
+            if end - start > 1 {
+                self.beam_group(staff, line, start..end, &mut stems);
+                beamed[start..end].fill(true);
+            }
+            start = end;
+        }
+
+        for (k, &(index, x)) in placed.iter().enumerate() {
+            let e = &line.elements[index];
+            let Some((up, stem_end)) = stems[k] else {
+                self.rest(e.value, x, staff.top);
+                continue;
+            };
+            self.heads(staff, e, x, (up, stem_end));
+            if !beamed[k] && e.value.flags() > 0 {
+                self.flags(e.value.flags(), self.stem_x(x, up), stem_end, up);
+            }
+            // ties to the next element, or to the end of the line
+            let next_x = placed.get(k + 1).map_or(bounds.1, |p| p.1 + hw / 2.);
+            for (h, head) in e.heads.iter().enumerate().filter(|(_, h)| h.tie) {
+                let above = if e.heads.len() > 1 {
+                    2 * h >= e.heads.len()
+                } else {
+                    !up
+                };
+                let y = self.y(staff, head.step);
+                self.tie(x + hw / 2. + 0.5 * sp, next_x - 0.5 * sp, y, above);
+            }
+            // ties from the previous line
+            if k == 0 && e.start > 0 {
+                for (h, head) in e.heads.iter().enumerate().filter(|(_, h)| h.tied) {
+                    let above = if e.heads.len() > 1 {
+                        2 * h >= e.heads.len()
+                    } else {
+                        !up
+                    };
+                    let y = self.y(staff, head.step);
+                    self.tie(bounds.0, x + hw / 2. - 0.5 * sp, y, above);
+                }
+            }
+        }
+
+        // triplet marks over the runs of triplets of each bar
+        let mut start = 0;
+        while start < placed.len() {
+            let bar = element(start).start / line.bar_ticks;
+            let mut end = start;
+            while end < placed.len()
+                && element(end).value.triplet
+                && element(end).start / line.bar_ticks == bar
+            {
+                end += 1;
+            }
+            if end == start {
+                start += 1;
+                continue;
+            }
+            let highest = (start..end)
+                .flat_map(|k| {
+                    let heads = element(k).heads.iter().map(|h| self.y(staff, h.step) - sp);
+                    heads.chain(stems[k].map(|s| s.1))
+                })
+                .fold(staff.top - sp, f64::min);
+            let x = (placed[start].1 + placed[end - 1].1 + hw) / 2.;
+            self.svg.text(
+                x,
+                highest - 0.6 * sp,
+                "3",
+                &format!(
+                    "class=\"tuplet\" font-size=\"{:.1}\" font-style=\"italic\" text-anchor=\"middle\"",
+                    1.3 * sp
+                ),
+            );
+            start = end;
+        }
+    }
+
+    /// Computes the stems of a group of beamed notes and draws its beams
+    fn beam_group(
+        &mut self,
+        staff: Staff,
+        line: &LineElements,
+        group: std::ops::Range<usize>,
+        stems: &mut [Option<(bool, f64)>],
+    ) {
+        let sp = self.sp;
+        let elements: Vec<(&Element, f64)> = line.placed[group.clone()]
+            .iter()
+            .map(|&(index, x)| (&line.elements[index], x))
+            .collect();
+        let steps = elements
+            .iter()
+            .flat_map(|(e, _)| e.heads.iter().map(|h| h.step));
+        let lowest = steps.clone().min().unwrap_or(0);
+        let highest = steps.max().unwrap_or(0);
+        let up = Self::stem_up(staff, lowest, highest);
+
+        let (first, last) = (elements[0], elements[elements.len() - 1]);
+        let (x1, x2) = (self.stem_x(first.1, up), self.stem_x(last.1, up));
+        let mut y1 = self.stem_end(staff, first.0, up);
+        let mut y2 = y1 + (self.stem_end(staff, last.0, up) - y1).clamp(-sp, sp);
+        // the beam is moved away from the heads so that all stems are long enough
+        let shift = elements
+            .iter()
+            .map(|(e, x)| {
+                let x = self.stem_x(*x, up);
+                let beam = y1 + (y2 - y1) * (x - x1) / (x2 - x1);
+                if up {
+                    beam - (self.y(staff, e.heads[e.heads.len() - 1].step) - 2.5 * sp)
+                } else {
+                    self.y(staff, e.heads[0].step) + 2.5 * sp - beam
+                }
+            })
+            .fold(0., f64::max);
+        let shift = if up { -shift } else { shift };
+        y1 += shift;
+        y2 += shift;
+        let beam_y = |x: f64| y1 + (y2 - y1) * (x - x1) / (x2 - x1);
+        let stem_xs: Vec<f64> = elements.iter().map(|(_, x)| self.stem_x(*x, up)).collect();
+        for (k, x) in group.clone().zip(&stem_xs) {
+            stems[k] = Some((up, beam_y(*x)));
+        }
+
+        self.beam((x1, y1), (x2, y2), up, 0);
+        // secondary beams between neighbours, or as stubs for isolated shorter notes
+        let flags: Vec<usize> = elements.iter().map(|(e, _)| e.value.flags()).collect();
+        for level in 1..flags.iter().copied().max().unwrap_or(1) {
+            for (k, x) in stem_xs.iter().enumerate() {
+                if flags[k] <= level {
+                    continue;
+                }
+                if flags.get(k + 1).is_some_and(|f| *f > level) {
+                    let next = stem_xs[k + 1];
+                    self.beam((*x, beam_y(*x)), (next, beam_y(next)), up, level);
+                } else if k == 0 || flags[k - 1] <= level {
+                    let stub = if k + 1 < stem_xs.len() { sp } else { -sp };
+                    let end = x + stub;
+                    let (from, to) = if stub > 0. { (*x, end) } else { (end, *x) };
+                    self.beam((from, beam_y(from)), (to, beam_y(to)), up, level);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{spell, StaffNotation, Value};
+    use crate::num::u7;
+    use crate::*;
+
+    #[test]
+    fn staff_notation() -> Result<()> {
+        // in G major: a dotted crotchet and quaver, a rest, a C#, a chord crossing the
+        // barline and two beamed semiquavers
+        let mut melody = Phrase::new();
+        melody.add_note(Note::new(
+            u7::new(67),
+            rhythm::DOTTED_CROTCHET,
+            dynamic::MF,
+        )?);
+        melody.add_note(Note::new(u7::new(66), rhythm::QUAVER, dynamic::MF)?);
+        melody.add_rest(rhythm::CROTCHET);
+        melody.add_note(Note::new(u7::new(61), rhythm::MINIM, dynamic::MF)?);
+        melody.add_chord(Chord::from_pitches(
+            rhythm::MINIM,
+            dynamic::MF,
+            &[60, 64, 67].map(u7::new),
+        )?);
+        melody.add_note(Note::new(u7::new(72), rhythm::SEMIQUAVER, dynamic::MF)?);
+        melody.add_note(Note::new(u7::new(74), rhythm::SEMIQUAVER, dynamic::MF)?);
+        let mut flute = Part::new(Instrument::Flute);
+        flute.set_name("Flute");
+        flute.add_phrase(melody, 0.);
+        let mut bass = Part::new(Instrument::AcousticBass);
+        bass.set_name("Bass");
+        bass.add_phrase(
+            Phrase::from_notes_sequence([Note::new(u7::new(43), rhythm::BREVE, dynamic::MF)])?,
+            0.,
+        );
+        let metadata = Metadata {
+            key_signature: 1,
+            time_numerator: 3,
+            time_denominator: 4,
+            ..Default::default()
+        };
+        let mut score = Score::new("Sketch", Tempo::new(90)?, Some(metadata));
+        score.add_part(flute);
+        score.add_part(bass);
+
+        let svg = StaffNotation::new().to_svg(&score);
+        assert!(svg.starts_with("<svg") && svg.contains(">Sketch</text>"));
+        // 3 bars of 3/4 on 2 staves
+        assert_eq!(svg.matches("class=\"staff\"").count(), 10);
+        assert_eq!(svg.matches(">3</text>").count(), 2);
+        // G, F#, C#, C E G tied across the barline, C, D, and G tied twice on the bass
+        assert_eq!(svg.matches("class=\"notehead\"").count(), 14);
+        assert_eq!(svg.matches("class=\"tie\"").count(), 5);
+        // 4 lines for each of the F# of the key signatures, the C# and the C natural
+        assert_eq!(svg.matches("class=\"accidental\"").count(), 4 * 4);
+        assert_eq!(svg.matches("class=\"rest\"").count(), 3);
+        assert_eq!(svg.matches("class=\"beam\"").count(), 2);
+        assert_eq!(svg.matches("class=\"dot\"").count(), 4);
+        #[cfg(feature = "png")]
+        assert!(StaffNotation::new().to_png(&score)?.starts_with(b"\x89PNG"));
+
+        // durations that no value can write, like quintuplets, are not shortened
+        let written = |ticks| {
+            Value::split(ticks)
+                .iter()
+                .map(|v| v.ticks)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(written(720), vec![720]);
+        assert_eq!(written(96), vec![80, 60]);
+        let mut quintuplets = Phrase::new();
+        for pitch in [60, 62, 64, 65, 67] {
+            quintuplets.add_note(Note::new(u7::new(pitch), 0.4, dynamic::MF)?);
+        }
+        quintuplets.add_rest(rhythm::MINIM);
+        let mut part = Part::new(Instrument::Flute);
+        part.add_phrase(quintuplets, 0.);
+        let mut score = Score::new("Quintuplets", Tempo::new(90)?, None);
+        score.add_part(part);
+        let svg = StaffNotation::new().to_svg(&score);
+        assert_eq!(svg.matches("class=\"notehead\"").count(), 10);
+
+        // chromatic notes are spelled from the key: C# Eb F# Ab Bb in C major, G# in
+        // A minor, E# in F# major, and Cb and B# (in the octaves above and below) in Gb and
+        // C# major
+        let spelled = |pitch, key, minor| spell(u7::new(pitch), key, minor);
+        let c_major: Vec<_> = [61, 63, 66, 68, 70].map(|p| spelled(p, 0, false)).to_vec();
+        let (flat, sharp) = (Accidental::Flat, Accidental::Sharp);
+        assert_eq!(
+            c_major,
+            vec![(35, sharp), (37, flat), (38, sharp), (40, flat), (41, flat)]
+        );
+        assert_eq!(spelled(68, 0, true), (39, sharp));
+        assert_eq!(spelled(65, 6, false), (37, sharp));
+        assert_eq!(spelled(71, -6, false), (42, flat));
+        assert_eq!(spelled(60, 7, false), (34, sharp));
+        Ok(())
+    }
+}
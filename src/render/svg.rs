@@ -0,0 +1,131 @@
+use std::fmt::Write;
+
+/// Builds an SVG document from basic shapes
+pub(crate) struct SvgWriter {
+    width: f64,
+    height: f64,
+    content: String,
+}
+
+impl SvgWriter {
+    /// Returns a new empty document of the given size in pixels, with a white background
+    pub(crate) fn new(width: f64, height: f64) -> Self {
+        let mut svg = Self {
+            width,
+            height,
+            content: String::new(),
+        };
+        svg.rect(0., 0., width, height, "fill=\"white\"");
+        svg
+    }
+
+    /// Adds a rectangle. `attributes` are added to the element as is.
+    pub(crate) fn rect(&mut self, x: f64, y: f64, width: f64, height: f64, attributes: &str) {
+        let _ = writeln!(
+            self.content,
+            "<rect x=\"{x:.2}\" y=\"{y:.2}\" width=\"{width:.2}\" height=\"{height:.2}\" {attributes}/>"
+        );
+    }
+
+    /// Adds a line. `attributes` are added to the element as is.
+    pub(crate) fn line(&mut self, x1: f64, y1: f64, x2: f64, y2: f64, attributes: &str) {
+        let _ = writeln!(
+            self.content,
+            "<line x1=\"{x1:.2}\" y1=\"{y1:.2}\" x2=\"{x2:.2}\" y2=\"{y2:.2}\" {attributes}/>"
+        );
+    }
+
+    /// Adds a text. `attributes` are added to the element as is.
+    pub(crate) fn text(&mut self, x: f64, y: f64, text: &str, attributes: &str) {
+        let _ = writeln!(
+            self.content,
+            "<text x=\"{x:.2}\" y=\"{y:.2}\" {attributes}>{}</text>",
+            escape(text)
+        );
+    }
+
+    /// Adds an ellipse. `attributes` are added to the element as is.
+    pub(crate) fn ellipse(&mut self, cx: f64, cy: f64, rx: f64, ry: f64, attributes: &str) {
+        let _ = writeln!(
+            self.content,
+            "<ellipse cx=\"{cx:.2}\" cy=\"{cy:.2}\" rx=\"{rx:.2}\" ry=\"{ry:.2}\" {attributes}/>"
+        );
+    }
+
+    /// Adds a path from its data (`d` attribute). `attributes` are added to the element as
+    /// is.
+    pub(crate) fn path(&mut self, data: &str, attributes: &str) {
+        let _ = writeln!(self.content, "<path d=\"{data}\" {attributes}/>");
+    }
+
+    /// Adds an element as is
+    pub(crate) fn raw(&mut self, element: &str) {
+        self.content.push_str(element);
+        self.content.push('\n');
+    }
+
+    /// Returns the SVG document
+    pub(crate) fn finish(self) -> String {
+        format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w:.0}\" height=\"{h:.0}\" \
+             viewBox=\"0 0 {w:.0} {h:.0}\" font-family=\"sans-serif\">\n{}</svg>\n",
+            self.content,
+            w = self.width.ceil(),
+            h = self.height.ceil(),
+        )
+    }
+}
+
+/// Escapes the characters of a text that have a meaning in XML
+pub(crate) fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Returns a PNG image of an SVG document. The texts are drawn with the fonts of the
+/// system.
+///
+/// # Errors
+///
+/// * `RenderError::InvalidSvg` if the document cannot be parsed
+/// * `RenderError::InvalidImageSize` if the image is empty or too large
+/// * `RenderError::PngEncoding` if the image cannot be encoded
+#[cfg(feature = "png")]
+pub(crate) fn svg_to_png(svg: &str) -> crate::Result<Vec<u8>> {
+    use crate::errors::RenderError;
+    use resvg::{tiny_skia, usvg};
+
+    let mut options = usvg::Options::default();
+    let fonts = options.fontdb_mut();
+    fonts.load_system_fonts();
+    // fall back to any font of the system when the usual sans-serif fonts are missing
+    let query = usvg::fontdb::Query {
+        families: &[usvg::fontdb::Family::SansSerif],
+        ..Default::default()
+    };
+    if fonts.query(&query).is_none() {
+        let families: Vec<String> = fonts
+            .faces()
+            .filter_map(|f| f.families.first().map(|(name, _)| name.clone()))
+            .collect();
+        let family = families
+            .iter()
+            .find(|name| name.contains("Sans"))
+            .or(families.first())
+            .cloned();
+        if let Some(family) = family {
+            fonts.set_sans_serif_family(family);
+        }
+    }
+    let tree =
+        usvg::Tree::from_str(svg, &options).map_err(|e| RenderError::InvalidSvg(e.to_string()))?;
+    let size = tree.size().to_int_size();
+    let mut pixmap = tiny_skia::Pixmap::new(size.width(), size.height())
+        .ok_or(RenderError::InvalidImageSize(size.width(), size.height()))?;
+    resvg::render(&tree, tiny_skia::Transform::default(), &mut pixmap.as_mut());
+    pixmap
+        .encode_png()
+        .map_err(|e| RenderError::PngEncoding(e.to_string()).into())
+}
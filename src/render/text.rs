@@ -0,0 +1,413 @@
+use std::fmt::Write;
+
+use crate::num::u7;
+use crate::{
+    dynamic, pitch_info, rhythm, Accidental, Instrument, Measures, NoteEvent, NoteEvents, Part,
+    Phrase, PhraseEntry, Score, TimeSignature,
+};
+
+/// Names of the rhythm values of `constants::rhythm`, the plain values first so that
+/// they are preferred to the equal ternary values
+const RHYTHM_NAMES: [(f64, &str); 20] = [
+    (rhythm::DEMI_SEMIQUAVER, "demisemiquaver"),
+    (rhythm::DOTTED_DEMI_SEMIQUAVER, "dotted demisemiquaver"),
+    (rhythm::SEMIQUAVER, "semiquaver"),
+    (rhythm::DOTTED_SEMIQUAVER, "dotted semiquaver"),
+    (rhythm::QUAVER, "quaver"),
+    (rhythm::DOTTED_QUAVER, "dotted quaver"),
+    (rhythm::CROTCHET, "crotchet"),
+    (rhythm::DOTTED_CROTCHET, "dotted crotchet"),
+    (rhythm::MINIM, "minim"),
+    (rhythm::DOTTED_MINIM, "dotted minim"),
+    (rhythm::SEMIBREVE, "semibreve"),
+    (rhythm::BREVE, "breve"),
+    (rhythm::TER_SEMIQUAVER, "triplet semiquaver"),
+    (rhythm::TER_QUAVER, "triplet quaver"),
+    (rhythm::TER_CROTCHET, "triplet crotchet"),
+    (rhythm::TER_MINIM, "triplet minim"),
+    (rhythm::TER_SEMIBREVE, "triplet semibreve"),
+    (rhythm::TER_DOTTED_SEMIQUAVER, "dotted triplet semiquaver"),
+    (rhythm::TER_DOTTED_QUAVER, "dotted triplet quaver"),
+    (rhythm::TER_DOTTED_CROTCHET, "dotted triplet crotchet"),
+];
+
+/// Names of the dynamics of `constants::dynamic`
+const DYNAMIC_NAMES: [(u7, &str); 9] = [
+    (dynamic::SILENT, "silent"),
+    (dynamic::PPP, "ppp"),
+    (dynamic::PP, "pp"),
+    (dynamic::P, "p"),
+    (dynamic::MP, "mp"),
+    (dynamic::MF, "mf"),
+    (dynamic::F, "f"),
+    (dynamic::FF, "ff"),
+    (dynamic::FFF, "fff"),
+];
+
+/// Returns the name of a pitch with its octave (e.g. `C#4` for pitch 49), with the same
+/// octave convention as `compute_pitch`
+pub fn pitch_name(pitch: u7) -> String {
+    let (name, accidental, octave) = pitch_info(pitch, true);
+    let accidental = match accidental {
+        Accidental::Sharp => "#",
+        Accidental::Flat => "b",
+        Accidental::Natural => "",
+    };
+    format!("{}{accidental}{octave}", name.letter())
+}
+
+/// Returns the name of a rhythm value of `constants::rhythm` (e.g. `dotted quaver`), or
+/// the number of beats for other values (e.g. `5 beats`)
+pub fn rhythm_name(rhythm: f64) -> String {
+    RHYTHM_NAMES
+        .iter()
+        .find(|(value, _)| (value - rhythm).abs() < 0.000_001)
+        .map_or_else(|| format!("{} beats", number(rhythm)), |r| r.1.to_string())
+}
+
+/// Returns the name of a dynamic of `constants::dynamic` (e.g. `mf`), or the value with
+/// the closest name for other values (e.g. `72 (~mf)`)
+pub fn dynamic_name(dynamic: u7) -> String {
+    if let Some((_, name)) = DYNAMIC_NAMES.iter().find(|(value, _)| *value == dynamic) {
+        return name.to_string();
+    }
+    let closest = DYNAMIC_NAMES
+        .iter()
+        .min_by_key(|(value, _)| value.as_int().abs_diff(dynamic.as_int()))
+        .map_or("", |d| d.1);
+    format!("{dynamic} (~{closest})")
+}
+
+/// Renders phrases, parts and scores as text for terminals: either a listing with one
+/// entry per line, or a piano-roll grid with one row per pitch.
+///
+/// Positions are written as `bar:beat`, both counted from 1, with beats in the unit of
+/// the time signature (e.g. quavers in 6/8). The time signature of the `Metadata` of a
+/// `Score` is used when it has one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextRenderer {
+    /// The time signature
+    time_signature: TimeSignature,
+    /// The rhythm value of a column of the grids
+    resolution: f64,
+}
+
+impl Default for TextRenderer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TextRenderer {
+    /// Returns a new `TextRenderer` in 4/4 with grids of one column per semiquaver
+    pub fn new() -> Self {
+        Self {
+            time_signature: TimeSignature::default(),
+            resolution: rhythm::SEMIQUAVER,
+        }
+    }
+
+    /// Sets the time signature used when a `Score` has no `Metadata`
+    /// (ignored if the numerator or the denominator is 0)
+    pub fn set_time_signature(&mut self, numerator: u8, denominator: u8) {
+        if let Ok(time_signature) = TimeSignature::new(numerator, denominator) {
+            self.time_signature = time_signature;
+        }
+    }
+
+    /// Sets the rhythm value of a column of the grids
+    pub fn set_resolution(&mut self, resolution: f64) {
+        self.resolution = resolution.max(0.000_001);
+    }
+
+    /// Returns the listing of a `Phrase` starting at beat 0: one entry per line with its
+    /// position, its note names, its rhythm value and its dynamic
+    pub fn phrase_listing(&self, phrase: &Phrase) -> String {
+        let mut listing = String::new();
+        self.write_phrase(&mut listing, phrase, 0., "");
+        listing
+    }
+
+    /// Returns the listing of the phrases of a `Part` (see `phrase_listing`)
+    pub fn part_listing(&self, part: &Part) -> String {
+        let mut listing = String::new();
+        self.write_part(&mut listing, part, self.time_signature);
+        listing
+    }
+
+    /// Returns the listing of the parts of a `Score` (see `phrase_listing`)
+    pub fn score_listing(&self, score: &Score) -> String {
+        let signature = self.signature_of(score);
+        let mut listing = format!(
+            "Score \"{}\": {} bpm, {}/{}, {} beats\n",
+            score.name(),
+            score.tempo(),
+            signature.numerator(),
+            signature.denominator(),
+            number(score.duration())
+        );
+        for part in score.parts() {
+            self.write_part(&mut listing, part, signature);
+        }
+        listing
+    }
+
+    /// Returns the piano-roll grid of a `Phrase`: one row per pitch, one column per
+    /// `resolution`, with `#` at the start of the notes, `=` while they sound and `|` at
+    /// the bar lines
+    pub fn phrase_grid(&self, phrase: &Phrase) -> String {
+        let mut part = Part::new(Instrument::None);
+        part.add_phrase(phrase.clone(), 0.);
+        self.part_grid(&part)
+    }
+
+    /// Returns the piano-roll grid of a `Part` (see `phrase_grid`)
+    pub fn part_grid(&self, part: &Part) -> String {
+        let events: Vec<NoteEvent> = NoteEvents::from_parts([(0, part)], 60).collect();
+        self.grid(&events, part.duration(), self.time_signature, false)
+    }
+
+    /// Returns the piano-roll grid of a `Score` (see `phrase_grid`). The start of the notes
+    /// is marked by the number of their part (from 1, modulo 10) instead of `#`.
+    pub fn score_grid(&self, score: &Score) -> String {
+        let events: Vec<NoteEvent> = score.events().collect();
+        self.grid(
+            &events,
+            score.duration(),
+            self.signature_of(score),
+            score.parts().len() > 1,
+        )
+    }
+
+    /// Returns the time signature of a `Score`
+    fn signature_of(&self, score: &Score) -> TimeSignature {
+        score
+            .metadata()
+            .and_then(|m| TimeSignature::new(m.time_numerator, m.time_denominator).ok())
+            .unwrap_or(self.time_signature)
+    }
+
+    /// Writes the listing of a part
+    fn write_part(&self, listing: &mut String, part: &Part, signature: TimeSignature) {
+        let renderer = Self {
+            time_signature: signature,
+            ..self.clone()
+        };
+        let instrument = if part.is_percussion() {
+            "Percussion".to_string()
+        } else {
+            format!("{:?}", part.instrument())
+        };
+        let _ = writeln!(listing, "Part \"{}\" ({instrument})", part.name());
+        for (index, (start, phrase)) in part.phrases().iter().enumerate() {
+            let _ = writeln!(
+                listing,
+                "  Phrase {} \"{}\" at {}",
+                index + 1,
+                phrase.name(),
+                renderer.position(*start)
+            );
+            renderer.write_phrase(listing, phrase, *start, "    ");
+        }
+    }
+
+    /// Writes the entries of a phrase starting at beat `start`, one per line
+    fn write_phrase(&self, listing: &mut String, phrase: &Phrase, start: f64, indent: &str) {
+        let mut time = start;
+        for entry in phrase.entries() {
+            let description = match entry {
+                PhraseEntry::Rest(r) => format!("{:<16} {}", "rest", rhythm_name(*r)),
+                PhraseEntry::Note(n) => format!(
+                    "{:<16} {:<16} {}",
+                    pitch_name(n.pitch()),
+                    rhythm_name(n.rhythm()),
+                    dynamic_name(n.dynamic())
+                ),
+                PhraseEntry::Chord(c) => {
+                    // the notes lasting longer than the chord show their own length
+                    let names: Vec<String> = c
+                        .notes()
+                        .iter()
+                        .map(|n| {
+                            let name = pitch_name(n.pitch());
+                            if (n.rhythm() - c.rhythm()).abs() < 0.000_001 {
+                                name
+                            } else {
+                                format!("{name}({})", rhythm_name(n.rhythm()))
+                            }
+                        })
+                        .collect();
+                    let loudest = c.notes().iter().map(|n| n.dynamic()).max();
+                    format!(
+                        "{:<16} {:<16} {}",
+                        names.join(" "),
+                        rhythm_name(c.rhythm()),
+                        loudest.map(dynamic_name).unwrap_or_default()
+                    )
+                }
+            };
+            let _ = writeln!(
+                listing,
+                "{indent}{:<9} {}",
+                self.position(time),
+                description.trim_end()
+            );
+            time += entry.rhythm();
+        }
+    }
+
+    /// Writes the grid of the events
+    fn grid(
+        &self,
+        events: &[NoteEvent],
+        duration: f64,
+        signature: TimeSignature,
+        numbered: bool,
+    ) -> String {
+        let Some(lowest) = events.iter().map(|e| e.pitch().as_int()).min() else {
+            return String::new();
+        };
+        let highest = events
+            .iter()
+            .map(|e| e.pitch().as_int())
+            .max()
+            .unwrap_or(lowest);
+        let columns = (duration / self.resolution - 0.000_001).ceil().max(1.) as usize;
+        let column = |beat: f64| (beat / self.resolution).round() as usize;
+        let mut rows = vec![vec!['.'; columns]; (highest - lowest + 1) as usize];
+        for event in events {
+            let row = &mut rows[(highest - event.pitch().as_int()) as usize];
+            let start = column(event.start).min(columns - 1);
+            let end = column(event.end).clamp(start + 1, columns);
+            for cell in row[start + 1..end].iter_mut() {
+                if *cell == '.' {
+                    *cell = '=';
+                }
+            }
+            row[start] = if numbered {
+                char::from_digit((event.part as u32 + 1) % 10, 10).unwrap_or('#')
+            } else {
+                '#'
+            };
+        }
+
+        let bar_length = signature.bar_length();
+        let is_bar_line = |c: usize| {
+            let beats = c as f64 * self.resolution / bar_length;
+            (beats - beats.round()).abs() < 0.000_001
+        };
+        // the bar numbers are written from the bar lines, unless the previous one is too long
+        let mut header = " ".repeat(5);
+        let mut position = header.len();
+        let mut bar = 1;
+        for c in 0..columns {
+            if is_bar_line(c) {
+                if header.len() <= position {
+                    header.push_str(&" ".repeat(position - header.len()));
+                    let _ = write!(header, "|{bar}");
+                }
+                bar += 1;
+                position += 1;
+            }
+            position += 1;
+        }
+        let mut grid = header.trim_end().to_string();
+        grid.push('\n');
+        for (index, row) in rows.iter().enumerate() {
+            let _ = write!(grid, "{:<4} ", pitch_name(u7::new(highest - index as u8)));
+            for (c, cell) in row.iter().enumerate() {
+                if is_bar_line(c) {
+                    grid.push('|');
+                }
+                grid.push(*cell);
+            }
+            grid.push_str("|\n");
+        }
+        grid
+    }
+
+    /// Returns the position of a beat as `bar:beat`
+    fn position(&self, beat: f64) -> String {
+        let (bar, offset) = Measures::new(self.time_signature).position(beat);
+        let in_bar = offset / self.time_signature.unit_length() + 1.;
+        format!("{}:{}", bar + 1, number(in_bar))
+    }
+}
+
+/// Returns a number without trailing zeros (at most 3 decimals)
+fn number(value: f64) -> String {
+    let text = format!("{value:.3}");
+    text.trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{dynamic_name, rhythm_name, TextRenderer};
+    use crate::num::u7;
+    use crate::*;
+
+    #[test]
+    fn text_rendering() -> Result<()> {
+        assert_eq!(rhythm_name(rhythm::DOTTED_QUAVER), "dotted quaver");
+        assert_eq!(rhythm_name(rhythm::TER_QUAVER), "triplet quaver");
+        assert_eq!(rhythm_name(5.), "5 beats");
+        assert_eq!(dynamic_name(dynamic::MF), "mf");
+        assert_eq!(dynamic_name(u7::new(72)), "72 (~mf)");
+
+        let mut phrase = Phrase::new();
+        phrase.set_name("theme");
+        phrase.add_note(Note::new(u7::new(60), rhythm::DOTTED_CROTCHET, dynamic::F)?);
+        phrase.add_note(Note::new(u7::new(62), rhythm::QUAVER, dynamic::MF)?);
+        phrase.add_rest(rhythm::CROTCHET);
+        phrase.add_chord(Chord::new(
+            rhythm::MINIM,
+            vec![
+                Note::new(u7::new(64), rhythm::MINIM, dynamic::MF)?,
+                Note::new(u7::new(67), rhythm::SEMIBREVE, dynamic::MF)?,
+            ],
+        )?);
+        let renderer = TextRenderer::new();
+        assert_eq!(
+            renderer.phrase_listing(&phrase),
+            "1:1       C5               dotted crotchet  f\n\
+             1:2.5     D5               quaver           mf\n\
+             1:3       rest             crotchet\n\
+             1:4       E5 G5(semibreve) minim            mf\n"
+        );
+
+        let mut part = Part::new(Instrument::Violin);
+        part.set_name("Violin");
+        part.add_phrase(phrase.clone(), 2.);
+        let metadata = Metadata {
+            time_numerator: 6,
+            time_denominator: 8,
+            ..Default::default()
+        };
+        let mut score = Score::new("Sketch", Tempo::new(90)?, Some(metadata));
+        score.add_part(part);
+        let listing = renderer.score_listing(&score);
+        assert!(listing.starts_with("Score \"Sketch\": 90 bpm, 6/8, 7 beats\nPart \"Violin\""));
+        assert!(listing.contains("  Phrase 1 \"theme\" at 1:5\n    1:5       C5"));
+        assert!(listing.contains("    2:5       E5 G5"));
+
+        let mut short = Phrase::new();
+        short.add_note(Note::new(u7::new(62), rhythm::QUAVER, dynamic::MF)?);
+        short.add_note(Note::new(
+            u7::new(60),
+            rhythm::DOTTED_CROTCHET,
+            dynamic::MF,
+        )?);
+        short.add_note(Note::new(u7::new(62), rhythm::MINIM, dynamic::MF)?);
+        let mut grid_renderer = TextRenderer::new();
+        grid_renderer.set_time_signature(2, 4);
+        grid_renderer.set_resolution(rhythm::QUAVER);
+        assert_eq!(
+            grid_renderer.phrase_grid(&short),
+            "     |1   |2\n\
+             D5   |#...|#===|\n\
+             C#5  |....|....|\n\
+             C5   |.#==|....|\n"
+        );
+        Ok(())
+    }
+}
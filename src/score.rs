@@ -5,13 +5,19 @@ use crate::errors::ToMidiConversionError;
 use crate::num::*;
 use crate::Instrument;
 use crate::Part;
-use crate::PhraseEntry;
 use crate::Result;
+use crate::TICKS_PER_BEAT;
 
 use crate::midly::{
     Format, Header, MetaMessage, MidiMessage, Smf, Timing, TrackEvent, TrackEventKind,
 };
 
+/// MIDI channel reserved for percussion in General MIDI (channel 10 counting from 1)
+const PERCUSSION_CHANNEL: usize = 9;
+
+/// The NoteOn and NoteOff events of a track at each tick
+type EventsPerTime<'a> = BTreeMap<u64, (Vec<TrackEvent<'a>>, Vec<TrackEvent<'a>>)>;
+
 /// Describes the scale mode (Major or Minor, other modes are not specified)
 #[derive(Debug, Default, Clone, PartialEq, Eq)]
 pub enum Mode {
@@ -90,7 +96,8 @@ impl Score {
     /// Adds a `Part` to the `Score`.
     /// Warning: q Score can contain unlimited Parts but if exporting to
     /// Standard MIDI File, any Score with more than 16 Parts will fail
-    /// because MIDI only supports 16 channels.
+    /// because MIDI only supports 16 channels. If the Score contains percussion
+    /// Parts, they share the percussion channel and the other Parts are limited to 15.
     pub fn add_part(&mut self, part: Part) {
         self.duration = self.duration.max(part.duration());
         self.parts.push(part);
@@ -152,12 +159,12 @@ impl<'a> TryFrom<&'a Score> for Smf<'a> {
     ///
     /// # Errors
     ///
-    /// Returns `ToMidiConversionError`
+    /// Returns `ToMidiConversionError::TooManyParts` if there are more than 16 parts, or
+    /// `ToMidiConversionError::TooManyMelodicParts` if there are percussion parts and
+    /// more than 15 other parts
     /// TODO: complete errors description
     fn try_from(score: &'a Score) -> Result<Smf<'a>> {
-        if score.parts.len() > 16 {
-            return Err(ToMidiConversionError::TooManyParts(score.parts.len()).into());
-        }
+        let channels = midi_channels(score.parts())?;
 
         let header = Header {
             format: if score.parts().len() == 1 {
@@ -165,7 +172,7 @@ impl<'a> TryFrom<&'a Score> for Smf<'a> {
             } else {
                 Format::Parallel
             },
-            timing: Timing::Metrical(u15::from(480)),
+            timing: Timing::Metrical(u15::new(TICKS_PER_BEAT as u16)),
         };
         let mut metadata_events = vec![
             TrackEvent {
@@ -199,81 +206,43 @@ impl<'a> TryFrom<&'a Score> for Smf<'a> {
 
         let mut tracks = Vec::new();
 
-        for (channel, part) in score.parts().iter().enumerate() {
-            let mut notes_per_time: BTreeMap<u64, (Vec<TrackEvent>, Vec<TrackEvent>)> =
-                BTreeMap::new();
-            for phrase in part.phrases() {
-                let mut cur_time = (phrase.0 * 480.).round() as u64;
-                for phrase_entry in phrase.1.entries() {
-                    match phrase_entry {
-                        PhraseEntry::Chord(c) => {
-                            notes_per_time.entry(cur_time).or_default().0.extend(
-                                c.notes().iter().map(|n| TrackEvent {
-                                    delta: u28::default(),
-                                    kind: TrackEventKind::Midi {
-                                        channel: u4::new(channel as u8),
-                                        message: MidiMessage::NoteOn {
-                                            key: n.pitch(),
-                                            vel: n.dynamic(),
-                                        },
-                                    },
-                                }),
-                            );
-                            for n in c.notes() {
-                                notes_per_time
-                                    .entry(cur_time + (n.rhythm() * 480.).round() as u64)
-                                    .or_default()
-                                    .1
-                                    .push(TrackEvent {
-                                        delta: u28::default(),
-                                        kind: TrackEventKind::Midi {
-                                            channel: u4::new(channel as u8),
-                                            message: MidiMessage::NoteOff {
-                                                key: n.pitch(),
-                                                vel: u7::default(),
-                                            },
-                                        },
-                                    })
-                            }
-                            cur_time += (c.rhythm() * 480.).round() as u64;
-                        }
-                        PhraseEntry::Note(n) => {
-                            notes_per_time
-                                .entry(cur_time)
-                                .or_default()
-                                .0
-                                .push(TrackEvent {
-                                    delta: u28::default(),
-                                    kind: TrackEventKind::Midi {
-                                        channel: u4::new(channel as u8),
-                                        message: MidiMessage::NoteOn {
-                                            key: n.pitch(),
-                                            vel: n.dynamic(),
-                                        },
-                                    },
-                                });
-                            notes_per_time
-                                .entry(cur_time + (n.rhythm() * 480.).round() as u64)
-                                .or_default()
-                                .1
-                                .push(TrackEvent {
-                                    delta: u28::default(),
-                                    kind: TrackEventKind::Midi {
-                                        channel: u4::new(channel as u8),
-                                        message: MidiMessage::NoteOff {
-                                            key: n.pitch(),
-                                            vel: u7::default(),
-                                        },
-                                    },
-                                });
-                            cur_time += (n.rhythm() * 480.).round() as u64;
-                        }
-                        PhraseEntry::Rest(r) => {
-                            cur_time += (r * 480.).round() as u64;
-                        }
-                    };
-                }
-            }
+        let mut notes_per_part: Vec<EventsPerTime> = vec![BTreeMap::new(); score.parts().len()];
+        for event in score.events() {
+            let channel = u4::new(channels[event.part] as u8);
+            let notes_per_time = &mut notes_per_part[event.part];
+            notes_per_time
+                .entry(event.start_tick)
+                .or_default()
+                .0
+                .push(TrackEvent {
+                    delta: u28::default(),
+                    kind: TrackEventKind::Midi {
+                        channel,
+                        message: MidiMessage::NoteOn {
+                            key: event.pitch(),
+                            vel: event.velocity(),
+                        },
+                    },
+                });
+            notes_per_time
+                .entry(event.end_tick)
+                .or_default()
+                .1
+                .push(TrackEvent {
+                    delta: u28::default(),
+                    kind: TrackEventKind::Midi {
+                        channel,
+                        message: MidiMessage::NoteOff {
+                            key: event.pitch(),
+                            vel: u7::default(),
+                        },
+                    },
+                });
+        }
+
+        for ((channel, part), notes_per_time) in
+            channels.into_iter().zip(score.parts()).zip(notes_per_part)
+        {
             // TODO: investigate if the usage of `round` on the time value (in ticks) can cause issues
             if notes_per_time.is_empty() {
                 continue;
@@ -281,7 +250,7 @@ impl<'a> TryFrom<&'a Score> for Smf<'a> {
 
             let mut track = metadata_events.clone();
             let part_instrument = part.instrument();
-            if !matches!(part_instrument, Instrument::None) {
+            if !part.is_percussion() && !matches!(part_instrument, Instrument::None) {
                 track.push(TrackEvent {
                     delta: u28::default(),
                     kind: TrackEventKind::Midi {
@@ -323,3 +292,69 @@ impl<'a> TryFrom<&'a Score> for Smf<'a> {
         Ok(Smf { header, tracks })
     }
 }
+
+/// Returns the MIDI channel of each `Part`. Without percussion parts, the channel of a
+/// part is its index. Otherwise, percussion parts share channel 10 (`9` when counting
+/// from 0) and the other parts get the remaining channels in order.
+fn midi_channels(parts: &[Part]) -> Result<Vec<usize>> {
+    if !parts.iter().any(Part::is_percussion) {
+        if parts.len() > 16 {
+            return Err(ToMidiConversionError::TooManyParts(parts.len()).into());
+        }
+        return Ok((0..parts.len()).collect());
+    }
+    let melodic = parts.iter().filter(|p| !p.is_percussion()).count();
+    if melodic > 15 {
+        return Err(ToMidiConversionError::TooManyMelodicParts(melodic).into());
+    }
+    let mut next_channel = 0;
+    Ok(parts
+        .iter()
+        .map(|part| {
+            if part.is_percussion() {
+                return PERCUSSION_CHANNEL;
+            }
+            let channel = next_channel;
+            next_channel += if next_channel + 1 == PERCUSSION_CHANNEL {
+                2
+            } else {
+                1
+            };
+            channel
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::midi_channels;
+    use crate::errors::ToMidiConversionError;
+    use crate::*;
+
+    #[test]
+    fn midi_channels_with_percussion() -> Result<()> {
+        let parts = vec![Part::new(Instrument::Violin); 16];
+        assert_eq!(midi_channels(&parts)?, (0..16).collect::<Vec<_>>());
+        let mut parts = vec![Part::new(Instrument::Violin); 11];
+        parts.insert(1, Part::new_percussion());
+        parts.push(Part::new_percussion());
+        assert_eq!(
+            midi_channels(&parts)?,
+            vec![0, 9, 1, 2, 3, 4, 5, 6, 7, 8, 10, 11, 9]
+        );
+        parts.extend(vec![Part::new(Instrument::Violin); 5]);
+        assert!(matches!(
+            midi_channels(&parts),
+            Err(Error::ToMidiConversion(
+                ToMidiConversionError::TooManyMelodicParts(16)
+            ))
+        ));
+        assert!(matches!(
+            midi_channels(&vec![Part::new(Instrument::Violin); 17]),
+            Err(Error::ToMidiConversion(
+                ToMidiConversionError::TooManyParts(17)
+            ))
+        ));
+        Ok(())
+    }
+}
@@ -0,0 +1,175 @@
+use crate::{NoteEvent, NoteEvents, Part, Score, TICKS_PER_BEAT};
+
+/// Describes a time segment in which the set of sounding notes is constant
+#[derive(Debug, Clone, PartialEq)]
+pub struct Slice<'a> {
+    /// The beat at which the segment starts
+    pub start: f64,
+    /// The beat at which the segment ends
+    pub end: f64,
+    /// The notes sounding during the whole segment, sorted by part then by pitch
+    /// (empty during silences). They can start before and end after the segment.
+    pub notes: Vec<NoteEvent<'a>>,
+}
+
+impl Slice<'_> {
+    /// Returns the duration of the segment in beats
+    pub fn duration(&self) -> f64 {
+        self.end - self.start
+    }
+
+    /// Returns true if a note starts at the beginning of the segment
+    pub fn has_onset(&self) -> bool {
+        self.notes
+            .iter()
+            .any(|n| (n.start - self.start).abs() < 0.5 / TICKS_PER_BEAT as f64)
+    }
+}
+
+/// Iterator over the vertical slices of a `Score` or a `Part`, in time order.
+///
+/// The slices cover the whole duration without gaps: a new slice starts each time a
+/// note starts or ends. The notes of a `Chord` sound for their own rhythm value, even
+/// when the `Chord` rhythm is shorter, and notes of overlapping `Phrase`s are all
+/// included. Times are those of `NoteEvents` and notes that round to no duration are
+/// ignored.
+#[derive(Debug, Clone)]
+pub struct Slices<'a> {
+    /// The notes sorted by start
+    notes: Vec<NoteEvent<'a>>,
+    /// The times in ticks at which a slice starts or ends
+    boundaries: Vec<u64>,
+    /// The index of the next slice
+    index: usize,
+    /// The index in `notes` of the next note to start
+    next_note: usize,
+    /// The notes sounding in the previous slice
+    active: Vec<NoteEvent<'a>>,
+}
+
+impl<'a> Slices<'a> {
+    /// Returns the slices of the given notes
+    fn new(events: NoteEvents<'a>) -> Self {
+        let mut notes: Vec<NoteEvent> = events.filter(|e| e.end_tick > e.start_tick).collect();
+        notes.sort_by_key(|n| n.start_tick);
+        let mut boundaries: Vec<u64> = notes
+            .iter()
+            .flat_map(|n| [n.start_tick, n.end_tick])
+            .collect();
+        boundaries.push(0);
+        boundaries.sort_unstable();
+        boundaries.dedup();
+        Self {
+            notes,
+            boundaries,
+            index: 0,
+            next_note: 0,
+            active: Vec::new(),
+        }
+    }
+}
+
+impl<'a> Iterator for Slices<'a> {
+    type Item = Slice<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (start, end) = (
+            *self.boundaries.get(self.index)?,
+            *self.boundaries.get(self.index + 1)?,
+        );
+        self.index += 1;
+        self.active.retain(|n| n.end_tick > start);
+        while let Some(note) = self
+            .notes
+            .get(self.next_note)
+            .filter(|n| n.start_tick <= start)
+        {
+            self.active.push(*note);
+            self.next_note += 1;
+        }
+        self.active
+            .sort_by_key(|n| (n.part, n.pitch(), n.start_tick));
+        Some(Slice {
+            start: start as f64 / TICKS_PER_BEAT as f64,
+            end: end as f64 / TICKS_PER_BEAT as f64,
+            notes: self.active.clone(),
+        })
+    }
+}
+
+impl Score {
+    /// Returns an iterator over the time segments in which the set of sounding notes of
+    /// the `Score` is constant (see `Slices`)
+    pub fn slices(&self) -> Slices<'_> {
+        Slices::new(self.events())
+    }
+}
+
+impl Part {
+    /// Returns an iterator over the time segments in which the set of sounding notes of
+    /// the `Part` is constant (see `Slices`)
+    pub fn slices(&self) -> Slices<'_> {
+        Slices::new(NoteEvents::from_parts([(0, self)], 60))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::num::u7;
+    use crate::*;
+
+    fn pitches(slice: &Slice) -> Vec<(usize, u8)> {
+        slice
+            .notes
+            .iter()
+            .map(|n| (n.part, n.note.pitch().as_int()))
+            .collect()
+    }
+
+    #[test]
+    fn slices() -> Result<()> {
+        // a C major chord whose rhythm is shorter than its notes, then a D
+        let mut upper = Phrase::new();
+        upper.add_chord(Chord::new(
+            rhythm::CROTCHET,
+            vec![
+                Note::new(u7::new(60), rhythm::MINIM, dynamic::MF)?,
+                Note::new(u7::new(64), rhythm::CROTCHET, dynamic::MF)?,
+            ],
+        )?);
+        upper.add_note(Note::new(u7::new(62), rhythm::CROTCHET, dynamic::MF)?);
+        let mut piano = Part::new(Instrument::AcousticGrandPiano);
+        piano.add_phrase(upper, 0.);
+        // an overlapping phrase in the same part
+        let mut overlap = Phrase::new();
+        overlap.add_note(Note::new(u7::new(67), rhythm::MINIM, dynamic::MF)?);
+        piano.add_phrase(overlap, 1.5);
+
+        let mut bass = Part::new(Instrument::AcousticBass);
+        let mut line = Phrase::new();
+        line.add_rest(rhythm::QUAVER);
+        line.add_note(Note::new(u7::new(36), rhythm::CROTCHET, dynamic::MF)?);
+        bass.add_phrase(line, 0.);
+
+        let mut score = Score::new("slices", Tempo::new(120)?, None);
+        score.add_part(piano.clone());
+        score.add_part(bass);
+
+        let slices: Vec<Slice> = score.slices().collect();
+        let bounds: Vec<(f64, f64)> = slices.iter().map(|s| (s.start, s.end)).collect();
+        assert_eq!(
+            bounds,
+            vec![(0., 0.5), (0.5, 1.), (1., 1.5), (1.5, 2.), (2., 3.5)]
+        );
+        assert_eq!(pitches(&slices[0]), vec![(0, 60), (0, 64)]);
+        assert_eq!(pitches(&slices[1]), vec![(0, 60), (0, 64), (1, 36)]);
+        assert_eq!(pitches(&slices[2]), vec![(0, 60), (0, 62), (1, 36)]);
+        assert_eq!(slices[2].notes[0].start, 0.);
+        assert_eq!(pitches(&slices[3]), vec![(0, 60), (0, 62), (0, 67)]);
+        assert_eq!(pitches(&slices[4]), vec![(0, 67)]);
+        assert!(slices[3].has_onset());
+
+        assert_eq!(piano.slices().count(), 4);
+        Ok(())
+    }
+}